@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
+};
+
+use super::{depfile_prerequisites, Edge, Graph};
+use crate::{build_dir::BuildLog, info, log};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    NotVisited,
+    /// Its dependencies are being built, reaching it again is a cycle
+    Visiting,
+    Done,
+}
+
+/// State of one build
+struct Build<'a> {
+    graph: &'a Graph,
+    build_dir: &'a Path,
+    /// Edge producing each output
+    producers: HashMap<&'a Path, usize>,
+    status: Vec<Status>,
+    /// Outputs of the edges being visited, for reporting cycles
+    stack: Vec<&'a Path>,
+    log: BuildLog,
+    ran: usize,
+}
+
+/// Bring `targets` up to date in `build_dir`, which are outputs of the graph
+/// or files that must exist. Returns how many commands ran.
+pub fn build(graph: &Graph, build_dir: &Path, targets: &[PathBuf]) -> Result<usize, String> {
+    let mut producers = HashMap::new();
+    for (i, edge) in graph.edges.iter().enumerate() {
+        for output in &edge.outputs {
+            if producers.insert(output.as_path(), i).is_some() {
+                return Err(format!("Multiple commands generate {}", output.display()));
+            }
+        }
+    }
+
+    let mut build = Build {
+        graph,
+        build_dir,
+        producers,
+        status: vec![Status::NotVisited; graph.edges.len()],
+        stack: vec![],
+        log: BuildLog::load(build_dir),
+        ran: 0,
+    };
+    for target in targets {
+        build.file(target, None)?;
+    }
+
+    if build.ran == 0 {
+        info!("Nothing to be done");
+    }
+    Ok(build.ran)
+}
+
+/// When a file was last modified, `None` if it doesn't exist
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl<'a> Build<'a> {
+    fn file(&mut self, path: &'a Path, needed_by: Option<&Path>) -> Result<(), String> {
+        if let Some(&edge) = self.producers.get(path) {
+            return self.edge(edge);
+        }
+        if self.build_dir.join(path).exists() {
+            return Ok(());
+        }
+
+        Err(match needed_by {
+            Some(output) => format!(
+                "'{}', needed by '{}', missing and no known rule to make it",
+                path.display(),
+                output.display()
+            ),
+            None => format!("Unknown target '{}'", path.display()),
+        })
+    }
+
+    fn edge(&mut self, index: usize) -> Result<(), String> {
+        let graph = self.graph;
+        let edge = &graph.edges[index];
+        let output = edge.outputs[0].as_path();
+        match self.status[index] {
+            Status::Done => return Ok(()),
+            Status::Visiting => {
+                let start = self.stack.iter().position(|o| *o == output).unwrap();
+                let mut cycle = self.stack[start..]
+                    .iter()
+                    .map(|o| o.display().to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(output.display().to_string());
+                return Err(format!("Dependency cycle: {}", cycle.join(" -> ")));
+            }
+            Status::NotVisited => {}
+        }
+
+        self.status[index] = Status::Visiting;
+        self.stack.push(output);
+        for dependency in edge
+            .inputs
+            .iter()
+            .chain(&edge.implicit)
+            .chain(&edge.order_only)
+        {
+            self.file(dependency, Some(output))?;
+        }
+        self.stack.pop();
+
+        if self.is_dirty(edge) {
+            self.run(edge)?;
+        }
+        self.status[index] = Status::Done;
+        Ok(())
+    }
+
+    /// Everything that decides whether the outputs are current, changing
+    /// any of it runs the command again
+    fn command_line(edge: &Edge) -> String {
+        let mut line = edge.command.join("\0");
+        if edge.capture {
+            line += "\0>";
+        }
+        if edge.feed {
+            line += "\0<";
+        }
+        line
+    }
+
+    fn is_dirty(&self, edge: &Edge) -> bool {
+        if edge.always {
+            return true;
+        }
+        let Some(oldest) = edge
+            .outputs
+            .iter()
+            .map(|o| modified(&self.build_dir.join(o)))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|times| times.into_iter().min())
+        else {
+            return true;
+        };
+        if !self
+            .log
+            .is_current(&edge.outputs[0], &Self::command_line(edge))
+        {
+            return true;
+        }
+
+        let mut prerequisites = edge
+            .inputs
+            .iter()
+            .chain(&edge.implicit)
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(depfile) = &edge.depfile {
+            let Ok(contents) = fs::read_to_string(self.build_dir.join(depfile)) else {
+                return true;
+            };
+            prerequisites.extend(
+                depfile_prerequisites(&contents)
+                    .into_iter()
+                    .map(PathBuf::from),
+            );
+        }
+
+        prerequisites
+            .iter()
+            .any(|p| modified(&self.build_dir.join(p)).is_none_or(|time| time > oldest))
+    }
+
+    fn run(&mut self, edge: &Edge) -> Result<(), String> {
+        self.ran += 1;
+        info!("[{}] {}", self.ran, edge.description);
+
+        for output in &edge.outputs {
+            let path = self.build_dir.join(output);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+            }
+            // Commands such as ar add to what's there
+            if fs::symlink_metadata(&path).is_ok() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Could not remove {}: {}", path.display(), e))?;
+            }
+        }
+
+        let Some((program, args)) = edge.command.split_first() else {
+            return Err(format!("No command to build {}", edge.outputs[0].display()));
+        };
+        let mut command = Command::new(program);
+        command.args(args).current_dir(self.build_dir);
+        if edge.feed {
+            let input = self.build_dir.join(&edge.inputs[0]);
+            let file = fs::File::open(&input)
+                .map_err(|e| format!("Could not open {}: {}", input.display(), e))?;
+            command.stdin(Stdio::from(file));
+        }
+
+        let output = log::run(&mut command).map_err(|e| {
+            format!(
+                "FAILED: {}\nCould not run {}: {}",
+                edge.outputs[0].display(),
+                program,
+                e
+            )
+        })?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            let mut message = format!(
+                "FAILED: {}\n{}",
+                edge.outputs[0].display(),
+                edge.command.join(" ")
+            );
+            for stream in [String::from_utf8_lossy(&output.stdout), stderr] {
+                if !stream.trim().is_empty() {
+                    message += &format!("\n{}", stream.trim_end());
+                }
+            }
+            return Err(message);
+        }
+
+        if edge.capture {
+            let path = self.build_dir.join(&edge.outputs[0]);
+            fs::write(&path, &output.stdout)
+                .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        } else if !output.stdout.is_empty() {
+            info!("{}", String::from_utf8_lossy(&output.stdout).trim_end());
+        }
+        // Warnings are worth seeing even when the command succeeds
+        if !stderr.trim().is_empty() {
+            info!("{}", stderr.trim_end());
+        }
+
+        for output in &edge.outputs {
+            if !self.build_dir.join(output).exists() {
+                return Err(format!(
+                    "Command for {} did not create it",
+                    output.display()
+                ));
+            }
+        }
+        self.log
+            .insert(&edge.outputs[0], &Self::command_line(edge))
+            .map_err(|e| format!("Could not write the build log: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(outputs: &[&str], inputs: &[&str], script: &str) -> Edge {
+        Edge {
+            outputs: outputs.iter().map(PathBuf::from).collect(),
+            inputs: inputs.iter().map(PathBuf::from).collect(),
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            description: format!("Generating {}", outputs.join(" ")),
+            ..Default::default()
+        }
+    }
+
+    /// Make a file newer than `than`, file systems may not tell apart
+    /// writes in quick succession
+    fn touch(path: &Path, than: &Path) {
+        let file = fs::File::options().append(true).open(path).unwrap();
+        let time = modified(than).unwrap() + std::time::Duration::from_millis(1);
+        file.set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn incremental() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_dir = dir.path();
+        fs::write(build_dir.join("in.txt"), "a").unwrap();
+
+        let mut graph = Graph {
+            edges: vec![
+                shell(&["gen/mid.txt"], &["in.txt"], "cat in.txt > gen/mid.txt"),
+                Edge {
+                    capture: true,
+                    feed: true,
+                    ..shell(&["out.txt"], &["gen/mid.txt"], "tr a-z A-Z")
+                },
+                Edge {
+                    always: true,
+                    ..shell(&["stamp"], &[], "date > stamp")
+                },
+            ],
+            defaults: vec!["out.txt".into()],
+        };
+
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 2);
+        assert_eq!(fs::read_to_string(build_dir.join("out.txt")).unwrap(), "A");
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 0);
+
+        fs::write(build_dir.join("in.txt"), "b").unwrap();
+        touch(&build_dir.join("in.txt"), &build_dir.join("out.txt"));
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 2);
+        assert_eq!(fs::read_to_string(build_dir.join("out.txt")).unwrap(), "B");
+
+        // A changed command runs again even though its input didn't change
+        graph.edges[1].command[2] = "tr a-z A-Z; echo !".to_string();
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(build_dir.join("out.txt")).unwrap(),
+            "B!\n"
+        );
+
+        let stamp = [PathBuf::from("stamp")];
+        assert_eq!(build(&graph, build_dir, &stamp).unwrap(), 1);
+        assert_eq!(build(&graph, build_dir, &stamp).unwrap(), 1);
+    }
+
+    #[test]
+    fn depfiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_dir = dir.path();
+        fs::write(build_dir.join("in.txt"), "").unwrap();
+        fs::write(build_dir.join("read by.txt"), "").unwrap();
+
+        let graph = Graph {
+            edges: vec![Edge {
+                depfile: Some("out.d".into()),
+                ..shell(
+                    &["out.txt"],
+                    &["in.txt"],
+                    "touch out.txt; printf 'out.txt: in.txt \\\\\\n read\\\\ by.txt\\n' > out.d",
+                )
+            }],
+            defaults: vec!["out.txt".into()],
+        };
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 1);
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 0);
+
+        touch(&build_dir.join("read by.txt"), &build_dir.join("out.txt"));
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 1);
+        assert_eq!(build(&graph, build_dir, &graph.defaults).unwrap(), 0);
+    }
+
+    #[test]
+    fn errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_dir = dir.path();
+
+        let graph = Graph {
+            edges: vec![
+                shell(&["a"], &["b"], "touch a"),
+                shell(&["b"], &["c"], "touch b"),
+                shell(&["c"], &["a"], "touch c"),
+                shell(&["d"], &["missing"], "touch d"),
+                shell(&["e"], &[], "echo broken >&2; exit 1"),
+                shell(&["f"], &[], "true"),
+            ],
+            defaults: vec![],
+        };
+        let error = |target: &str| build(&graph, build_dir, &[target.into()]).unwrap_err();
+
+        assert_eq!(error("a"), "Dependency cycle: a -> b -> c -> a");
+        assert_eq!(
+            error("d"),
+            "'missing', needed by 'd', missing and no known rule to make it"
+        );
+        assert_eq!(
+            error("e"),
+            "FAILED: e\nsh -c echo broken >&2; exit 1\nbroken"
+        );
+        assert_eq!(error("f"), "Command for f did not create it");
+        assert_eq!(error("g"), "Unknown target 'g'");
+
+        let graph = Graph {
+            edges: vec![shell(&["a"], &[], "touch a"), shell(&["a"], &[], "touch a")],
+            defaults: vec![],
+        };
+        assert_eq!(
+            build(&graph, build_dir, &[]).unwrap_err(),
+            "Multiple commands generate a"
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    compiler::{Compiler, Language},
+    options::Options,
+    BuildTarget, Builder, File, TargetId, TargetKind,
+};
+
+mod execute;
+
+pub use execute::build;
+
+/// A command producing its outputs from its inputs. Paths are relative to
+/// the build directory, where commands run, or absolute
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub outputs: Vec<PathBuf>,
+    pub inputs: Vec<PathBuf>,
+    /// Also built first and rebuilt from, without being in the command
+    pub implicit: Vec<PathBuf>,
+    /// Built first, e.g. generated headers, but only what the depfile lists
+    /// causes a rebuild
+    pub order_only: Vec<PathBuf>,
+    /// Program and arguments
+    pub command: Vec<String>,
+    /// Write the command's standard output to the single output
+    pub capture: bool,
+    /// Give the single input to the command on its standard input
+    pub feed: bool,
+    /// Lists what the command read, written by the command
+    pub depfile: Option<PathBuf>,
+    /// Run on every build
+    pub always: bool,
+    /// Shown while the command runs
+    pub description: String,
+}
+
+/// Everything the build directory knows how to build
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Graph {
+    pub edges: Vec<Edge>,
+    /// Outputs built when no target is named
+    pub defaults: Vec<PathBuf>,
+}
+
+/// Language of a source file from its extension, `None` for files that
+/// aren't compiled such as headers
+fn source_language(path: &Path) -> Option<Language> {
+    match path.extension()?.to_str()? {
+        "c" => Some(Language::C),
+        "cpp" | "cc" | "cxx" | "C" | "c++" => Some(Language::Cpp),
+        _ => None,
+    }
+}
+
+/// Whether a file is already compiled and only needs linking
+fn is_object(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("o" | "obj" | "a" | "so")
+    )
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// A target's output, with the symlinks pointing at it
+fn target_outputs(target: &BuildTarget) -> Vec<PathBuf> {
+    let mut outputs = vec![target.output()];
+    outputs.extend(
+        target
+            .aliases()
+            .into_iter()
+            .map(|(link, _)| target.subdir.join(link)),
+    );
+    outputs
+}
+
+impl Graph {
+    pub fn new(builder: &Builder) -> Result<Self, String> {
+        let mut graph = Graph::default();
+
+        for target in &builder.custom_targets {
+            graph.edges.push(Edge {
+                outputs: target
+                    .output_files()
+                    .iter()
+                    .map(|f| f.relative_path())
+                    .collect(),
+                inputs: target.inputs.iter().map(|f| builder.path(f)).collect(),
+                implicit: target
+                    .depend_files
+                    .iter()
+                    .map(|f| builder.path(f))
+                    .chain(builder.target_files(&target.depends))
+                    .collect(),
+                command: target.command.clone(),
+                capture: target.capture,
+                feed: target.feed,
+                depfile: target.depfile.as_ref().map(|d| target.subdir.join(d)),
+                always: target.build_always_stale,
+                description: format!("Generating {} with a custom command", target.name),
+                ..Default::default()
+            });
+            if target.build_by_default {
+                graph
+                    .defaults
+                    .extend(target.output_files().iter().map(|f| f.relative_path()));
+            }
+        }
+
+        for target in &builder.build_targets {
+            graph.add_build_target(builder, target)?;
+            if target.build_by_default {
+                graph.defaults.extend(target_outputs(target));
+            }
+        }
+
+        Ok(graph)
+    }
+
+    fn add_build_target(&mut self, builder: &Builder, target: &BuildTarget) -> Result<(), String> {
+        let options = target.options(&builder.options)?;
+
+        for generated in &target.generated {
+            self.edges.push(Edge {
+                outputs: generated
+                    .outputs
+                    .iter()
+                    .map(|f| f.relative_path())
+                    .collect(),
+                inputs: vec![builder.path(&generated.input)],
+                implicit: builder.target_files(&generated.depends),
+                command: generated.command.clone(),
+                capture: generated.capture,
+                depfile: generated.depfile.as_ref().map(|f| f.relative_path()),
+                description: format!("Generating {}", generated.input.name.display()),
+                ..Default::default()
+            });
+        }
+
+        // Headers and other generated files have to exist before compiling
+        // anything that might include them
+        let generated_files = target
+            .files
+            .iter()
+            .filter(|f| f.is_built && source_language(&f.name).is_none() && !is_object(&f.name))
+            .map(|f| f.relative_path())
+            .collect::<Vec<_>>();
+        // They can be generated in another directory than the target's
+        let mut generated_dirs = vec![];
+        for file in &generated_files {
+            let dir = builder
+                .project
+                .build_dir
+                .join(file.parent().unwrap_or(Path::new("")));
+            let dir = format!("-I{}", dir.display());
+            if !generated_dirs.contains(&dir) {
+                generated_dirs.push(dir);
+            }
+        }
+
+        let mut objects = vec![];
+        let mut languages = vec![];
+        for file in &target.files {
+            let source = builder.path(file);
+            if is_object(&file.name) {
+                objects.push(source);
+                continue;
+            }
+            let Some(language) = source_language(&file.name) else {
+                continue;
+            };
+            let compiler = builder.compiler(language).ok_or_else(|| {
+                format!(
+                    "No {} compiler for {} of target {}, add {} to the project's languages",
+                    language.display_name(),
+                    file.relative_path().display(),
+                    target.name,
+                    language
+                )
+            })?;
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+
+            // Sources from different directories can share a name
+            let object_name = format!(
+                "{}.o",
+                file.relative_path().to_string_lossy().replace('/', "_")
+            );
+            let object = target.private_dir().join(object_name);
+            let object_string = path_string(&object);
+            let depfile = PathBuf::from(format!("{}.d", object_string));
+
+            let mut command = vec![path_string(&compiler.path)];
+            command.extend(compiler.args.iter().cloned());
+            command.extend(compiler.args().compile_args(
+                &options,
+                language,
+                target,
+                &builder.project.source_dir,
+                &builder.project.build_dir,
+            ));
+            command.extend(generated_dirs.iter().cloned());
+            command.extend([
+                "-MD".to_string(),
+                "-MQ".to_string(),
+                object_string.clone(),
+                "-MF".to_string(),
+                path_string(&depfile),
+                "-o".to_string(),
+                object_string,
+                "-c".to_string(),
+                path_string(&source),
+            ]);
+
+            self.edges.push(Edge {
+                outputs: vec![object.clone()],
+                inputs: vec![source],
+                order_only: generated_files.clone(),
+                command,
+                depfile: Some(depfile),
+                description: format!(
+                    "Compiling {} object {}",
+                    language.display_name(),
+                    object.display()
+                ),
+                ..Default::default()
+            });
+            objects.push(object);
+        }
+        objects.extend(target.objects.iter().map(|f| builder.path(f)));
+
+        let output = target.output();
+        let mut link = match target.kind {
+            TargetKind::StaticLibrary => {
+                let mut command = ["ar", "csrD", &path_string(&output)]
+                    .map(str::to_string)
+                    .to_vec();
+                command.extend(objects.iter().map(|o| path_string(o)));
+                Edge {
+                    inputs: objects,
+                    command,
+                    ..Default::default()
+                }
+            }
+            TargetKind::Executable | TargetKind::SharedLibrary => {
+                // C++ needs its runtime linked, which its compiler driver does
+                let language = if languages.contains(&Language::Cpp) {
+                    Language::Cpp
+                } else {
+                    Language::C
+                };
+                let linker = builder
+                    .compiler(language)
+                    .or_else(|| builder.compilers.first())
+                    .ok_or_else(|| format!("No compiler to link target {} with", target.name))?;
+                link_edge(builder, target, linker, &options, objects)
+            }
+        };
+        link.implicit
+            .extend(target.link_depends.iter().map(|f| builder.path(f)));
+        link.description = format!("Linking target {}", output.display());
+        link.outputs = vec![output];
+        self.edges.push(link);
+
+        for (link, pointee) in target.aliases() {
+            let link = target.subdir.join(link);
+            self.edges.push(Edge {
+                outputs: vec![link.clone()],
+                inputs: vec![target.subdir.join(&pointee)],
+                command: vec![
+                    "ln".to_string(),
+                    "-sf".to_string(),
+                    pointee,
+                    path_string(&link),
+                ],
+                description: format!("Creating library symlink {}", link.display()),
+                ..Default::default()
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Command linking an executable or shared library, with the files it needs
+fn link_edge(
+    builder: &Builder,
+    target: &BuildTarget,
+    linker: &Compiler,
+    options: &Options,
+    objects: Vec<PathBuf>,
+) -> Edge {
+    let mut command = vec![path_string(&linker.path)];
+    command.extend(linker.args.iter().cloned());
+    command.extend(["-o".to_string(), path_string(&target.output())]);
+    if let Some(soname) = target.soname() {
+        command.push("-shared".to_string());
+        command.push(format!("-Wl,-soname,{}", soname));
+    }
+    command.extend(objects.iter().map(|o| path_string(o)));
+
+    let mut inputs = objects;
+    let mut implicit = vec![];
+    if !target.link_whole.is_empty() {
+        command.push("-Wl,--whole-archive".to_string());
+        for library in &target.link_whole {
+            let output = builder.build_targets[*library].output();
+            command.push(path_string(&output));
+            inputs.push(output);
+        }
+        command.push("-Wl,--no-whole-archive".to_string());
+    }
+
+    let mut rpaths = vec![];
+    for library in &target.link_with {
+        let library = &builder.build_targets[*library];
+        command.push(path_string(&library.output()));
+        inputs.push(library.output());
+        // The loader looks for the soname, which the symlinks provide
+        implicit.extend(target_outputs(library).into_iter().skip(1));
+
+        if library.kind == TargetKind::SharedLibrary {
+            let up = "../".repeat(target.subdir.components().count());
+            let rpath = format!("$ORIGIN/{}{}", up, library.subdir.display());
+            if !rpaths.contains(&rpath) {
+                rpaths.push(rpath);
+            }
+        }
+    }
+    if !rpaths.is_empty() {
+        command.push(format!("-Wl,-rpath,{}", rpaths.join(":")));
+    }
+    command.extend(linker.args().link_args(options, target));
+
+    Edge {
+        inputs,
+        implicit,
+        command,
+        ..Default::default()
+    }
+}
+
+impl Builder {
+    /// Where a file is for the commands, which run in the build directory
+    fn path(&self, file: &File) -> PathBuf {
+        if file.is_built {
+            file.relative_path()
+        } else {
+            file.absolute_path(&self.project.source_dir, &self.project.build_dir)
+        }
+    }
+
+    fn compiler(&self, language: Language) -> Option<&Compiler> {
+        self.compilers.iter().find(|c| c.language == language)
+    }
+
+    /// Outputs of the targets, to build before what depends on them
+    fn target_files(&self, targets: &[TargetId]) -> Vec<PathBuf> {
+        targets
+            .iter()
+            .flat_map(|target| match target {
+                TargetId::Build(index) => target_outputs(&self.build_targets[*index]),
+                TargetId::Custom(index) => self.custom_targets[*index]
+                    .output_files()
+                    .iter()
+                    .map(|f| f.relative_path())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Files a depfile lists as the prerequisites of its outputs, with escaped
+/// spaces kept in their names
+pub fn depfile_prerequisites(contents: &str) -> Vec<String> {
+    let contents = contents.replace("\\\n", " ");
+    let mut files = vec![];
+    for line in contents.lines() {
+        let Some((_, prerequisites)) = line.split_once(": ") else {
+            continue;
+        };
+
+        let mut file = String::new();
+        let mut chars = prerequisites.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => file.push(chars.next().unwrap()),
+                c if c.is_whitespace() => {
+                    if !file.is_empty() {
+                        files.push(std::mem::take(&mut file));
+                    }
+                }
+                c => file.push(c),
+            }
+        }
+        if !file.is_empty() {
+            files.push(file);
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depfiles() {
+        assert_eq!(
+            depfile_prerequisites("out.o: a.c \\\n  b.h\\ c.h \\\n d.h\nother: e.h\n"),
+            ["a.c", "b.h c.h", "d.h", "e.h"]
+        );
+        assert_eq!(depfile_prerequisites("out.o:\n"), Vec::<String>::new());
+    }
+}
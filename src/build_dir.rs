@@ -0,0 +1,996 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    backend::{Edge, Graph},
+    options::{BuildOption, OptionType, OptionValue, Options, Section},
+    Builder, EnvLookup, Test,
+};
+
+/// Directory inside the build directory holding reson's own state
+pub const PRIVATE_DIR: &str = "reson-private";
+
+const CMD_LINE: &str = "cmd_line.txt";
+const STATE: &str = "state.txt";
+const REGENERATE: &str = "regenerate";
+const CHECK_CACHE: &str = "check_cache.txt";
+const TESTS: &str = "tests.txt";
+const GRAPH: &str = "build.txt";
+const BUILD_LOG: &str = "build_log.txt";
+
+/// Version of reson writing the state, build directories configured by
+/// another major or minor version must be set up again
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `s` in double quotes, with backslashes, quotes and line breaks escaped
+/// so it reads back unchanged
+fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\\' => quoted += "\\\\",
+            '"' => quoted += "\\\"",
+            '\n' => quoted += "\\n",
+            '\r' => quoted += "\\r",
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The string quoted at the start of `s` and what follows it
+fn unquote(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut unquoted = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((unquoted, &s[i + 2..])),
+            '\\' => unquoted.push(match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                c @ ('\\' | '"') => c,
+                _ => return None,
+            }),
+            c => unquoted.push(c),
+        }
+    }
+    None
+}
+
+/// A line of a state file, see `parse`
+fn entry(key: &str, value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("{} = {}\n", quote(key), quote(value)),
+        None => format!("{}\n", quote(key)),
+    }
+}
+
+/// Lines of an INI-like state file as `(section, key, value)`. Keys and
+/// values are quoted so they can hold any text, a line with no `=` has no
+/// value
+fn parse(path: &Path, contents: &str) -> Result<Vec<(String, String, Option<String>)>, String> {
+    let mut entries = vec![];
+    let mut section = None;
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.to_string());
+            continue;
+        }
+
+        let malformed = || format!("Malformed line in {}: {}", path.display(), line);
+        let Some(section) = &section else {
+            return Err(malformed());
+        };
+        let (key, rest) = unquote(line).ok_or_else(malformed)?;
+        let value = match rest.trim_start() {
+            "" => None,
+            rest => match rest
+                .strip_prefix('=')
+                .map(str::trim_start)
+                .and_then(unquote)
+            {
+                Some((value, "")) => Some(value),
+                _ => return Err(malformed()),
+            },
+        };
+        entries.push((section.clone(), key, value));
+    }
+
+    Ok(entries)
+}
+
+fn malformed(path: &Path, section: &str, key: &str) -> String {
+    format!(
+        "Malformed entry in {}: {} in section [{}]",
+        path.display(),
+        key,
+        section
+    )
+}
+
+fn not_configured(build_dir: &Path) -> String {
+    format!(
+        "Directory {} does not seem to be a build directory, run setup first",
+        build_dir.display()
+    )
+}
+
+/// What a build directory was configured with, enough to interpret the
+/// project again
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CmdLine {
+    pub source_dir: PathBuf,
+    /// Options given on the command line as `(name, value)`
+    pub options: Vec<(String, String)>,
+    /// Absolute paths of the native files, in the order given
+    pub native_files: Vec<PathBuf>,
+}
+
+impl CmdLine {
+    pub fn new(source_dir: &Path, options: Vec<(String, String)>) -> Self {
+        Self {
+            source_dir: source_dir.to_path_buf(),
+            options,
+            native_files: vec![],
+        }
+    }
+
+    pub fn read(build_dir: &Path) -> Result<Self, String> {
+        let path = build_dir.join(PRIVATE_DIR).join(CMD_LINE);
+        let contents = fs::read_to_string(&path).map_err(|_| not_configured(build_dir))?;
+
+        let mut cmd_line = Self::default();
+        for (section, key, value) in parse(&path, &contents)? {
+            match (section.as_str(), key.as_str(), value) {
+                ("properties", "source_dir", Some(value)) => cmd_line.source_dir = value.into(),
+                ("properties", "native_file", Some(value)) => {
+                    cmd_line.native_files.push(value.into())
+                }
+                ("options", _, Some(value)) => cmd_line.options.push((key, value)),
+                _ => return Err(malformed(&path, &section, &key)),
+            }
+        }
+
+        Ok(cmd_line)
+    }
+
+    pub fn write(&self, build_dir: &Path) -> io::Result<()> {
+        let dir = build_dir.join(PRIVATE_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let mut contents = "[properties]\n".to_string();
+        contents += &entry("source_dir", Some(&self.source_dir.display().to_string()));
+        for native_file in &self.native_files {
+            contents += &entry("native_file", Some(&native_file.display().to_string()));
+        }
+        contents += "\n[options]\n";
+        for (name, value) in &self.options {
+            contents += &entry(name, Some(value));
+        }
+
+        fs::write(dir.join(CMD_LINE), contents)
+    }
+
+    /// Add options given later, replacing earlier values for the same option
+    pub fn merge(&mut self, options: Vec<(String, String)>) {
+        for (name, value) in options {
+            match self.options.iter_mut().find(|(n, _)| *n == name) {
+                Some(existing) => existing.1 = value,
+                None => self.options.push((name, value)),
+            }
+        }
+    }
+}
+
+/// Result of configuring a build directory, used to tell when it's out of
+/// date
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct State {
+    pub version: String,
+    /// Every option with its value, to list them without configuring again
+    pub options: Options,
+    /// Compilers found as `(language, path)`
+    pub compilers: Vec<(String, PathBuf)>,
+    /// Build and option files read, relative to the source directory
+    pub build_files: Vec<PathBuf>,
+    /// Environment variables consulted, `None` when unset
+    pub environment: Vec<(String, Option<String>)>,
+}
+
+impl State {
+    pub fn new(builder: &Builder) -> Self {
+        Self {
+            version: VERSION.to_string(),
+            options: builder.options.clone(),
+            compilers: builder
+                .compilers
+                .iter()
+                .map(|c| (c.language.to_string(), c.path.clone()))
+                .collect(),
+            build_files: builder.build_files.clone(),
+            environment: builder.environment.clone(),
+        }
+    }
+
+    pub fn read(build_dir: &Path) -> Result<Self, String> {
+        let path = build_dir.join(PRIVATE_DIR).join(STATE);
+        let contents = fs::read_to_string(&path).map_err(|_| not_configured(build_dir))?;
+        let entries = parse(&path, &contents)?;
+
+        // Check the version first, the rest of the format may have changed
+        let version = entries
+            .iter()
+            .find(|(section, key, _)| section == "reson" && key == "version")
+            .and_then(|(_, _, value)| value.clone())
+            .unwrap_or_default();
+        if !compatible(&version, VERSION) {
+            return Err(format!(
+                "Build directory {} was configured by reson {}, which is incompatible with \
+                 reson {}, run setup with --wipe to configure it again",
+                build_dir.display(),
+                if version.is_empty() {
+                    "unknown"
+                } else {
+                    &version
+                },
+                VERSION
+            ));
+        }
+
+        let mut state = Self {
+            version,
+            ..Default::default()
+        };
+        let mut options: Vec<(String, Vec<_>)> = vec![];
+        for (section, key, value) in entries {
+            match (section.as_str(), value) {
+                ("reson", Some(_)) if key == "version" => {}
+                (name, value) if name.starts_with("option ") => {
+                    if options.last().is_none_or(|(s, _)| *s != section) {
+                        options.push((section.clone(), vec![]));
+                    }
+                    options.last_mut().unwrap().1.push((key, value));
+                }
+                ("compilers", Some(value)) => state.compilers.push((key, value.into())),
+                ("files", None) => state.build_files.push(key.into()),
+                ("environment", value) => state.environment.push((key, value)),
+                _ => return Err(malformed(&path, &section, &key)),
+            }
+        }
+        state.options = Options::from_saved(
+            options
+                .into_iter()
+                .map(|(section, entries)| {
+                    read_option(entries).ok_or_else(|| {
+                        format!("Malformed section in {}: [{}]", path.display(), section)
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        );
+
+        Ok(state)
+    }
+
+    pub fn write(&self, build_dir: &Path) -> io::Result<()> {
+        let dir = build_dir.join(PRIVATE_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let mut contents = "[reson]\n".to_string();
+        contents += &entry("version", Some(&self.version));
+        for (i, option) in self.options.iter().enumerate() {
+            contents += &format!("\n[option {}]\n", i);
+            contents += &option_entries(option);
+        }
+        contents += "\n[compilers]\n";
+        for (language, path) in &self.compilers {
+            contents += &entry(language, Some(&path.display().to_string()));
+        }
+        contents += "\n[files]\n";
+        for file in &self.build_files {
+            contents += &entry(&file.display().to_string(), None);
+        }
+        contents += "\n[environment]\n";
+        for (name, value) in &self.environment {
+            contents += &entry(name, value.as_deref());
+        }
+
+        fs::write(dir.join(STATE), contents)?;
+        let _ = fs::remove_file(dir.join(REGENERATE));
+        Ok(())
+    }
+
+    /// Why the build directory has to be configured again, if it does
+    pub fn outdated(&self, source_dir: &Path, build_dir: &Path, env: &EnvLookup) -> Option<String> {
+        let dir = build_dir.join(PRIVATE_DIR);
+        if dir.join(REGENERATE).exists() {
+            return Some("options changed".to_string());
+        }
+
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let configured = modified(&dir.join(STATE)).unwrap_or(SystemTime::UNIX_EPOCH);
+        for file in &self.build_files {
+            match modified(&source_dir.join(file)) {
+                Some(time) if time <= configured => {}
+                Some(_) => return Some(format!("{} changed", file.display())),
+                None => return Some(format!("{} was removed", file.display())),
+            }
+        }
+
+        for (name, value) in &self.environment {
+            if env.get(name) != *value {
+                return Some(format!("environment variable {} changed", name));
+            }
+        }
+
+        None
+    }
+}
+
+/// How option sections are written in the state
+const SECTIONS: [(Section, &str); 4] = [
+    (Section::Builtin, "builtin"),
+    (Section::Base, "base"),
+    (Section::Compiler, "compiler"),
+    (Section::Project, "project"),
+];
+
+/// The entries of an `[option N]` section of the state
+fn option_entries(option: &BuildOption) -> String {
+    let section = SECTIONS.iter().find(|(s, _)| *s == option.section).unwrap();
+    let mut contents = entry("name", Some(&option.name));
+    contents += &entry("description", Some(&option.description));
+    contents += &entry("section", Some(section.1));
+    contents += &entry("type", Some(option.ty.name()));
+    match &option.ty {
+        OptionType::Combo(choices) | OptionType::Array(Some(choices)) => {
+            // Tells an array restricted to no choices from an unrestricted one
+            if let OptionType::Array(_) = option.ty {
+                contents += &entry("restricted", None);
+            }
+            for choice in choices {
+                contents += &entry("choice", Some(choice));
+            }
+        }
+        OptionType::Integer { min, max } => {
+            for (key, bound) in [("min", min), ("max", max)] {
+                if let Some(bound) = bound {
+                    contents += &entry(key, Some(&bound.to_string()));
+                }
+            }
+        }
+        _ => {}
+    }
+    for (key, value) in [("default", &option.default), ("value", &option.value)] {
+        match value {
+            // An entry per item, which can then hold commas
+            OptionValue::Array(items) => {
+                for item in items {
+                    contents += &entry(key, Some(item));
+                }
+            }
+            value => contents += &entry(key, Some(&value.to_string())),
+        }
+    }
+    contents
+}
+
+/// An option from the entries `option_entries` wrote, `None` if they're
+/// malformed
+fn read_option(entries: Vec<(String, Option<String>)>) -> Option<BuildOption> {
+    let (mut name, mut description, mut section, mut ty) = (None, String::new(), None, None);
+    let (mut restricted, mut choices, mut min, mut max) = (false, vec![], None, None);
+    let (mut default, mut value) = (vec![], vec![]);
+    for (key, entry) in entries {
+        match (key.as_str(), entry) {
+            ("restricted", None) => restricted = true,
+            ("name", Some(v)) => name = Some(v),
+            ("description", Some(v)) => description = v,
+            ("section", Some(v)) => section = Some(SECTIONS.iter().find(|(_, k)| *k == v)?.0),
+            ("type", Some(v)) => ty = Some(v),
+            ("choice", Some(v)) => choices.push(v),
+            ("min", Some(v)) => min = Some(v.parse().ok()?),
+            ("max", Some(v)) => max = Some(v.parse().ok()?),
+            ("default", Some(v)) => default.push(v),
+            ("value", Some(v)) => value.push(v),
+            _ => return None,
+        }
+    }
+
+    let name = name?;
+    let ty = match ty?.as_str() {
+        "string" => OptionType::String,
+        "boolean" => OptionType::Boolean,
+        "combo" => OptionType::Combo(choices),
+        "integer" => OptionType::Integer { min, max },
+        "array" => OptionType::Array(restricted.then_some(choices)),
+        "feature" => OptionType::Feature,
+        _ => return None,
+    };
+    let parse = |values: Vec<String>| match (&ty, values.as_slice()) {
+        (OptionType::Array(_), _) => ty.validate(&name, OptionValue::Array(values)).ok(),
+        (_, [value]) => ty.parse(&name, value).ok(),
+        _ => None,
+    };
+    let (default, value) = (parse(default)?, parse(value)?);
+
+    Some(BuildOption {
+        name,
+        description,
+        section: section?,
+        ty,
+        default,
+        value,
+    })
+}
+
+/// State written by one version can be read by another with the same major
+/// and minor version
+fn compatible(a: &str, b: &str) -> bool {
+    let major_minor = |v: &str| v.split('.').take(2).map(str::to_string).collect::<Vec<_>>();
+    !a.is_empty() && major_minor(a) == major_minor(b)
+}
+
+/// 64-bit FNV-1a, stable across runs and Rust versions unlike `DefaultHasher`
+fn hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Results of compiler checks, kept in the build directory so configuring
+/// again doesn't repeat them
+#[derive(Debug, Default)]
+pub struct CheckCache {
+    path: PathBuf,
+    results: HashMap<u64, String>,
+}
+
+impl CheckCache {
+    /// The cache of `build_dir`, empty if it has none yet
+    pub fn load(build_dir: &Path) -> Self {
+        let path = build_dir.join(PRIVATE_DIR).join(CHECK_CACHE);
+        let results = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (key, result) = line.split_once(' ')?;
+                Some((u64::from_str_radix(key, 16).ok()?, result.to_string()))
+            })
+            .collect();
+
+        Self { path, results }
+    }
+
+    /// Forget every result in `build_dir`
+    pub fn clear(build_dir: &Path) -> io::Result<()> {
+        match fs::remove_file(build_dir.join(PRIVATE_DIR).join(CHECK_CACHE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Result of the check identified by `key`, e.g. its code and arguments
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.results.get(&hash(key)).map(String::as_str)
+    }
+
+    /// Remember a result, which must fit on one line
+    pub fn insert(&mut self, key: &str, result: String) -> io::Result<()> {
+        let key = hash(key);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{:016x} {}", key, result)?;
+
+        self.results.insert(key, result);
+        Ok(())
+    }
+}
+
+/// Commands that last produced each output, so changing a command rebuilds
+/// its outputs
+#[derive(Debug, Default)]
+pub struct BuildLog {
+    path: PathBuf,
+    commands: HashMap<PathBuf, u64>,
+}
+
+impl BuildLog {
+    /// The log of `build_dir`, empty if nothing was built yet. Later lines
+    /// take precedence, they are from later builds
+    pub fn load(build_dir: &Path) -> Self {
+        let path = build_dir.join(PRIVATE_DIR).join(BUILD_LOG);
+        let commands = fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (hash, output) = line.split_once(' ')?;
+                Some((output.into(), u64::from_str_radix(hash, 16).ok()?))
+            })
+            .collect();
+
+        Self { path, commands }
+    }
+
+    /// Whether `command` is what last produced `output`
+    pub fn is_current(&self, output: &Path, command: &str) -> bool {
+        self.commands.get(output) == Some(&hash(command))
+    }
+
+    /// Remember that `command` produced `output`
+    pub fn insert(&mut self, output: &Path, command: &str) -> io::Result<()> {
+        let command = hash(command);
+        if self.commands.get(output) == Some(&command) {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{:016x} {}", command, output.display())?;
+
+        self.commands.insert(output.to_path_buf(), command);
+        Ok(())
+    }
+}
+
+/// Record what the build directory can build, one section per edge
+pub fn write_graph(build_dir: &Path, graph: &Graph) -> io::Result<()> {
+    let dir = build_dir.join(PRIVATE_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let mut contents = String::new();
+    for (i, edge) in graph.edges.iter().enumerate() {
+        contents += &format!("[edge {}]\n", i);
+        let paths = [
+            ("output", &edge.outputs),
+            ("input", &edge.inputs),
+            ("implicit", &edge.implicit),
+            ("order_only", &edge.order_only),
+        ];
+        for (key, paths) in paths {
+            for path in paths {
+                contents += &entry(key, Some(&path.display().to_string()));
+            }
+        }
+        for arg in &edge.command {
+            contents += &entry("command", Some(arg));
+        }
+        contents += &entry("capture", Some(&edge.capture.to_string()));
+        contents += &entry("feed", Some(&edge.feed.to_string()));
+        if let Some(depfile) = &edge.depfile {
+            contents += &entry("depfile", Some(&depfile.display().to_string()));
+        }
+        contents += &entry("always", Some(&edge.always.to_string()));
+        contents += &entry("description", Some(&edge.description));
+        contents += "\n";
+    }
+    contents += "[defaults]\n";
+    for path in &graph.defaults {
+        contents += &entry("default", Some(&path.display().to_string()));
+    }
+
+    fs::write(dir.join(GRAPH), contents)
+}
+
+pub fn read_graph(build_dir: &Path) -> Result<Graph, String> {
+    let path = build_dir.join(PRIVATE_DIR).join(GRAPH);
+    let contents = fs::read_to_string(&path).map_err(|_| not_configured(build_dir))?;
+
+    let mut graph = Graph::default();
+    let mut current = None;
+    for (section, key, value) in parse(&path, &contents)? {
+        let error = || malformed(&path, &section, &key);
+        let value = value.ok_or_else(error)?;
+        if section == "defaults" {
+            match key.as_str() {
+                "default" => graph.defaults.push(value.into()),
+                _ => return Err(error()),
+            }
+            continue;
+        }
+
+        if current.as_ref() != Some(&section) {
+            graph.edges.push(Edge::default());
+            current = Some(section.clone());
+        }
+        let edge = graph.edges.last_mut().unwrap();
+        let flag = |value: &str| value.parse::<bool>().map_err(|_| error());
+
+        match key.as_str() {
+            "output" => edge.outputs.push(value.into()),
+            "input" => edge.inputs.push(value.into()),
+            "implicit" => edge.implicit.push(value.into()),
+            "order_only" => edge.order_only.push(value.into()),
+            "command" => edge.command.push(value),
+            "capture" => edge.capture = flag(&value)?,
+            "feed" => edge.feed = flag(&value)?,
+            "depfile" => edge.depfile = Some(value.into()),
+            "always" => edge.always = flag(&value)?,
+            "description" => edge.description = value,
+            _ => return Err(error()),
+        }
+    }
+
+    Ok(graph)
+}
+
+/// Record the tests and benchmarks of a configuration for the test runner,
+/// one section per test
+pub fn write_tests(build_dir: &Path, tests: &[Test]) -> io::Result<()> {
+    let dir = build_dir.join(PRIVATE_DIR);
+    fs::create_dir_all(&dir)?;
+
+    let mut contents = String::new();
+    for (i, test) in tests.iter().enumerate() {
+        contents += &format!("[test {}]\n", i);
+        contents += &entry("name", Some(&test.name));
+        contents += &entry("benchmark", Some(&test.is_benchmark.to_string()));
+        for arg in &test.command {
+            contents += &entry("command", Some(arg));
+        }
+        for (name, value) in &test.env {
+            contents += &entry("env", Some(&format!("{}={}", name, value)));
+        }
+        contents += &entry("timeout", Some(&test.timeout.to_string()));
+        for suite in &test.suites {
+            contents += &entry("suite", Some(suite));
+        }
+        contents += &entry("should_fail", Some(&test.should_fail.to_string()));
+        contents += &entry("is_parallel", Some(&test.is_parallel.to_string()));
+        contents += &entry("protocol", Some(&test.protocol.to_string()));
+        for depend in &test.depends {
+            contents += &entry("depends", Some(&depend.display().to_string()));
+        }
+        if let Some(workdir) = &test.workdir {
+            contents += &entry("workdir", Some(&workdir.display().to_string()));
+        }
+        contents += &entry("priority", Some(&test.priority.to_string()));
+        contents += "\n";
+    }
+
+    fs::write(dir.join(TESTS), contents)
+}
+
+pub fn read_tests(build_dir: &Path) -> Result<Vec<Test>, String> {
+    let path = build_dir.join(PRIVATE_DIR).join(TESTS);
+    let contents = fs::read_to_string(&path).map_err(|_| not_configured(build_dir))?;
+
+    let mut tests: Vec<Test> = vec![];
+    let mut current = None;
+    for (section, key, value) in parse(&path, &contents)? {
+        if current.as_ref() != Some(&section) {
+            tests.push(Test::default());
+            current = Some(section.clone());
+        }
+        let test = tests.last_mut().unwrap();
+        let error = || malformed(&path, &section, &key);
+        let value = value.ok_or_else(error)?;
+        let flag = |value: &str| value.parse::<bool>().map_err(|_| error());
+        let number = |value: &str| value.parse::<i64>().map_err(|_| error());
+
+        match key.as_str() {
+            "name" => test.name = value,
+            "benchmark" => test.is_benchmark = flag(&value)?,
+            "command" => test.command.push(value),
+            "env" => {
+                let (name, value) = value.split_once('=').ok_or_else(error)?;
+                test.env.push((name.to_string(), value.to_string()));
+            }
+            "timeout" => test.timeout = number(&value)?,
+            "suite" => test.suites.push(value),
+            "should_fail" => test.should_fail = flag(&value)?,
+            "is_parallel" => test.is_parallel = flag(&value)?,
+            "protocol" => test.protocol = value.parse().map_err(|_| error())?,
+            "depends" => test.depends.push(value.into()),
+            "workdir" => test.workdir = Some(value.into()),
+            "priority" => test.priority = number(&value)?,
+            _ => return Err(error()),
+        }
+    }
+
+    Ok(tests)
+}
+
+/// Ask for the build directory to be regenerated before the next build
+pub fn mark_regenerate(build_dir: &Path) -> io::Result<()> {
+    let dir = build_dir.join(PRIVATE_DIR);
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join(REGENERATE), "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cmd_line_round_trip() {
+        let build_dir = tempfile::tempdir().unwrap();
+        assert!(CmdLine::read(build_dir.path()).is_err());
+
+        let mut cmd_line = CmdLine::new(
+            Path::new("/src/project"),
+            vec![
+                ("buildtype".to_string(), "debug".to_string()),
+                ("docs".to_string(), "true".to_string()),
+            ],
+        );
+        cmd_line.merge(vec![
+            ("buildtype".to_string(), "release".to_string()),
+            ("prefix".to_string(), "/opt/x y".to_string()),
+            ("foo".to_string(), " x ".to_string()),
+            (
+                "motd".to_string(),
+                "line \"one\" =\n\\two\r\n  ".to_string(),
+            ),
+        ]);
+        assert_eq!(
+            cmd_line.options,
+            vec![
+                ("buildtype".to_string(), "release".to_string()),
+                ("docs".to_string(), "true".to_string()),
+                ("prefix".to_string(), "/opt/x y".to_string()),
+                ("foo".to_string(), " x ".to_string()),
+                (
+                    "motd".to_string(),
+                    "line \"one\" =\n\\two\r\n  ".to_string(),
+                ),
+            ]
+        );
+
+        cmd_line.native_files = vec!["/etc/native.ini".into(), "/tmp/x y.ini".into()];
+
+        cmd_line.write(build_dir.path()).unwrap();
+        assert_eq!(CmdLine::read(build_dir.path()).unwrap(), cmd_line);
+    }
+
+    #[test]
+    fn quoting() {
+        for s in [
+            "",
+            " a = b ",
+            "\"quoted\"",
+            "back\\slash\\",
+            "\r\n\n",
+            "ünïcode",
+        ] {
+            let quoted = quote(s);
+            assert!(!quoted.contains('\n'), "{}", quoted);
+            assert_eq!(unquote(&quoted), Some((s.to_string(), "")));
+        }
+        assert_eq!(
+            unquote("\"a\" = \"b\""),
+            Some(("a".to_string(), " = \"b\""))
+        );
+        assert_eq!(unquote("\"unterminated"), None);
+        assert_eq!(unquote("\"bad \\x escape\""), None);
+        assert_eq!(unquote("unquoted"), None);
+
+        let path = Path::new("state.txt");
+        assert!(parse(path, "[s]\nunquoted = \"x\"").is_err());
+        assert!(parse(path, "[s]\n\"x\" = \"y\" trailing").is_err());
+        assert!(parse(path, "\"x\" = \"y\"").is_err());
+    }
+
+    #[test]
+    fn check_cache() {
+        let build_dir = tempfile::tempdir().unwrap();
+
+        let mut cache = CheckCache::load(build_dir.path());
+        assert_eq!(cache.get("sizeof long"), None);
+        cache.insert("sizeof long", "8".to_string()).unwrap();
+        cache
+            .insert("has_header\nfoo.h", "false".to_string())
+            .unwrap();
+
+        let cache = CheckCache::load(build_dir.path());
+        assert_eq!(cache.get("sizeof long"), Some("8"));
+        assert_eq!(cache.get("has_header\nfoo.h"), Some("false"));
+        assert_eq!(cache.get("has_header\nbar.h"), None);
+
+        CheckCache::clear(build_dir.path()).unwrap();
+        CheckCache::clear(build_dir.path()).unwrap();
+        assert_eq!(CheckCache::load(build_dir.path()).get("sizeof long"), None);
+    }
+
+    #[test]
+    fn state() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let build_dir = tempfile::tempdir().unwrap();
+        fs::write(source_dir.path().join("meson.build"), "").unwrap();
+
+        let mut options = Options::builtin();
+        let project_options = [
+            (
+                "title",
+                OptionType::String,
+                OptionValue::String("\n  two lines \n".to_string()),
+            ),
+            (
+                "jobs",
+                OptionType::Integer {
+                    min: Some(1),
+                    max: None,
+                },
+                OptionValue::Integer(4),
+            ),
+            (
+                "flags",
+                OptionType::Array(None),
+                OptionValue::Array(vec![" a, b ".to_string(), String::new()]),
+            ),
+            (
+                "backends",
+                OptionType::Array(Some(vec![])),
+                OptionValue::Array(vec![]),
+            ),
+            (
+                "docs",
+                OptionType::Feature,
+                OptionValue::Feature(crate::options::Feature::Auto),
+            ),
+        ];
+        for (name, ty, default) in project_options {
+            let option =
+                BuildOption::new(name, "A \"project\" option", Section::Project, ty, default);
+            options.add(option).unwrap();
+        }
+        options.set("buildtype", "release").unwrap();
+        options.set("flags", "['x']").unwrap();
+
+        let state = State {
+            version: VERSION.to_string(),
+            options,
+            compilers: vec![("c".to_string(), "/usr/bin/cc".into())],
+            build_files: vec!["meson.build".into()],
+            environment: vec![
+                ("RESON_TEST_UNSET".to_string(), None),
+                ("RESON_TEST_SET".to_string(), Some("1".to_string())),
+            ],
+        };
+        let mut env = EnvLookup::default();
+        env.set("RESON_TEST_UNSET", None);
+        env.set("RESON_TEST_SET", Some("1"));
+        state.write(build_dir.path()).unwrap();
+        assert_eq!(State::read(build_dir.path()).unwrap(), state);
+        assert_eq!(
+            state.outdated(source_dir.path(), build_dir.path(), &env),
+            None
+        );
+
+        let mut changed = env.clone();
+        changed.set("RESON_TEST_SET", Some("2"));
+        assert_eq!(
+            state.outdated(source_dir.path(), build_dir.path(), &changed),
+            Some("environment variable RESON_TEST_SET changed".to_string())
+        );
+
+        mark_regenerate(build_dir.path()).unwrap();
+        assert!(state
+            .outdated(source_dir.path(), build_dir.path(), &env)
+            .is_some());
+        state.write(build_dir.path()).unwrap();
+
+        fs::remove_file(source_dir.path().join("meson.build")).unwrap();
+        assert_eq!(
+            state.outdated(source_dir.path(), build_dir.path(), &env),
+            Some("meson.build was removed".to_string())
+        );
+
+        let old = State {
+            version: "0.0.1".to_string(),
+            ..state
+        };
+        old.write(build_dir.path()).unwrap();
+        assert!(State::read(build_dir.path())
+            .unwrap_err()
+            .contains("was configured by reson 0.0.1"));
+    }
+
+    #[test]
+    fn tests_round_trip() {
+        let build_dir = tempfile::tempdir().unwrap();
+        assert!(read_tests(build_dir.path()).is_err());
+
+        let tests = vec![
+            Test {
+                name: "unit".to_string(),
+                command: vec![
+                    "/b/unit".to_string(),
+                    "--opt=a b".to_string(),
+                    "".to_string(),
+                ],
+                env: vec![("VAR".to_string(), "x=y".to_string())],
+                timeout: 30,
+                suites: vec!["p".to_string(), "p:fast".to_string()],
+                is_parallel: true,
+                protocol: crate::Protocol::Tap,
+                depends: vec!["sub/unit".into()],
+                workdir: Some("/tmp".into()),
+                priority: -1,
+                ..Default::default()
+            },
+            Test {
+                name: "bench".to_string(),
+                is_benchmark: true,
+                command: vec!["/b/bench".to_string()],
+                should_fail: true,
+                ..Default::default()
+            },
+        ];
+        write_tests(build_dir.path(), &tests).unwrap();
+        assert_eq!(read_tests(build_dir.path()).unwrap(), tests);
+
+        // Nothing is trimmed or split at line breaks
+        let tests = vec![Test {
+            name: " spaced name ".to_string(),
+            command: vec![
+                " /b/x y ".to_string(),
+                "multi\nline\n".to_string(),
+                "\r\n".to_string(),
+                " = ".to_string(),
+                "\"quoted\\\"".to_string(),
+            ],
+            env: vec![("VAR".to_string(), "\n value \n".to_string())],
+            suites: vec![" p ".to_string()],
+            depends: vec![" sub dir/unit ".into()],
+            workdir: Some("/tmp/ x\n".into()),
+            ..Default::default()
+        }];
+        write_tests(build_dir.path(), &tests).unwrap();
+        assert_eq!(read_tests(build_dir.path()).unwrap(), tests);
+
+        write_tests(build_dir.path(), &[]).unwrap();
+        assert_eq!(read_tests(build_dir.path()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn graph_round_trip() {
+        let build_dir = tempfile::tempdir().unwrap();
+        assert!(read_graph(build_dir.path()).is_err());
+
+        let graph = Graph {
+            edges: vec![
+                Edge {
+                    outputs: vec!["gen/a b.h".into(), "gen/c.h".into()],
+                    inputs: vec!["/src/x y.in".into()],
+                    implicit: vec!["tool".into()],
+                    order_only: vec!["gen/d.h".into()],
+                    command: vec!["tool".to_string(), " -o ".to_string(), "\n\n".to_string()],
+                    capture: true,
+                    feed: true,
+                    depfile: Some("gen/a.d".into()),
+                    always: true,
+                    description: "Generating \"a\"".to_string(),
+                },
+                Edge {
+                    outputs: vec!["tool".into()],
+                    command: vec!["cc".to_string()],
+                    ..Default::default()
+                },
+            ],
+            defaults: vec!["tool".into(), "gen/a b.h".into()],
+        };
+        write_graph(build_dir.path(), &graph).unwrap();
+        assert_eq!(read_graph(build_dir.path()).unwrap(), graph);
+
+        write_graph(build_dir.path(), &Graph::default()).unwrap();
+        assert_eq!(read_graph(build_dir.path()).unwrap(), Graph::default());
+    }
+}
@@ -0,0 +1,464 @@
+use std::path::Path;
+
+use super::Language;
+use crate::{
+    options::{OptionValue, Options},
+    BuildTarget, IncludeDirectories, TargetKind,
+};
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Translation of Meson's abstract options to command line arguments, the
+/// provided methods are right for GCC-like compilers
+pub trait CompilerArgs {
+    /// Arguments enabling every warning the compiler has
+    fn everything_warning_args(&self) -> Vec<String>;
+
+    fn warning_args(&self, level: &str) -> Vec<String> {
+        match level {
+            "1" => strings(&["-Wall"]),
+            "2" => strings(&["-Wall", "-Wextra"]),
+            "3" => strings(&["-Wall", "-Wextra", "-Wpedantic"]),
+            "everything" => self.everything_warning_args(),
+            _ => vec![],
+        }
+    }
+
+    /// Arguments making the compiler fail on arguments it doesn't know,
+    /// rather than ignore them with a warning
+    fn unknown_argument_error_args(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn werror_args(&self) -> Vec<String> {
+        strings(&["-Werror"])
+    }
+
+    fn optimization_args(&self, level: &str) -> Vec<String> {
+        match level {
+            "0" | "g" | "1" | "2" | "3" | "s" => vec![format!("-O{}", level)],
+            _ => vec![],
+        }
+    }
+
+    fn debug_args(&self) -> Vec<String> {
+        strings(&["-g"])
+    }
+
+    /// `std` is the value of `c_std` or `cpp_std`
+    fn std_args(&self, std: &str) -> Vec<String> {
+        match std {
+            "none" | "" => vec![],
+            std => vec![format!("-std={}", std)],
+        }
+    }
+
+    fn ndebug_args(&self) -> Vec<String> {
+        strings(&["-DNDEBUG"])
+    }
+
+    fn pic_args(&self) -> Vec<String> {
+        strings(&["-fPIC"])
+    }
+
+    fn pie_args(&self) -> Vec<String> {
+        strings(&["-fPIE"])
+    }
+
+    fn pie_link_args(&self) -> Vec<String> {
+        strings(&["-pie"])
+    }
+
+    fn lto_args(&self) -> Vec<String> {
+        strings(&["-flto"])
+    }
+
+    fn lto_link_args(&self) -> Vec<String> {
+        self.lto_args()
+    }
+
+    /// Search `dir` for headers, as a system directory if `is_system`
+    fn include_args(&self, dir: &Path, is_system: bool) -> Vec<String> {
+        let flag = if is_system { "-isystem" } else { "-I" };
+        vec![format!("{}{}", flag, dir.display())]
+    }
+
+    /// Include arguments for every directory of `include_directories`
+    fn include_directories_args(
+        &self,
+        include_directories: &[IncludeDirectories],
+        source_dir: &Path,
+        build_dir: &Path,
+    ) -> Vec<String> {
+        include_directories
+            .iter()
+            .flat_map(|inc| {
+                inc.paths(source_dir, build_dir)
+                    .into_iter()
+                    .flat_map(|dir| self.include_args(&dir, inc.is_system))
+            })
+            .collect()
+    }
+
+    /// Arguments for compiling `target`'s sources in `language`, the options'
+    /// arguments come first so that those of the target can override them
+    fn compile_args(
+        &self,
+        options: &Options,
+        language: Language,
+        target: &BuildTarget,
+        source_dir: &Path,
+        build_dir: &Path,
+    ) -> Vec<String> {
+        let string = |name: &str| options.value(name).map(|v| v.to_string());
+        let enabled = |name: &str| options.value(name) == Some(&OptionValue::Boolean(true));
+
+        let mut args = vec![];
+        if let Some(std) = string(&format!("{}_std", language)) {
+            args.extend(self.std_args(&std));
+        }
+        if let Some(level) = string("warning_level") {
+            args.extend(self.warning_args(&level));
+        }
+        if enabled("werror") {
+            args.extend(self.werror_args());
+        }
+        if let Some(level) = string("optimization") {
+            args.extend(self.optimization_args(&level));
+        }
+        if enabled("debug") {
+            args.extend(self.debug_args());
+        }
+
+        let ndebug = match string("b_ndebug").as_deref() {
+            Some("true") => true,
+            Some("if-release") => {
+                matches!(string("buildtype").as_deref(), Some("release" | "plain"))
+            }
+            _ => false,
+        };
+        if ndebug {
+            args.extend(self.ndebug_args());
+        }
+
+        if target.pic {
+            args.extend(self.pic_args());
+        } else if target.kind == TargetKind::Executable && enabled("b_pie") {
+            args.extend(self.pie_args());
+        }
+        if enabled("b_lto") {
+            args.extend(self.lto_args());
+        }
+
+        if target.implicit_include_directories {
+            args.extend(self.include_args(&build_dir.join(&target.subdir), false));
+            args.extend(self.include_args(&source_dir.join(&target.subdir), false));
+        }
+        args.extend(self.include_directories_args(
+            &target.include_directories,
+            source_dir,
+            build_dir,
+        ));
+        args.extend(target.compile_args.iter().cloned());
+        for (_, language_args) in target.language_args.iter().filter(|(l, _)| *l == language) {
+            args.extend(language_args.iter().cloned());
+        }
+
+        args
+    }
+
+    /// Arguments for linking `target`
+    fn link_args(&self, options: &Options, target: &BuildTarget) -> Vec<String> {
+        let enabled = |name: &str| options.value(name) == Some(&OptionValue::Boolean(true));
+
+        let mut args = vec![];
+        if target.kind == TargetKind::Executable && enabled("b_pie") {
+            args.extend(self.pie_link_args());
+        }
+        if enabled("b_lto") {
+            args.extend(self.lto_link_args());
+        }
+        args.extend(target.link_args.iter().cloned());
+
+        args
+    }
+}
+
+pub struct Gcc;
+
+impl CompilerArgs for Gcc {
+    fn everything_warning_args(&self) -> Vec<String> {
+        strings(&[
+            "-Wall",
+            "-Wextra",
+            "-Wpedantic",
+            "-Wcast-qual",
+            "-Wconversion",
+            "-Wduplicated-branches",
+            "-Wduplicated-cond",
+            "-Wformat=2",
+            "-Wlogical-op",
+            "-Wmissing-declarations",
+            "-Wnull-dereference",
+            "-Wshadow",
+            "-Wundef",
+            "-Wunused",
+        ])
+    }
+}
+
+pub struct Clang;
+
+impl CompilerArgs for Clang {
+    fn everything_warning_args(&self) -> Vec<String> {
+        strings(&["-Weverything"])
+    }
+
+    fn unknown_argument_error_args(&self) -> Vec<String> {
+        strings(&[
+            "-Werror=unknown-warning-option",
+            "-Werror=unused-command-line-argument",
+            "-Werror=ignored-optimization-argument",
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Dependency;
+
+    fn target(kind: TargetKind, pic: bool) -> BuildTarget {
+        BuildTarget {
+            name: "foo".to_string(),
+            kind,
+            pic,
+            ..Default::default()
+        }
+    }
+
+    fn options(values: &[(&str, &str)]) -> Options {
+        let mut options = Options::builtin();
+        for language in [Language::C, Language::Cpp] {
+            for option in language.options() {
+                options.add(option).unwrap();
+            }
+        }
+        for (name, value) in values {
+            options.set(name, value).unwrap();
+        }
+        options
+    }
+
+    #[test]
+    fn compile_args() {
+        let exe = target(TargetKind::Executable, false);
+        let static_lib = target(TargetKind::StaticLibrary, true);
+        let shared_lib = target(TargetKind::SharedLibrary, true);
+
+        struct Test<'a> {
+            compiler: &'a dyn CompilerArgs,
+            options: &'a [(&'a str, &'a str)],
+            language: Language,
+            target: &'a BuildTarget,
+            expected: &'a str,
+        }
+
+        let tests = [
+            Test {
+                compiler: &Gcc,
+                options: &[],
+                language: Language::C,
+                target: &exe,
+                expected: "-Wall -O0 -g",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[("buildtype", "release"), ("b_ndebug", "if-release")],
+                language: Language::C,
+                target: &exe,
+                expected: "-Wall -O3 -DNDEBUG",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[("buildtype", "debugoptimized"), ("b_ndebug", "if-release")],
+                language: Language::C,
+                target: &exe,
+                expected: "-Wall -O2 -g",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[("buildtype", "plain"), ("warning_level", "0")],
+                language: Language::C,
+                target: &exe,
+                expected: "",
+            },
+            Test {
+                compiler: &Clang,
+                options: &[
+                    ("warning_level", "3"),
+                    ("werror", "true"),
+                    ("optimization", "g"),
+                    ("debug", "false"),
+                    ("b_ndebug", "true"),
+                ],
+                language: Language::C,
+                target: &exe,
+                expected: "-Wall -Wextra -Wpedantic -Werror -Og -DNDEBUG",
+            },
+            Test {
+                compiler: &Clang,
+                options: &[("warning_level", "everything"), ("optimization", "s")],
+                language: Language::C,
+                target: &exe,
+                expected: "-Weverything -Os -g",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[
+                    ("warning_level", "2"),
+                    ("c_std", "c11"),
+                    ("cpp_std", "c++17"),
+                ],
+                language: Language::C,
+                target: &static_lib,
+                expected: "-std=c11 -Wall -Wextra -O0 -g -fPIC",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[("cpp_std", "c++17"), ("b_lto", "true")],
+                language: Language::Cpp,
+                target: &shared_lib,
+                expected: "-std=c++17 -Wall -O0 -g -fPIC -flto",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[("b_pie", "true"), ("optimization", "1"), ("debug", "false")],
+                language: Language::C,
+                target: &exe,
+                expected: "-Wall -O1 -fPIE",
+            },
+            Test {
+                compiler: &Gcc,
+                options: &[
+                    ("b_pie", "true"),
+                    ("b_staticpic", "false"),
+                    ("debug", "false"),
+                ],
+                language: Language::C,
+                target: &target(TargetKind::StaticLibrary, false),
+                expected: "-Wall -O0",
+            },
+        ];
+
+        for test in tests {
+            let args = test.compiler.compile_args(
+                &options(test.options),
+                test.language,
+                test.target,
+                Path::new("/src"),
+                Path::new("/build"),
+            );
+            assert_eq!(args.join(" "), test.expected, "{:?}", test.options);
+        }
+
+        let gcc = Gcc.warning_args("everything");
+        assert_eq!(gcc[..3], ["-Wall", "-Wextra", "-Wpedantic"]);
+        assert!(gcc.contains(&"-Wshadow".to_string()));
+    }
+
+    #[test]
+    fn link_args() {
+        let exe = target(TargetKind::Executable, false);
+        let shared_lib = target(TargetKind::SharedLibrary, true);
+
+        let options = options(&[("b_pie", "true"), ("b_lto", "true")]);
+        assert_eq!(Gcc.link_args(&options, &exe), ["-pie", "-flto"]);
+        assert_eq!(Clang.link_args(&options, &shared_lib), ["-flto"]);
+        assert!(Gcc.link_args(&self::options(&[]), &exe).is_empty());
+    }
+
+    #[test]
+    fn target_args() {
+        let dependency = Dependency {
+            compile_args: strings(&["-DDEP"]),
+            include_directories: vec![IncludeDirectories {
+                subdir: "dep".into(),
+                dirs: vec!["include".into()],
+                is_system: true,
+            }],
+            link_args: strings(&["-ldep"]),
+            ..Default::default()
+        };
+        let library = BuildTarget {
+            kind: TargetKind::StaticLibrary,
+            link_args: strings(&["-lm"]),
+            ..Default::default()
+        };
+
+        let mut exe = target(TargetKind::Executable, false);
+        exe.subdir = "exe".into();
+        exe.implicit_include_directories = true;
+        exe.compile_args = strings(&["-DEXE"]);
+        exe.language_args = vec![
+            (Language::C, strings(&["-DC"])),
+            (Language::Cpp, strings(&["-DCPP"])),
+        ];
+        exe.add_dependency(&dependency);
+        exe.link_through(&library);
+
+        let options = options(&[("debug", "false"), ("warning_level", "0")]);
+        assert_eq!(
+            Gcc.compile_args(
+                &options,
+                Language::C,
+                &exe,
+                Path::new("/src"),
+                Path::new("/build")
+            ),
+            [
+                "-O0",
+                "-I/build/exe",
+                "-I/src/exe",
+                "-isystem/build/dep/include",
+                "-isystem/src/dep/include",
+                "-DEXE",
+                "-DDEP",
+                "-DC",
+            ]
+        );
+        assert_eq!(Gcc.link_args(&options, &exe), ["-ldep", "-lm"]);
+    }
+
+    #[test]
+    fn include_args() {
+        let include_directories = [
+            IncludeDirectories {
+                subdir: "lib".into(),
+                dirs: vec!["include".into(), "/opt/x/include".into()],
+                is_system: false,
+            },
+            IncludeDirectories {
+                subdir: "".into(),
+                dirs: vec!["third_party".into()],
+                is_system: true,
+            },
+        ];
+        assert_eq!(
+            Gcc.include_directories_args(
+                &include_directories,
+                Path::new("/src"),
+                Path::new("/build")
+            ),
+            [
+                "-I/build/lib/include",
+                "-I/src/lib/include",
+                "-I/opt/x/include",
+                "-isystem/build/third_party",
+                "-isystem/src/third_party",
+            ]
+        );
+    }
+}
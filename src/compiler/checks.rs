@@ -0,0 +1,252 @@
+use std::{fs, path::Path, process::Command};
+
+use super::{Compiler, Language};
+
+/// Smallest program every C and C++ compiler accepts
+const TRIVIAL: &str = "int main(void) { return 0; }\n";
+
+impl Language {
+    /// Suffix of source files checks are written to
+    pub(super) fn source_suffix(&self) -> &'static str {
+        match self {
+            Language::C => "c",
+            Language::Cpp => "cpp",
+        }
+    }
+}
+
+/// How far a check takes its test program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Preprocess,
+    Compile,
+    Link,
+    /// Link and then run the program
+    Run,
+}
+
+/// Outcome of a check, the program's exit code and output are only set when
+/// it was run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckOutput {
+    pub compiled: bool,
+    pub returncode: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Compiler {
+    /// Build `code` with `args` in `scratch_dir` as far as `mode` asks
+    pub fn check(
+        &self,
+        scratch_dir: &Path,
+        code: &str,
+        args: &[String],
+        mode: Mode,
+    ) -> Result<CheckOutput, String> {
+        fs::create_dir_all(scratch_dir)
+            .map_err(|e| format!("Failed to create {}: {}", scratch_dir.display(), e))?;
+        // The program runs from the scratch dir, so a relative path to it
+        // would be wrong
+        let scratch_dir = &scratch_dir
+            .canonicalize()
+            .map_err(|e| format!("Failed to resolve {}: {}", scratch_dir.display(), e))?;
+
+        let source = scratch_dir.join(format!("check.{}", self.language.source_suffix()));
+        fs::write(&source, code)
+            .map_err(|e| format!("Failed to write {}: {}", source.display(), e))?;
+        crate::debug!("Code:\n{}", code.trim_end());
+        let output = scratch_dir.join(match mode {
+            Mode::Preprocess => "check.i",
+            Mode::Compile => "check.o",
+            Mode::Link | Mode::Run => "check",
+        });
+
+        let (source_arg, output_arg) = (source.to_string_lossy(), output.to_string_lossy());
+        let mut command = vec![source_arg.as_ref(), "-o", output_arg.as_ref()];
+        match mode {
+            Mode::Preprocess => command.push("-E"),
+            Mode::Compile => command.push("-c"),
+            Mode::Link | Mode::Run => {}
+        }
+        command.extend(args.iter().map(String::as_str));
+
+        let compiled = self.run(&command)?.status.success();
+        if mode != Mode::Run || !compiled {
+            return Ok(CheckOutput {
+                compiled,
+                ..Default::default()
+            });
+        }
+
+        let run = crate::log::run(Command::new(&output).current_dir(scratch_dir))
+            .map_err(|e| format!("Failed to run {}: {}", output.display(), e))?;
+        Ok(CheckOutput {
+            compiled,
+            returncode: run.status.code().map_or(-1, i64::from),
+            stdout: String::from_utf8_lossy(&run.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&run.stderr).into_owned(),
+        })
+    }
+
+    /// Arguments checking whether the compiler accepts `arg`, to compile
+    /// [`TRIVIAL`] with
+    pub fn argument_check(&self, arg: &str) -> (&'static str, Vec<String>) {
+        let mut args = self.args().unknown_argument_error_args();
+        // GCC silently accepts any -Wno-foo, but not an unknown -Wfoo
+        match arg.strip_prefix("-Wno-") {
+            Some(warning) => args.push(format!("-W{}", warning)),
+            None => args.push(arg.to_string()),
+        }
+        (TRIVIAL, args)
+    }
+
+    /// Whether the compiler accepts `arg` when compiling
+    pub fn has_argument(&self, scratch_dir: &Path, arg: &str) -> Result<bool, String> {
+        let (code, args) = self.argument_check(arg);
+        Ok(self
+            .check(scratch_dir, code, &args, Mode::Compile)?
+            .compiled)
+    }
+
+    /// Whether the compiler accepts `arg` when linking
+    pub fn has_link_argument(&self, scratch_dir: &Path, arg: &str) -> Result<bool, String> {
+        let (code, args) = self.argument_check(arg);
+        Ok(self.check(scratch_dir, code, &args, Mode::Link)?.compiled)
+    }
+}
+
+/// Program checking whether `header` can be included
+pub fn header_code(prefix: &str, header: &str) -> String {
+    format!("{}\n#include <{}>\n", prefix, header)
+}
+
+/// Program checking whether `function` links, a prefix is expected to
+/// declare it, otherwise it's declared with a dummy prototype
+pub fn function_code(prefix: &str, function: &str) -> String {
+    if prefix.contains("#include") {
+        return format!(
+            "{prefix}\n\
+             int main(void) {{\n\
+             \x20   void *a = (void *) &{function};\n\
+             \x20   long long b = (long long) a;\n\
+             \x20   return (int) b;\n\
+             }}\n"
+        );
+    }
+
+    format!(
+        "#define {function} reson_disable_define_of_{function}\n\
+         {prefix}\n\
+         #include <limits.h>\n\
+         #undef {function}\n\
+         #ifdef __cplusplus\n\
+         extern \"C\"\n\
+         #endif\n\
+         char {function} (void);\n\
+         #if defined __stub_{function} || defined __stub___{function}\n\
+         fail fail fail this function is not going to work\n\
+         #endif\n\
+         int main(void) {{ return {function} (); }}\n"
+    )
+}
+
+/// Program printing `sizeof(ty)`
+pub fn sizeof_code(prefix: &str, ty: &str) -> String {
+    format!(
+        "{prefix}\n\
+         #include <stdio.h>\n\
+         int main(void) {{\n\
+         \x20   printf(\"%ld\\n\", (long) sizeof({ty}));\n\
+         \x20   return 0;\n\
+         }}\n"
+    )
+}
+
+/// Program printing the alignment of `ty`
+pub fn alignment_code(prefix: &str, ty: &str) -> String {
+    format!(
+        "{prefix}\n\
+         #include <stdio.h>\n\
+         #include <stddef.h>\n\
+         struct reson_alignment_check {{ char c; {ty} target; }};\n\
+         int main(void) {{\n\
+         \x20   printf(\"%d\\n\", (int) offsetof(struct reson_alignment_check, target));\n\
+         \x20   return 0;\n\
+         }}\n"
+    )
+}
+
+/// Program checking whether `ty` has the field `member`
+pub fn member_code(prefix: &str, ty: &str, member: &str) -> String {
+    format!(
+        "{prefix}\n\
+         void reson_member_check(void) {{\n\
+         \x20   {ty} foo;\n\
+         \x20   (void) (foo.{member});\n\
+         }}\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let scratch_dir = tempfile::tempdir().unwrap();
+        let compiler = Compiler::detect(Language::C, None, scratch_dir.path()).unwrap();
+
+        assert!(compiler.has_argument(scratch_dir.path(), "-Wall").unwrap());
+        assert!(compiler
+            .has_argument(scratch_dir.path(), "-Wno-shadow")
+            .unwrap());
+        assert!(!compiler
+            .has_argument(scratch_dir.path(), "-Wreson-no-such-warning")
+            .unwrap());
+        assert!(!compiler
+            .has_argument(scratch_dir.path(), "-Wno-reson-no-such-warning")
+            .unwrap());
+
+        assert!(compiler
+            .has_link_argument(scratch_dir.path(), "-Wl,--as-needed")
+            .unwrap());
+        assert!(!compiler
+            .has_link_argument(scratch_dir.path(), "-Wl,--reson-no-such-flag")
+            .unwrap());
+    }
+
+    #[test]
+    fn programs() {
+        let scratch_dir = tempfile::tempdir().unwrap();
+        let compiler = Compiler::detect(Language::C, None, scratch_dir.path()).unwrap();
+        let check = |code: &str, mode| compiler.check(scratch_dir.path(), code, &[], mode).unwrap();
+
+        assert!(check(&header_code("", "stdio.h"), Mode::Preprocess).compiled);
+        assert!(!check(&header_code("", "reson_missing.h"), Mode::Preprocess).compiled);
+
+        assert!(check(&function_code("", "printf"), Mode::Link).compiled);
+        assert!(check(&function_code("#include <stdio.h>", "printf"), Mode::Link).compiled);
+        assert!(!check(&function_code("", "reson_missing"), Mode::Link).compiled);
+
+        let sizeof = check(&sizeof_code("", "int"), Mode::Run);
+        assert_eq!((sizeof.returncode, sizeof.stdout.trim()), (0, "4"));
+        let alignment = check(&alignment_code("#include <stdint.h>", "int32_t"), Mode::Run);
+        assert_eq!(alignment.stdout.trim(), "4");
+
+        let member = "#include <time.h>";
+        assert!(check(&member_code(member, "struct tm", "tm_sec"), Mode::Compile).compiled);
+        assert!(!check(&member_code(member, "struct tm", "tm_nope"), Mode::Compile).compiled);
+
+        let run = check("int main(void) { return }", Mode::Run);
+        assert!(!run.compiled);
+        let run = check(
+            "#include <stdio.h>\nint main(void) { puts(\"out\"); return 3; }",
+            Mode::Run,
+        );
+        assert_eq!(
+            (run.compiled, run.returncode, run.stdout.as_str()),
+            (true, 3, "out\n")
+        );
+    }
+}
@@ -1 +1,437 @@
-pub struct Compiler {}
+mod args;
+pub mod checks;
+
+pub use args::{Clang, CompilerArgs, Gcc};
+pub use checks::{CheckOutput, Mode};
+
+use crate::options::{BuildOption, OptionType, OptionValue, Section};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    str::FromStr,
+};
+
+/// Languages reson can compile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Language {
+    C,
+    Cpp,
+}
+
+impl Language {
+    /// Environment variable naming the compiler to use
+    pub fn env_var(&self) -> &'static str {
+        match self {
+            Language::C => "CC",
+            Language::Cpp => "CXX",
+        }
+    }
+
+    /// Compilers probed in order when the environment doesn't name one
+    fn candidates(&self) -> &'static [&'static str] {
+        match self {
+            Language::C => &["cc", "gcc", "clang"],
+            Language::Cpp => &["c++", "g++", "clang++"],
+        }
+    }
+
+    /// Argument to `-x` selecting the language
+    fn x_arg(&self) -> &'static str {
+        match self {
+            Language::C => "c",
+            Language::Cpp => "c++",
+        }
+    }
+
+    /// Options the language adds once the project uses it
+    pub fn options(&self) -> Vec<BuildOption> {
+        let (name, description, standards): (_, _, &[&str]) = match self {
+            Language::C => (
+                "c_std",
+                "C language standard to use",
+                &[
+                    "none", "c89", "c99", "c11", "c17", "c18", "c2x", "gnu89", "gnu99", "gnu11",
+                    "gnu17", "gnu18", "gnu2x",
+                ],
+            ),
+            Language::Cpp => (
+                "cpp_std",
+                "C++ language standard to use",
+                &[
+                    "none", "c++98", "c++03", "c++11", "c++14", "c++17", "c++20", "c++2b",
+                    "gnu++98", "gnu++03", "gnu++11", "gnu++14", "gnu++17", "gnu++20", "gnu++2b",
+                ],
+            ),
+        };
+
+        vec![BuildOption::new(
+            name,
+            description,
+            Section::Compiler,
+            OptionType::Combo(standards.iter().map(|s| s.to_string()).collect()),
+            OptionValue::String("none".to_string()),
+        )]
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Cpp => "C++",
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "c" => Ok(Language::C),
+            "cpp" => Ok(Language::Cpp),
+            _ => Err(format!(
+                "Language '{}' is not supported, must be one of c, cpp",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Language::C => write!(f, "c"),
+            Language::Cpp => write!(f, "cpp"),
+        }
+    }
+}
+
+/// Compiler family, as returned by `get_id()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompilerId {
+    Gcc,
+    Clang,
+}
+
+impl Display for CompilerId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompilerId::Gcc => write!(f, "gcc"),
+            CompilerId::Clang => write!(f, "clang"),
+        }
+    }
+}
+
+/// A compiler found on the system and what it reported about itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compiler {
+    pub language: Language,
+    pub id: CompilerId,
+    pub path: PathBuf,
+    /// Arguments that came with the compiler in `CC` or `CXX`
+    pub args: Vec<String>,
+    pub version: String,
+    /// Target triple, e.g. `x86_64-linux-gnu`
+    pub target: String,
+    /// Directories searched for `#include <...>` by default
+    pub include_dirs: Vec<PathBuf>,
+    /// Linker the compiler drives, e.g. `ld.bfd`
+    pub linker: String,
+}
+
+impl Compiler {
+    /// Find the compiler for `language`, `env` is the value of the language's
+    /// environment variable which takes precedence over probing. Probes
+    /// write their input to `scratch_dir`
+    pub fn detect(
+        language: Language,
+        env: Option<String>,
+        scratch_dir: &Path,
+    ) -> Result<Self, String> {
+        if let Some(env) = env.filter(|e| !e.trim().is_empty()) {
+            let mut words = env.split_whitespace().map(str::to_string);
+            let name = words.next().unwrap();
+            return Self::probe(language, &name, words.collect(), scratch_dir).map_err(|e| {
+                format!(
+                    "{} compiler {}={} is not usable: {}",
+                    language.display_name(),
+                    language.env_var(),
+                    env,
+                    e
+                )
+            });
+        }
+
+        let candidates = language.candidates();
+        candidates
+            .iter()
+            .find_map(|name| Self::probe(language, name, vec![], scratch_dir).ok())
+            .ok_or_else(|| {
+                format!(
+                    "No usable {} compiler found, tried {} (set {} to choose one)",
+                    language.display_name(),
+                    candidates.join(", "),
+                    language.env_var()
+                )
+            })
+    }
+
+    fn probe(
+        language: Language,
+        name: &str,
+        args: Vec<String>,
+        scratch_dir: &Path,
+    ) -> Result<Self, String> {
+        let path = which(name).ok_or_else(|| format!("{} not found", name))?;
+        let mut compiler = Self {
+            language,
+            id: CompilerId::Gcc,
+            path,
+            args,
+            version: String::new(),
+            target: String::new(),
+            include_dirs: vec![],
+            linker: String::new(),
+        };
+
+        // An empty source rather than /dev/null, which not every system has
+        fs::create_dir_all(scratch_dir)
+            .map_err(|e| format!("Failed to create {}: {}", scratch_dir.display(), e))?;
+        let empty = scratch_dir.join(format!("empty.{}", language.source_suffix()));
+        fs::write(&empty, "").map_err(|e| format!("Failed to write {}: {}", empty.display(), e))?;
+        let empty = empty.to_string_lossy();
+
+        let macros = compiler.stdout(&["-x", language.x_arg(), "-E", "-dM", &empty])?;
+        (compiler.id, compiler.version) =
+            identify(&macros).ok_or_else(|| format!("{} is neither GCC nor Clang", name))?;
+        compiler.target = compiler.stdout(&["-dumpmachine"])?.trim().to_string();
+
+        let output = compiler.run(&["-x", language.x_arg(), "-E", "-v", &empty])?;
+        compiler.include_dirs = include_dirs(&String::from_utf8_lossy(&output.stderr));
+
+        // Linkers without a GNU-style --version, like Apple's ld64, are
+        // still usable
+        let output = compiler.run(&["-Wl,--version"])?;
+        compiler.linker = linker(&String::from_utf8_lossy(&output.stdout))
+            .unwrap_or("unknown")
+            .to_string();
+
+        Ok(compiler)
+    }
+
+    /// What identifies the compiler for cached check results, changes when
+    /// the binary is replaced
+    pub fn identity(&self) -> String {
+        let binary = std::fs::metadata(&self.path)
+            .map(|m| (m.len(), m.modified().ok()))
+            .ok();
+        format!(
+            "{} {} {} {} {:?}",
+            self.id,
+            self.version,
+            self.path.display(),
+            self.args.join(" "),
+            binary
+        )
+    }
+
+    /// Translation of options to arguments for this compiler
+    pub fn args(&self) -> &'static dyn CompilerArgs {
+        match self.id {
+            CompilerId::Gcc => &Gcc,
+            CompilerId::Clang => &Clang,
+        }
+    }
+
+    /// Run the compiler with `args` after the ones it was configured with
+    pub fn run(&self, args: &[&str]) -> Result<Output, String> {
+        crate::log::run(Command::new(&self.path).args(&self.args).args(args))
+            .map_err(|e| format!("Failed to run {}: {}", self.path.display(), e))
+    }
+
+    fn stdout(&self, args: &[&str]) -> Result<String, String> {
+        let output = self.run(args)?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} {} failed: {}",
+                self.path.display(),
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// Look for an executable in `PATH`, names containing a slash are used as is
+pub fn which(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| path.is_file();
+    if name.contains('/') {
+        return Some(PathBuf::from(name)).filter(|p| is_executable(p));
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+/// Compiler id and version from its predefined macros, Clang also defines
+/// the GCC ones so it has to be checked first
+fn identify(macros: &str) -> Option<(CompilerId, String)> {
+    let define = |name: &str| {
+        macros.lines().find_map(|line| {
+            let mut words = line.split_whitespace();
+            (words.next() == Some("#define") && words.next() == Some(name))
+                .then(|| words.next().unwrap_or_default().to_string())
+        })
+    };
+    let version = |names: [&str; 3]| -> Option<String> {
+        let parts = names.map(define).into_iter().collect::<Option<Vec<_>>>()?;
+        Some(parts.join("."))
+    };
+
+    if define("__clang__").is_some() {
+        let names = ["__clang_major__", "__clang_minor__", "__clang_patchlevel__"];
+        return Some((CompilerId::Clang, version(names)?));
+    }
+    if define("__GNUC__").is_some() {
+        let names = ["__GNUC__", "__GNUC_MINOR__", "__GNUC_PATCHLEVEL__"];
+        return Some((CompilerId::Gcc, version(names)?));
+    }
+
+    None
+}
+
+/// Default include directories from the output of `-E -v`
+fn include_dirs(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("#include <...> search starts here:"))
+        .skip(1)
+        .take_while(|line| !line.starts_with("End of search list."))
+        .map(|line| PathBuf::from(line.trim()))
+        .collect()
+}
+
+/// Linker name from the output of `-Wl,--version`
+fn linker(output: &str) -> Option<&'static str> {
+    output.lines().find_map(|line| {
+        if line.starts_with("GNU ld") {
+            Some("ld.bfd")
+        } else if line.starts_with("GNU gold") {
+            Some("ld.gold")
+        } else if line.starts_with("LLD") || line.contains("(compatible with GNU linkers)") {
+            Some("ld.lld")
+        } else if line.starts_with("mold") {
+            Some("ld.mold")
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_probes() {
+        let gcc = "#define __GNUC__ 12\n#define __GNUC_PATCHLEVEL__ 0\n#define __GNUC_MINOR__ 2\n";
+        assert_eq!(identify(gcc), Some((CompilerId::Gcc, "12.2.0".to_string())));
+
+        let clang = "#define __GNUC__ 4\n#define __GNUC_MINOR__ 2\n#define __GNUC_PATCHLEVEL__ 1\n\
+                     #define __clang__ 1\n#define __clang_major__ 17\n\
+                     #define __clang_minor__ 0\n#define __clang_patchlevel__ 6\n";
+        assert_eq!(
+            identify(clang),
+            Some((CompilerId::Clang, "17.0.6".to_string()))
+        );
+        assert_eq!(identify("#define __STDC__ 1\n"), None);
+
+        let verbose = "ignoring nonexistent directory \"/usr/local/include/x86_64-linux-gnu\"\n\
+                       #include \"...\" search starts here:\n\
+                       #include <...> search starts here:\n \
+                       /usr/lib/gcc/x86_64-linux-gnu/12/include\n \
+                       /usr/include\n\
+                       End of search list.\n";
+        assert_eq!(
+            include_dirs(verbose),
+            [
+                PathBuf::from("/usr/lib/gcc/x86_64-linux-gnu/12/include"),
+                PathBuf::from("/usr/include")
+            ]
+        );
+
+        assert_eq!(
+            linker("collect2 version 12.2.0\nGNU ld (GNU Binutils) 2.40\n"),
+            Some("ld.bfd")
+        );
+        assert_eq!(
+            linker("LLD 17.0.6 (compatible with GNU linkers)\n"),
+            Some("ld.lld")
+        );
+        assert_eq!(
+            linker("mold 2.4.0 (compatible with GNU ld)\n"),
+            Some("ld.mold")
+        );
+        assert_eq!(linker(""), None);
+    }
+
+    #[test]
+    fn detect() {
+        let scratch_dir = tempfile::tempdir().unwrap();
+        let compiler = Compiler::detect(Language::C, None, scratch_dir.path()).unwrap();
+        assert!(!compiler.version.is_empty());
+        assert!(!compiler.target.is_empty());
+        assert!(!compiler.include_dirs.is_empty());
+
+        let error = Compiler::detect(
+            Language::Cpp,
+            Some("reson-no-such-compiler -O2".into()),
+            scratch_dir.path(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "C++ compiler CXX=reson-no-such-compiler -O2 is not usable: \
+             reson-no-such-compiler not found"
+        );
+    }
+
+    #[test]
+    fn unknown_linker() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cc = dir.path().join("cc");
+        // GCC-like, except that probe inputs must exist and the linker
+        // doesn't know --version
+        fs::write(
+            &cc,
+            "#!/bin/sh\n\
+             for last; do :; done\n\
+             case \"$*\" in\n\
+             *-dM*) test -f \"$last\" || exit 1\n\
+             printf '#define __GNUC__ 12\\n#define __GNUC_MINOR__ 2\\n#define __GNUC_PATCHLEVEL__ 0\\n' ;;\n\
+             *-v*) test -f \"$last\" || exit 1 ;;\n\
+             -dumpmachine) echo arm64-apple-darwin ;;\n\
+             *) echo 'ld: unknown option: --version' >&2; exit 1 ;;\n\
+             esac\n",
+        )
+        .unwrap();
+        fs::set_permissions(&cc, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let scratch_dir = dir.path().join("scratch");
+        let compiler = Compiler::detect(
+            Language::C,
+            Some(cc.to_string_lossy().into_owned()),
+            &scratch_dir,
+        )
+        .unwrap();
+        assert_eq!(compiler.version, "12.2.0");
+        assert_eq!(compiler.target, "arm64-apple-darwin");
+        assert_eq!(compiler.linker, "unknown");
+    }
+}
@@ -0,0 +1,388 @@
+use std::{fs, io, path::Path};
+
+/// A value of a configuration data entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    Boolean(bool),
+    Number(i64),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub value: ConfigValue,
+    /// Written as a comment above the entry in generated headers
+    pub description: Option<String>,
+}
+
+/// Values for `configure_file()`, from `configuration_data()`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigurationData {
+    pub entries: Vec<Entry>,
+    /// Set once a file was configured with it, it can't change afterwards
+    pub used: bool,
+}
+
+/// Syntax of the variables in a file to configure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `@VAR@` and `#mesondefine VAR`
+    Meson,
+    /// `${VAR}`, `@VAR@`, `#cmakedefine VAR` and `#cmakedefine01 VAR`
+    Cmake,
+    /// Like `Cmake` without `${VAR}`
+    CmakeAt,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "meson" => Ok(Format::Meson),
+            "cmake" => Ok(Format::Cmake),
+            "cmake@" => Ok(Format::CmakeAt),
+            s => Err(format!(
+                "Format '{}' is not one of 'meson', 'cmake' or 'cmake@'",
+                s
+            )),
+        }
+    }
+}
+
+/// Language of a generated header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    C,
+    Nasm,
+}
+
+impl std::str::FromStr for HeaderFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "c" => Ok(HeaderFormat::C),
+            "nasm" => Ok(HeaderFormat::Nasm),
+            s => Err(format!("Output format '{}' is not one of 'c' or 'nasm'", s)),
+        }
+    }
+}
+
+impl ConfigurationData {
+    /// Add or replace an entry, keeping the position of a replaced one
+    pub fn set(&mut self, name: &str, value: ConfigValue, description: Option<String>) {
+        let entry = Entry {
+            name: name.to_string(),
+            value,
+            description,
+        };
+        match self.entries.iter_mut().find(|e| e.name == name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Take every entry of `other`, overriding those with the same name
+    pub fn merge_from(&mut self, other: &ConfigurationData) {
+        for entry in &other.entries {
+            self.set(&entry.name, entry.value.clone(), entry.description.clone());
+        }
+    }
+
+    /// Substitute the variables in a template, also returning the names of
+    /// those that had no value
+    pub fn substitute(
+        &self,
+        contents: &str,
+        format: Format,
+    ) -> Result<(String, Vec<String>), String> {
+        let mut missing = vec![];
+        let mut result = String::new();
+        for line in contents.split_inclusive('\n') {
+            let (text, newline) = match line.strip_suffix('\n') {
+                Some(text) => (text, "\n"),
+                None => (line, ""),
+            };
+            let define = text.trim_start().strip_prefix('#').map(str::trim_start);
+            let keyword = match format {
+                Format::Meson => "mesondefine",
+                Format::Cmake | Format::CmakeAt => "cmakedefine",
+            };
+
+            match define.and_then(|d| d.strip_prefix(keyword)) {
+                Some(rest) if rest.starts_with(char::is_whitespace) || rest.starts_with("01") => {
+                    result += &self.define(rest, format, &mut missing)?;
+                }
+                _ => result += &self.replace(text, format, &mut missing)?,
+            }
+            result += newline;
+        }
+
+        Ok((result, missing))
+    }
+
+    /// Replace `@VAR@`, and `${VAR}` in CMake syntax, `\@` is a literal `@`
+    fn replace(
+        &self,
+        line: &str,
+        format: Format,
+        missing: &mut Vec<String>,
+    ) -> Result<String, String> {
+        let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+
+        let mut result = String::new();
+        let mut rest = line;
+        while let Some(start) = rest.find(['@', '$', '\\']) {
+            result += &rest[..start];
+            rest = &rest[start..];
+
+            if let Some(after) = rest.strip_prefix("\\@") {
+                result.push('@');
+                rest = after;
+                continue;
+            }
+
+            let name = match rest.chars().next().unwrap() {
+                '@' => rest[1..]
+                    .find(|c: char| !is_name(c))
+                    .filter(|&end| end > 0 && rest[1 + end..].starts_with('@'))
+                    .map(|end| (&rest[1..1 + end], 2 + end)),
+                '$' if format == Format::Cmake && rest.starts_with("${") => rest[2..]
+                    .find(|c: char| !is_name(c))
+                    .filter(|&end| end > 0 && rest[2 + end..].starts_with('}'))
+                    .map(|end| (&rest[2..2 + end], 3 + end)),
+                _ => None,
+            };
+            let Some((name, len)) = name else {
+                let c = rest.chars().next().unwrap();
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            };
+
+            match self.get(name) {
+                Some(entry) => result += &value_string(name, &entry.value, format)?,
+                None if !missing.iter().any(|m| m == name) => missing.push(name.to_string()),
+                None => {}
+            }
+            rest = &rest[len..];
+        }
+
+        Ok(result + rest)
+    }
+
+    /// The line for a `#mesondefine` or `#cmakedefine`, `rest` follows the
+    /// keyword
+    fn define(
+        &self,
+        rest: &str,
+        format: Format,
+        missing: &mut Vec<String>,
+    ) -> Result<String, String> {
+        if let Some(rest) = rest.strip_prefix("01") {
+            let name = rest.trim();
+            let defined = self.get(name).is_some_and(|e| is_true(&e.value));
+            return Ok(format!("#define {} {}", name, defined as u8));
+        }
+
+        let mut tokens = rest.split_whitespace();
+        let name = tokens
+            .next()
+            .ok_or_else(|| "Define without a variable name".to_string())?;
+        let tail = tokens.collect::<Vec<_>>().join(" ");
+
+        if format == Format::Meson {
+            if !tail.is_empty() {
+                return Err(format!(
+                    "#mesondefine does not contain exactly two tokens: #mesondefine {}",
+                    rest.trim()
+                ));
+            }
+            return Ok(match self.get(name).map(|e| &e.value) {
+                None => format!("/* #undef {} */", name),
+                Some(ConfigValue::Boolean(true)) => format!("#define {}", name),
+                Some(ConfigValue::Boolean(false)) => format!("#undef {}", name),
+                Some(ConfigValue::Number(n)) => format!("#define {} {}", name, n),
+                Some(ConfigValue::String(s)) => format!("#define {} {}", name, s),
+            });
+        }
+
+        match self.get(name) {
+            Some(entry) if is_true(&entry.value) => {
+                let tail = self.replace(&tail, format, missing)?;
+                Ok(format!("#define {} {}", name, tail).trim_end().to_string())
+            }
+            _ => Ok(format!("/* #undef {} */", name)),
+        }
+    }
+
+    /// A header defining every entry, for `configure_file()` without input
+    pub fn header(&self, format: HeaderFormat, macro_name: Option<&str>) -> String {
+        let mut entries = self.entries.iter().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let (notice, define, undef) = match format {
+            HeaderFormat::C => (
+                "/*\n * Autogenerated by the Meson build system.\n * Do not edit, your changes will be lost.\n */\n\n",
+                "#define",
+                "#undef",
+            ),
+            HeaderFormat::Nasm => (
+                "; Autogenerated by the Meson build system.\n; Do not edit, your changes will be lost.\n\n",
+                "%define",
+                "%undef",
+            ),
+        };
+        let mut header = notice.to_string();
+        match (format, macro_name) {
+            (HeaderFormat::C, Some(name)) => {
+                header += &format!("#ifndef {}\n#define {}\n\n", name, name)
+            }
+            (HeaderFormat::C, None) => header += "#pragma once\n\n",
+            (HeaderFormat::Nasm, _) => {}
+        }
+
+        for entry in entries {
+            if let Some(description) = &entry.description {
+                header += &match format {
+                    HeaderFormat::C => format!("/* {} */\n", description),
+                    HeaderFormat::Nasm => format!("; {}\n", description),
+                };
+            }
+            header += &match &entry.value {
+                ConfigValue::Boolean(true) => format!("{} {}", define, entry.name),
+                ConfigValue::Boolean(false) => format!("{} {}", undef, entry.name),
+                ConfigValue::Number(n) => format!("{} {} {}", define, entry.name, n),
+                ConfigValue::String(s) => format!("{} {} {}", define, entry.name, s),
+            };
+            header += "\n\n";
+        }
+
+        if let (HeaderFormat::C, Some(name)) = (format, macro_name) {
+            header += &format!("#endif // {}\n", name);
+        }
+        header
+    }
+}
+
+/// Whether `#cmakedefine` defines the variable, like CMake's `if()`
+fn is_true(value: &ConfigValue) -> bool {
+    match value {
+        ConfigValue::Boolean(b) => *b,
+        ConfigValue::Number(n) => *n != 0,
+        ConfigValue::String(s) => {
+            let s = s.to_uppercase();
+            !(s.is_empty()
+                || ["0", "OFF", "NO", "FALSE", "N", "IGNORE", "NOTFOUND"].contains(&s.as_str())
+                || s.ends_with("-NOTFOUND"))
+        }
+    }
+}
+
+fn value_string(name: &str, value: &ConfigValue, format: Format) -> Result<String, String> {
+    Ok(match value {
+        ConfigValue::String(s) => s.clone(),
+        ConfigValue::Number(n) => n.to_string(),
+        ConfigValue::Boolean(b) if format != Format::Meson => (*b as u8).to_string(),
+        ConfigValue::Boolean(_) => {
+            return Err(format!(
+                "Tried to replace variable '{}' value with something other than a string or int",
+                name
+            ))
+        }
+    })
+}
+
+/// Write `contents` unless the file already has them, so that what
+/// depends on it isn't rebuilt needlessly. Returns whether it was written.
+pub fn write_if_changed(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    if fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> ConfigurationData {
+        let mut data = ConfigurationData::default();
+        data.set("VERSION", ConfigValue::String("\"1.0\"".to_string()), None);
+        data.set(
+            "COUNT",
+            ConfigValue::Number(3),
+            Some("How many".to_string()),
+        );
+        data.set("HAVE_FOO", ConfigValue::Boolean(true), None);
+        data.set("HAVE_BAR", ConfigValue::Boolean(false), None);
+        data
+    }
+
+    #[test]
+    fn substitute_meson() {
+        let (result, missing) = data()
+            .substitute(
+                "v = @VERSION@ x@COUNT@ \\@COUNT@ a@b.c @MISSING@\n#mesondefine HAVE_FOO\n  # mesondefine HAVE_BAR\n#mesondefine COUNT\n#mesondefine NOPE\n${COUNT}",
+                Format::Meson,
+            )
+            .unwrap();
+        assert_eq!(
+            result,
+            "v = \"1.0\" x3 @COUNT@ a@b.c \n#define HAVE_FOO\n#undef HAVE_BAR\n#define COUNT 3\n/* #undef NOPE */\n${COUNT}"
+        );
+        assert_eq!(missing, ["MISSING"]);
+
+        assert!(data()
+            .substitute("#mesondefine HAVE_FOO 1\n", Format::Meson)
+            .is_err());
+        assert!(data().substitute("@HAVE_FOO@", Format::Meson).is_err());
+    }
+
+    #[test]
+    fn substitute_cmake() {
+        let template = "${COUNT} @COUNT@ @HAVE_FOO@\n#cmakedefine HAVE_FOO ${VERSION}\n#cmakedefine HAVE_BAR 1\n#cmakedefine01 HAVE_FOO\n#cmakedefine01 NOPE\n#cmakedefine COUNT\n";
+        let (result, missing) = data().substitute(template, Format::Cmake).unwrap();
+        assert_eq!(
+            result,
+            "3 3 1\n#define HAVE_FOO \"1.0\"\n/* #undef HAVE_BAR */\n#define HAVE_FOO 1\n#define NOPE 0\n#define COUNT\n"
+        );
+        assert!(missing.is_empty());
+
+        let (result, _) = data()
+            .substitute("${COUNT} @COUNT@", Format::CmakeAt)
+            .unwrap();
+        assert_eq!(result, "${COUNT} 3");
+    }
+
+    #[test]
+    fn header() {
+        assert_eq!(
+            data().header(HeaderFormat::C, None),
+            "/*\n * Autogenerated by the Meson build system.\n * Do not edit, your changes will be lost.\n */\n\n\
+             #pragma once\n\n\
+             /* How many */\n#define COUNT 3\n\n\
+             #undef HAVE_BAR\n\n\
+             #define HAVE_FOO\n\n\
+             #define VERSION \"1.0\"\n\n"
+        );
+
+        let header = data().header(HeaderFormat::C, Some("CONFIG_H"));
+        assert!(header.contains("#ifndef CONFIG_H\n#define CONFIG_H\n\n"));
+        assert!(header.ends_with("#endif // CONFIG_H\n"));
+
+        let nasm = data().header(HeaderFormat::Nasm, None);
+        assert!(nasm.starts_with("; Autogenerated by the Meson build system.\n; Do not edit"));
+        assert!(nasm.contains("; How many\n%define COUNT 3\n\n%undef HAVE_BAR\n"));
+    }
+}
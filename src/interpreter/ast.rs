@@ -1,5 +1,4 @@
 use enum_as_inner::EnumAsInner;
-use std::collections::HashMap;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Program {
@@ -9,13 +8,36 @@ pub struct Program {
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Arguments {
     pub args: Vec<Node>,
-    pub kwargs: HashMap<String, Node>,
+    /// In source order
+    pub kwargs: Vec<(String, Node)>,
+}
+
+/// Location of a node in its source file, both 1-based
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Function {
     pub name: String,
     pub args: Arguments,
+    pub span: Span,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct MethodCall {
+    pub object: Box<Node>,
+    pub name: String,
+    pub args: Arguments,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -31,12 +53,14 @@ pub struct Arithmetic {
     pub left: Box<Node>,
     pub right: Box<Node>,
     pub op: MathOp,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Assignment {
     pub left: Box<Node>,
     pub right: Box<Node>,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -48,6 +72,8 @@ pub struct IfClause {
 pub struct If {
     pub condition: Node,
     pub block: Node,
+    /// Of the condition
+    pub span: Span,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -64,6 +90,7 @@ pub struct Comparison {
     pub left: Box<Node>,
     pub op: CompareOp,
     pub right: Box<Node>,
+    pub span: Span,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, EnumAsInner)]
@@ -73,6 +100,8 @@ pub enum Node {
     Boolean(bool),
     Number(i64),
     String(String),
+    Array(Vec<Node>),
+    Dict(Vec<(Node, Node)>),
 
     Identifier(String),
 
@@ -84,6 +113,22 @@ pub enum Node {
     Or,
     And,
     Function(Function),
+    MethodCall(MethodCall),
     Program(Program),
     Codeblock(Vec<Node>),
 }
+
+impl Node {
+    /// Where the node starts, for the kinds of nodes that record it
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Node::Function(function) => Some(function.span),
+            Node::MethodCall(call) => Some(call.span),
+            Node::Arithmetic(arithmetic) => Some(arithmetic.span),
+            Node::Assignment(assignment) => Some(assignment.span),
+            Node::Comparison(comparison) => Some(comparison.span),
+            Node::IfClause(clause) => clause.ifs.first().map(|branch| branch.span),
+            _ => None,
+        }
+    }
+}
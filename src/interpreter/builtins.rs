@@ -0,0 +1,1115 @@
+use super::{
+    configure_file::SetKind,
+    signature::{Args, Kwarg, Literal, Positional, Signature, Type},
+    value::Value,
+    Error, Interpreter,
+};
+use crate::{options::Feature, TargetKind};
+
+pub struct BuiltinFunction {
+    pub signature: Signature,
+    pub call: fn(&mut Interpreter, Args) -> Result<Value, Error>,
+}
+
+pub struct BuiltinMethod {
+    /// `Value::type_name` of the object the method is called on
+    pub object: &'static str,
+    pub signature: Signature,
+    pub call: fn(&mut Interpreter, Value, Args) -> Result<Value, Error>,
+}
+
+pub fn function(name: &str) -> Option<&'static BuiltinFunction> {
+    FUNCTIONS.iter().find(|f| f.signature.name == name)
+}
+
+pub fn method(object: &Value, name: &str) -> Option<&'static BuiltinMethod> {
+    METHODS
+        .iter()
+        .find(|m| m.object == object.type_name() && m.signature.name == name)
+}
+
+pub fn function_names() -> Vec<&'static str> {
+    FUNCTIONS.iter().map(|f| f.signature.name).collect()
+}
+
+pub fn method_names(object: &str) -> Vec<&'static str> {
+    METHODS
+        .iter()
+        .filter(|m| m.object == object)
+        .map(|m| m.signature.name)
+        .collect()
+}
+
+const STRING: Type = Type::String;
+const STRINGS: Type = Type::List(&Type::String);
+const ANY: Type = Type::Any;
+const ANYS: Type = Type::List(&Type::Any);
+const TARGETS: Type = Type::List(&Type::BuildTarget);
+const DEPENDENCIES: Type = Type::List(&Type::Dependency);
+const TARGETS_ANY_KIND: Type = Type::List(&Type::OneOf(&[Type::BuildTarget, Type::CustomTarget]));
+const SOURCE: Type = Type::OneOf(&[Type::String, Type::File]);
+/// Strings are shorthand for `include_directories()` of them
+const INCLUDE_DIRECTORIES: Type =
+    Type::List(&Type::OneOf(&[Type::IncludeDirectories, Type::String]));
+/// Files that exist once configured, or once a custom target ran
+const INPUT_LIST: Type = Type::List(&Type::OneOf(&[
+    Type::String,
+    Type::File,
+    Type::CustomTarget,
+]));
+/// Sources of a target, including per-target generated ones
+const SOURCE_LIST: Type = Type::List(&Type::OneOf(&[
+    Type::String,
+    Type::File,
+    Type::CustomTarget,
+    Type::GeneratedList,
+]));
+
+/// Keyword arguments of the compiler checks, plus the given extras
+macro_rules! check_kwargs {
+    ($($extra:expr),* $(,)?) => {
+        &[
+            Kwarg::new("prefix", STRINGS),
+            Kwarg::new("args", STRINGS),
+            Kwarg::new("dependencies", DEPENDENCIES),
+            Kwarg::new("include_directories", INCLUDE_DIRECTORIES),
+            Kwarg::new("no_builtin_args", Type::Boolean).default(Literal::Boolean(false)),
+            $($extra),*
+        ]
+    };
+}
+
+/// Keyword arguments of the checks taking a whole program
+const CODE_KWARGS: &[Kwarg] = &[
+    Kwarg::new("name", STRING),
+    Kwarg::new("args", STRINGS),
+    Kwarg::new("dependencies", DEPENDENCIES),
+    Kwarg::new("include_directories", INCLUDE_DIRECTORIES),
+    Kwarg::new("no_builtin_args", Type::Boolean).default(Literal::Boolean(false)),
+    Kwarg::new("werror", Type::Boolean)
+        .default(Literal::Boolean(false))
+        .since("1.3.0"),
+];
+
+/// Keyword arguments every build target accepts, plus the given extras
+macro_rules! target_kwargs {
+    ($($extra:expr),* $(,)?) => {
+        &[
+            Kwarg::new("sources", SOURCE_LIST),
+            Kwarg::new("dependencies", DEPENDENCIES),
+            Kwarg::new("include_directories", INCLUDE_DIRECTORIES),
+            Kwarg::new("link_with", TARGETS),
+            Kwarg::new("link_whole", TARGETS),
+            Kwarg::new("link_args", STRINGS),
+            Kwarg::new("link_depends", ANYS),
+            Kwarg::new("link_language", STRING),
+            Kwarg::new("c_args", STRINGS),
+            Kwarg::new("cpp_args", STRINGS),
+            Kwarg::new("objects", ANYS),
+            Kwarg::new("extra_files", ANYS),
+            Kwarg::new("install", Type::Boolean).default(Literal::Boolean(false)),
+            Kwarg::new("install_dir", ANY),
+            Kwarg::new("install_mode", ANYS),
+            Kwarg::new("install_rpath", STRING),
+            Kwarg::new("install_tag", STRING).since("0.60.0"),
+            Kwarg::new("build_rpath", STRING),
+            Kwarg::new("build_by_default", Type::Boolean).default(Literal::Boolean(true)),
+            Kwarg::new("gnu_symbol_visibility", STRING).default(Literal::String("")),
+            Kwarg::new("implicit_include_directories", Type::Boolean),
+            Kwarg::new("name_prefix", ANY),
+            Kwarg::new("name_suffix", ANY),
+            Kwarg::new("native", Type::Boolean),
+            Kwarg::new("override_options", ANY),
+            $($extra,)*
+        ]
+    };
+}
+
+const EXECUTABLE_KWARGS: &[Kwarg] = target_kwargs![
+    Kwarg::new("pie", Type::Boolean),
+    Kwarg::new("export_dynamic", Type::Boolean).since("0.45.0"),
+    Kwarg::new("implib", Type::OneOf(&[Type::Boolean, Type::String])),
+    Kwarg::new("win_subsystem", STRING).since("0.56.0"),
+    Kwarg::new("gui_app", Type::Boolean).deprecated("use win_subsystem instead"),
+    Kwarg::new("vs_module_defs", SOURCE).since("1.3.0"),
+];
+
+const LIBRARY_KWARGS: &[Kwarg] = target_kwargs![
+    Kwarg::new("pic", Type::Boolean),
+    Kwarg::new("prelink", Type::Boolean),
+    Kwarg::new("version", STRING),
+    Kwarg::new("soversion", Type::OneOf(&[Type::String, Type::Number])),
+    Kwarg::new("darwin_versions", ANYS),
+    Kwarg::new("vs_module_defs", SOURCE),
+];
+
+/// Only valid in option files, so it isn't one of the regular functions
+pub const OPTION: Signature = Signature::new("option")
+    .positional(&[Positional::new("option_name", STRING)])
+    .kwargs(&[
+        Kwarg::new("type", STRING),
+        Kwarg::new("description", STRING),
+        Kwarg::new("value", ANY),
+        Kwarg::new("choices", ANYS),
+        Kwarg::new("min", Type::Number),
+        Kwarg::new("max", Type::Number),
+        Kwarg::new("yield", Type::Boolean).default(Literal::Boolean(false)),
+        Kwarg::new("deprecated", ANY).since("0.60.0"),
+    ]);
+
+/// Keyword arguments of `benchmark()`, plus the given extras for `test()`
+macro_rules! test_kwargs {
+    ($($extra:expr),* $(,)?) => {
+        &[
+            Kwarg::new(
+                "args",
+                Type::List(&Type::OneOf(&[
+                    Type::String,
+                    Type::File,
+                    Type::BuildTarget,
+                    Type::CustomTarget,
+                ])),
+            ),
+            Kwarg::new("env", Type::OneOf(&[Type::Dict, STRINGS])),
+            Kwarg::new("timeout", Type::Number).default(Literal::Number(30)),
+            Kwarg::new("suite", STRINGS),
+            Kwarg::new("should_fail", Type::Boolean).default(Literal::Boolean(false)),
+            Kwarg::new("protocol", STRING)
+                .default(Literal::String("exitcode"))
+                .since("0.50.0"),
+            Kwarg::new("depends", TARGETS_ANY_KIND).since("0.46.0"),
+            Kwarg::new("workdir", STRING),
+            Kwarg::new("priority", Type::Number)
+                .default(Literal::Number(0))
+                .since("0.52.0"),
+            $($extra),*
+        ]
+    };
+}
+
+const TEST_POSITIONAL: &[Positional] = &[
+    Positional::new("name", STRING),
+    Positional::new(
+        "executable",
+        Type::OneOf(&[
+            Type::BuildTarget,
+            Type::CustomTarget,
+            Type::Program,
+            Type::File,
+        ]),
+    ),
+];
+
+const TARGET_POSITIONAL: &[Positional] = &[Positional::new("target_name", STRING)];
+const SOURCES: Positional = Positional::new("sources", SOURCE_LIST);
+
+const FUNCTIONS: &[BuiltinFunction] = &[
+    BuiltinFunction {
+        signature: Signature::new("project")
+            .positional(&[Positional::new("project_name", STRING)])
+            .varargs(Positional::new("language", STRINGS))
+            .kwargs(&[
+                Kwarg::new("version", STRING),
+                Kwarg::new("meson_version", STRING),
+                Kwarg::new("license", STRINGS),
+                Kwarg::new("license_files", STRINGS).since("1.1.0"),
+                Kwarg::new("default_options", STRINGS).default(Literal::EmptyArray),
+                Kwarg::new("subproject_dir", STRING),
+            ]),
+        call: Interpreter::project,
+    },
+    BuiltinFunction {
+        signature: Signature::new("executable")
+            .positional(TARGET_POSITIONAL)
+            .varargs(SOURCES)
+            .kwargs(EXECUTABLE_KWARGS),
+        call: |i, args| i.build_target(args, TargetKind::Executable),
+    },
+    BuiltinFunction {
+        signature: Signature::new("static_library")
+            .positional(TARGET_POSITIONAL)
+            .varargs(SOURCES)
+            .kwargs(LIBRARY_KWARGS),
+        call: |i, args| i.build_target(args, TargetKind::StaticLibrary),
+    },
+    BuiltinFunction {
+        signature: Signature::new("shared_library")
+            .positional(TARGET_POSITIONAL)
+            .varargs(SOURCES)
+            .kwargs(LIBRARY_KWARGS),
+        call: |i, args| i.build_target(args, TargetKind::SharedLibrary),
+    },
+    BuiltinFunction {
+        signature: Signature::new("both_libraries")
+            .positional(TARGET_POSITIONAL)
+            .varargs(SOURCES)
+            .kwargs(LIBRARY_KWARGS),
+        call: Interpreter::both_libraries,
+    },
+    BuiltinFunction {
+        signature: Signature::new("library")
+            .positional(TARGET_POSITIONAL)
+            .varargs(SOURCES)
+            .kwargs(LIBRARY_KWARGS),
+        call: Interpreter::library,
+    },
+    BuiltinFunction {
+        signature: Signature::new("get_option")
+            .positional(&[Positional::new("option_name", STRING)]),
+        call: Interpreter::get_option,
+    },
+    BuiltinFunction {
+        signature: Signature::new("files").varargs(Positional::new("file", STRINGS)),
+        call: Interpreter::files,
+    },
+    BuiltinFunction {
+        signature: Signature::new("include_directories")
+            .varargs(Positional::new("includes", STRINGS))
+            .kwargs(&[Kwarg::new("is_system", Type::Boolean).default(Literal::Boolean(false))]),
+        call: Interpreter::include_directories,
+    },
+    BuiltinFunction {
+        signature: Signature::new("dependency")
+            .varargs(Positional::new("names", STRINGS))
+            .kwargs(&[
+                Kwarg::new("version", STRINGS),
+                Kwarg::new("required", Type::OneOf(&[Type::Boolean, Type::Feature]))
+                    .default(Literal::Boolean(true)),
+                Kwarg::new("method", STRING).default(Literal::String("auto")),
+                Kwarg::new("static", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("modules", STRINGS),
+                Kwarg::new("native", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("fallback", STRINGS),
+                Kwarg::new("allow_fallback", Type::Boolean).since("0.56.0"),
+                Kwarg::new("default_options", STRINGS).since("0.37.0"),
+                Kwarg::new("include_type", STRING)
+                    .default(Literal::String("preserve"))
+                    .since("0.52.0"),
+                Kwarg::new("not_found_message", STRING).since("0.50.0"),
+                Kwarg::new("disabler", Type::Boolean).since("0.49.0"),
+            ]),
+        call: Interpreter::dependency,
+    },
+    BuiltinFunction {
+        signature: Signature::new("declare_dependency").kwargs(&[
+            Kwarg::new("compile_args", STRINGS),
+            Kwarg::new("link_args", STRINGS),
+            Kwarg::new("include_directories", INCLUDE_DIRECTORIES),
+            Kwarg::new("sources", INPUT_LIST),
+            Kwarg::new("link_with", TARGETS),
+            Kwarg::new("link_whole", TARGETS).since("0.46.0"),
+            Kwarg::new("dependencies", DEPENDENCIES),
+            Kwarg::new("variables", Type::OneOf(&[Type::Dict, STRINGS])).since("0.54.0"),
+            Kwarg::new("version", STRING),
+            Kwarg::new("d_module_versions", ANYS).since("0.62.0"),
+            Kwarg::new("d_import_dirs", ANYS).since("0.62.0"),
+            Kwarg::new("objects", ANYS).since("1.1.0"),
+            Kwarg::new("extra_files", ANYS).since("1.2.0"),
+        ]),
+        call: Interpreter::declare_dependency,
+    },
+    BuiltinFunction {
+        signature: Signature::new("find_program")
+            .varargs(Positional::new("program_name", Type::List(&SOURCE)))
+            .kwargs(&[
+                Kwarg::new("required", Type::OneOf(&[Type::Boolean, Type::Feature]))
+                    .default(Literal::Boolean(true)),
+                Kwarg::new("version", STRINGS).since("0.52.0"),
+                Kwarg::new("version_argument", STRING).since("1.5.0"),
+                Kwarg::new("dirs", STRINGS).since("0.53.0"),
+                Kwarg::new("native", Type::Boolean)
+                    .default(Literal::Boolean(false))
+                    .since("0.43.0"),
+                Kwarg::new("disabler", Type::Boolean).since("0.49.0"),
+            ]),
+        call: Interpreter::find_program,
+    },
+    BuiltinFunction {
+        signature: Signature::new("custom_target")
+            .positional(&[Positional::new("name", STRING).optional()])
+            .kwargs(&[
+                Kwarg::new(
+                    "input",
+                    Type::List(&Type::OneOf(&[
+                        Type::String,
+                        Type::File,
+                        Type::BuildTarget,
+                        Type::CustomTarget,
+                    ])),
+                ),
+                Kwarg::new("output", STRINGS),
+                Kwarg::new("command", ANYS),
+                Kwarg::new("capture", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("feed", Type::Boolean)
+                    .default(Literal::Boolean(false))
+                    .since("0.59.0"),
+                Kwarg::new("depfile", STRING),
+                Kwarg::new("depend_files", Type::List(&SOURCE)),
+                Kwarg::new("depends", TARGETS_ANY_KIND),
+                Kwarg::new("build_always_stale", Type::Boolean)
+                    .default(Literal::Boolean(false))
+                    .since("0.47.0"),
+                Kwarg::new("build_by_default", Type::Boolean).since("0.38.0"),
+                Kwarg::new("install", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("install_dir", STRINGS),
+                Kwarg::new("install_mode", ANYS),
+                Kwarg::new("install_tag", STRINGS).since("0.60.0"),
+                Kwarg::new("env", ANY).since("0.57.0"),
+            ]),
+        call: Interpreter::custom_target,
+    },
+    BuiltinFunction {
+        signature: Signature::new("generator")
+            .positional(&[Positional::new(
+                "exe",
+                Type::OneOf(&[Type::Program, Type::BuildTarget]),
+            )])
+            .kwargs(&[
+                Kwarg::new("arguments", STRINGS),
+                Kwarg::new("output", STRINGS),
+                Kwarg::new("depfile", STRING),
+                Kwarg::new("capture", Type::Boolean)
+                    .default(Literal::Boolean(false))
+                    .since("0.43.0"),
+                Kwarg::new("depends", TARGETS_ANY_KIND).since("0.51.0"),
+            ]),
+        call: Interpreter::generator,
+    },
+    BuiltinFunction {
+        signature: Signature::new("configuration_data").positional(&[Positional::new(
+            "data",
+            Type::Dict,
+        )
+        .optional()]),
+        call: Interpreter::configuration_data,
+    },
+    BuiltinFunction {
+        signature: Signature::new("configure_file").kwargs(&[
+            Kwarg::new("input", Type::List(&SOURCE)),
+            Kwarg::new("output", STRING),
+            Kwarg::new(
+                "configuration",
+                Type::OneOf(&[Type::ConfigurationData, Type::Dict]),
+            ),
+            Kwarg::new("copy", Type::Boolean)
+                .default(Literal::Boolean(false))
+                .since("0.47.0"),
+            Kwarg::new("command", ANYS),
+            Kwarg::new("capture", Type::Boolean)
+                .default(Literal::Boolean(false))
+                .since("0.41.0"),
+            Kwarg::new("depfile", STRING).since("0.52.0"),
+            Kwarg::new("format", STRING)
+                .default(Literal::String("meson"))
+                .since("0.46.0"),
+            Kwarg::new("output_format", STRING)
+                .default(Literal::String("c"))
+                .since("0.47.0"),
+            Kwarg::new("macro_name", STRING).since("1.3.0"),
+            Kwarg::new("encoding", STRING)
+                .default(Literal::String("utf-8"))
+                .since("0.47.0"),
+            Kwarg::new("install", Type::Boolean).since("0.50.0"),
+            Kwarg::new("install_dir", STRING),
+            Kwarg::new("install_mode", ANYS).since("0.47.0"),
+            Kwarg::new("install_tag", STRING).since("0.60.0"),
+        ]),
+        call: Interpreter::configure_file,
+    },
+    BuiltinFunction {
+        signature: Signature::new("subdir")
+            .positional(&[Positional::new("dir_name", STRING)])
+            .kwargs(&[Kwarg::new("if_found", ANYS).since("0.44.0")]),
+        call: Interpreter::subdir,
+    },
+    BuiltinFunction {
+        signature: Signature::new("test")
+            .positional(TEST_POSITIONAL)
+            .kwargs(test_kwargs![
+                Kwarg::new("is_parallel", Type::Boolean).default(Literal::Boolean(true))
+            ]),
+        call: |i, args| i.test(args, false),
+    },
+    BuiltinFunction {
+        signature: Signature::new("benchmark")
+            .positional(TEST_POSITIONAL)
+            .kwargs(test_kwargs![]),
+        call: |i, args| i.test(args, true),
+    },
+    BuiltinFunction {
+        signature: Signature::new("message")
+            .positional(&[Positional::new("text", ANY)])
+            .varargs(Positional::new("rest", ANY)),
+        call: Interpreter::message,
+    },
+    BuiltinFunction {
+        signature: Signature::new("warning")
+            .positional(&[Positional::new("text", ANY)])
+            .varargs(Positional::new("rest", ANY))
+            .since("0.44.0"),
+        call: Interpreter::warning,
+    },
+    BuiltinFunction {
+        signature: Signature::new("error")
+            .positional(&[Positional::new("message", ANY)])
+            .varargs(Positional::new("rest", ANY)),
+        call: Interpreter::error,
+    },
+    BuiltinFunction {
+        signature: Signature::new("assert").positional(&[
+            Positional::new("condition", Type::Boolean),
+            Positional::new("message", STRING).optional(),
+        ]),
+        call: Interpreter::assert,
+    },
+    BuiltinFunction {
+        signature: Signature::new("summary")
+            .positional(&[
+                Positional::new("key", Type::OneOf(&[Type::String, Type::Dict])),
+                Positional::new("value", ANY).optional(),
+            ])
+            .kwargs(&[
+                Kwarg::new("section", STRING).default(Literal::String("")),
+                Kwarg::new("bool_yn", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("list_sep", STRING).since("0.54.0"),
+            ])
+            .since("0.53.0"),
+        call: Interpreter::add_summary,
+    },
+];
+
+const METHODS: &[BuiltinMethod] = &[
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("strip")
+            .positional(&[Positional::new("strip_chars", STRING).optional()]),
+        call: |_, s, args| {
+            let s = s.into_string().unwrap();
+            Ok(Value::String(match args.string(0) {
+                Some(chars) => s.trim_matches(|c| chars.contains(c)).to_string(),
+                None => s.trim().to_string(),
+            }))
+        },
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("to_lower"),
+        call: |_, s, _| Ok(Value::String(s.into_string().unwrap().to_lowercase())),
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("to_upper"),
+        call: |_, s, _| Ok(Value::String(s.into_string().unwrap().to_uppercase())),
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("startswith").positional(&[Positional::new("prefix", STRING)]),
+        call: |_, s, args| {
+            Ok(Value::Boolean(
+                s.into_string()
+                    .unwrap()
+                    .starts_with(&args.string(0).unwrap()),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("endswith").positional(&[Positional::new("suffix", STRING)]),
+        call: |_, s, args| {
+            Ok(Value::Boolean(
+                s.into_string().unwrap().ends_with(&args.string(0).unwrap()),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("contains")
+            .positional(&[Positional::new("fragment", STRING)])
+            .since("0.56.0"),
+        call: |_, s, args| {
+            Ok(Value::Boolean(
+                s.into_string().unwrap().contains(&args.string(0).unwrap()),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("split")
+            .positional(&[Positional::new("split_string", STRING).optional()]),
+        call: |_, s, args| {
+            let s = s.into_string().unwrap();
+            let parts: Vec<&str> = match args.string(0) {
+                Some(sep) => s.split(sep.as_str()).collect(),
+                None => s.split_whitespace().collect(),
+            };
+            Ok(Value::Array(
+                parts
+                    .into_iter()
+                    .map(|p| Value::String(p.to_string()))
+                    .collect(),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "str",
+        signature: Signature::new("join").varargs(Positional::new("strings", STRINGS)),
+        call: |_, s, args| {
+            let parts = args
+                .varargs
+                .into_iter()
+                .flat_map(Value::flatten)
+                .map(|v| v.into_string().unwrap())
+                .collect::<Vec<_>>();
+            Ok(Value::String(parts.join(&s.into_string().unwrap())))
+        },
+    },
+    BuiltinMethod {
+        object: "array",
+        signature: Signature::new("length"),
+        call: |_, a, _| Ok(Value::Number(a.into_array().unwrap().len() as i64)),
+    },
+    BuiltinMethod {
+        object: "array",
+        signature: Signature::new("contains").positional(&[Positional::new("item", ANY)]),
+        call: |_, a, args| {
+            let item = &args.positional[0];
+            Ok(Value::Boolean(a.flatten().contains(item)))
+        },
+    },
+    BuiltinMethod {
+        object: "array",
+        signature: Signature::new("get").positional(&[
+            Positional::new("index", Type::Number),
+            Positional::new("fallback", ANY).optional(),
+        ]),
+        call: |_, a, args| {
+            let a = a.into_array().unwrap();
+            let index = *args.positional[0].as_number().unwrap();
+            // Negative indices count from the end
+            let i = if index < 0 {
+                a.len() as i64 + index
+            } else {
+                index
+            };
+
+            match (
+                usize::try_from(i).ok().and_then(|i| a.get(i)),
+                args.positional.get(1),
+            ) {
+                (Some(v), _) => Ok(v.clone()),
+                (None, Some(fallback)) => Ok(fallback.clone()),
+                (None, None) => Err(Error::InvalidArguments(format!(
+                    "Index {} out of bounds of array of size {}",
+                    index,
+                    a.len()
+                ))),
+            }
+        },
+    },
+    BuiltinMethod {
+        object: "dict",
+        signature: Signature::new("has_key").positional(&[Positional::new("key", STRING)]),
+        call: |_, d, args| {
+            let key = args.string(0).unwrap();
+            Ok(Value::Boolean(
+                d.into_dict().unwrap().iter().any(|(k, _)| *k == key),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "dict",
+        signature: Signature::new("get").positional(&[
+            Positional::new("key", STRING),
+            Positional::new("fallback", ANY).optional(),
+        ]),
+        call: |_, d, args| {
+            let key = args.string(0).unwrap();
+            let value = d.into_dict().unwrap().into_iter().find(|(k, _)| *k == key);
+
+            match (value, args.positional.get(1)) {
+                (Some((_, v)), _) => Ok(v),
+                (None, Some(fallback)) => Ok(fallback.clone()),
+                (None, None) => Err(Error::InvalidArguments(format!(
+                    "Key '{}' is not in the dictionary",
+                    key
+                ))),
+            }
+        },
+    },
+    BuiltinMethod {
+        object: "dict",
+        signature: Signature::new("keys"),
+        call: |_, d, _| {
+            let mut keys = d
+                .into_dict()
+                .unwrap()
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>();
+            keys.sort();
+            Ok(Value::Array(keys.into_iter().map(Value::String).collect()))
+        },
+    },
+    BuiltinMethod {
+        object: "int",
+        signature: Signature::new("to_string"),
+        call: |_, n, _| Ok(Value::String(n.as_number().unwrap().to_string())),
+    },
+    BuiltinMethod {
+        object: "bool",
+        signature: Signature::new("to_string").positional(&[
+            Positional::new("true_str", STRING).optional(),
+            Positional::new("false_str", STRING).optional(),
+        ]),
+        call: |_, b, args| {
+            let (t, f) = (
+                args.string(0).unwrap_or("true".to_string()),
+                args.string(1).unwrap_or("false".to_string()),
+            );
+            Ok(Value::String(if *b.as_boolean().unwrap() { t } else { f }))
+        },
+    },
+    BuiltinMethod {
+        object: "file",
+        signature: Signature::new("full_path").since("1.4.0"),
+        call: |i, f, _| {
+            let project = &i.builder.project;
+            let path = f
+                .as_file()
+                .unwrap()
+                .absolute_path(&project.source_dir, &project.build_dir);
+            Ok(Value::String(path.to_string_lossy().to_string()))
+        },
+    },
+    BuiltinMethod {
+        object: "feature",
+        signature: Signature::new("enabled"),
+        call: |_, f, _| Ok(Value::Boolean(f == Value::Feature(Feature::Enabled))),
+    },
+    BuiltinMethod {
+        object: "feature",
+        signature: Signature::new("disabled"),
+        call: |_, f, _| Ok(Value::Boolean(f == Value::Feature(Feature::Disabled))),
+    },
+    BuiltinMethod {
+        object: "feature",
+        signature: Signature::new("auto"),
+        call: |_, f, _| Ok(Value::Boolean(f == Value::Feature(Feature::Auto))),
+    },
+    BuiltinMethod {
+        object: "feature",
+        signature: Signature::new("allowed").since("0.59.0"),
+        call: |_, f, _| Ok(Value::Boolean(f != Value::Feature(Feature::Disabled))),
+    },
+    BuiltinMethod {
+        object: "both_libs",
+        signature: Signature::new("get_shared_lib").since("0.46.0"),
+        call: |_, t, _| Ok(Value::BuildTarget(t.into_both_libraries().unwrap().0)),
+    },
+    BuiltinMethod {
+        object: "both_libs",
+        signature: Signature::new("get_static_lib").since("0.46.0"),
+        call: |_, t, _| Ok(Value::BuildTarget(t.into_both_libraries().unwrap().1)),
+    },
+    BuiltinMethod {
+        object: "build_tgt",
+        signature: Signature::new("name"),
+        call: |i, t, _| {
+            let target = &i.builder.build_targets[*t.as_build_target().unwrap()];
+            Ok(Value::String(target.name.clone()))
+        },
+    },
+    BuiltinMethod {
+        object: "build_tgt",
+        signature: Signature::new("full_path"),
+        call: |i, t, _| {
+            let target = &i.builder.build_targets[*t.as_build_target().unwrap()];
+            let path = i.builder.project.build_dir.join(target.output());
+            Ok(Value::String(path.to_string_lossy().to_string()))
+        },
+    },
+    BuiltinMethod {
+        object: "custom_tgt",
+        signature: Signature::new("full_path"),
+        call: |i, t, _| {
+            let target = i.custom_target_object(&t);
+            let [output] = target.output_files().try_into().map_err(|_| {
+                Error::InvalidArguments(format!(
+                    "custom_target {} has more than one output, use to_list() to get them",
+                    target.name
+                ))
+            })?;
+            let project = &i.builder.project;
+            let path = output.absolute_path(&project.source_dir, &project.build_dir);
+            Ok(Value::String(path.to_string_lossy().to_string()))
+        },
+    },
+    BuiltinMethod {
+        object: "custom_tgt",
+        signature: Signature::new("to_list").since("0.54.0"),
+        call: |i, t, _| {
+            let outputs = i.custom_target_object(&t).output_files();
+            Ok(Value::Array(outputs.into_iter().map(Value::File).collect()))
+        },
+    },
+    BuiltinMethod {
+        object: "generator",
+        signature: Signature::new("process")
+            .varargs(Positional::new("files", INPUT_LIST))
+            .kwargs(&[
+                Kwarg::new("extra_args", STRINGS),
+                Kwarg::new("preserve_path_from", STRING).since("0.45.0"),
+                Kwarg::new("env", ANY).since("1.3.0"),
+            ]),
+        call: Interpreter::generator_process,
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("set")
+            .positional(&[
+                Positional::new("varname", STRING),
+                Positional::new(
+                    "value",
+                    Type::OneOf(&[Type::String, Type::Number, Type::Boolean]),
+                ),
+            ])
+            .kwargs(&[Kwarg::new("description", STRING)]),
+        call: |i, c, args| i.configuration_set(c, args, SetKind::Value),
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("set10")
+            .positional(&[
+                Positional::new("varname", STRING),
+                Positional::new("value", Type::OneOf(&[Type::Boolean, Type::Number])),
+            ])
+            .kwargs(&[Kwarg::new("description", STRING)]),
+        call: |i, c, args| i.configuration_set(c, args, SetKind::Set10),
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("set_quoted")
+            .positional(&[
+                Positional::new("varname", STRING),
+                Positional::new("value", STRING),
+            ])
+            .kwargs(&[Kwarg::new("description", STRING)]),
+        call: |i, c, args| i.configuration_set(c, args, SetKind::Quoted),
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("get").positional(&[
+            Positional::new("varname", STRING),
+            Positional::new("default_value", ANY).optional(),
+        ]),
+        call: |i, c, args| i.configuration_get(c, args, false),
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("get_unquoted")
+            .positional(&[
+                Positional::new("varname", STRING),
+                Positional::new("default_value", ANY).optional(),
+            ])
+            .since("0.44.0"),
+        call: |i, c, args| i.configuration_get(c, args, true),
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("has").positional(&[Positional::new("varname", STRING)]),
+        call: |i, c, args| {
+            let data = i.configuration_data_object(&c);
+            Ok(Value::Boolean(data.get(&args.string(0).unwrap()).is_some()))
+        },
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("keys").since("0.57.0"),
+        call: |i, c, _| {
+            let data = i.configuration_data_object(&c);
+            let mut keys = data
+                .entries
+                .iter()
+                .map(|e| e.name.clone())
+                .collect::<Vec<_>>();
+            keys.sort();
+            Ok(Value::Array(keys.into_iter().map(Value::String).collect()))
+        },
+    },
+    BuiltinMethod {
+        object: "cfg_data",
+        signature: Signature::new("merge_from")
+            .positional(&[Positional::new("other", Type::ConfigurationData)])
+            .since("0.42.0"),
+        call: Interpreter::configuration_merge_from,
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("found"),
+        call: |i, d, _| Ok(Value::Boolean(i.dependency_object(&d).found())),
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("version"),
+        call: |i, d, _| {
+            let version = i.dependency_object(&d).version.clone();
+            Ok(Value::String(version.unwrap_or("unknown".to_string())))
+        },
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("name"),
+        call: |i, d, _| Ok(Value::String(i.dependency_object(&d).name.clone())),
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("type_name"),
+        call: |i, d, _| Ok(Value::String(i.dependency_object(&d).kind.to_string())),
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("partial_dependency")
+            .kwargs(&[
+                Kwarg::new("compile_args", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("link_args", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("links", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("includes", Type::Boolean).default(Literal::Boolean(false)),
+                Kwarg::new("sources", Type::Boolean).default(Literal::Boolean(false)),
+            ])
+            .since("0.46.0"),
+        call: Interpreter::partial_dependency,
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("as_link_whole").since("0.56.0"),
+        call: Interpreter::as_link_whole,
+    },
+    BuiltinMethod {
+        object: "dep",
+        signature: Signature::new("get_variable")
+            .positional(&[Positional::new("varname", STRING).optional()])
+            .kwargs(&[
+                Kwarg::new("pkgconfig", STRING),
+                Kwarg::new("cmake", STRING),
+                Kwarg::new("configtool", STRING),
+                Kwarg::new("internal", STRING).since("0.54.0"),
+                Kwarg::new("default_value", STRING),
+                Kwarg::new("pkgconfig_define", STRINGS),
+            ])
+            .since("0.51.0"),
+        call: Interpreter::dependency_variable,
+    },
+    BuiltinMethod {
+        object: "meson",
+        signature: Signature::new("get_compiler")
+            .positional(&[Positional::new("language", STRING)])
+            .kwargs(&[Kwarg::new("native", Type::Boolean).default(Literal::Boolean(false))]),
+        call: |i, _, args| i.get_compiler(args),
+    },
+    BuiltinMethod {
+        object: "meson",
+        signature: Signature::new("override_find_program")
+            .positional(&[
+                Positional::new("progname", STRING),
+                Positional::new("program", Type::OneOf(&[Type::Program, Type::BuildTarget])),
+            ])
+            .since("0.46.0"),
+        call: |i, _, args| i.override_find_program(args),
+    },
+    BuiltinMethod {
+        object: "external_program",
+        signature: Signature::new("found"),
+        call: |i, p, _| Ok(Value::Boolean(i.program(&p).found())),
+    },
+    BuiltinMethod {
+        object: "external_program",
+        signature: Signature::new("full_path").since("0.55.0"),
+        call: |i, p, _| {
+            let program = i.program(&p);
+            if !program.found() {
+                return Err(Error::Program(format!(
+                    "Program '{}' was not found, so it has no path",
+                    program.name
+                )));
+            }
+            Ok(Value::String(program.path.to_string_lossy().to_string()))
+        },
+    },
+    BuiltinMethod {
+        object: "external_program",
+        signature: Signature::new("version").since("0.62.0"),
+        call: |i, p, _| Ok(Value::String(i.program_version(&p, "--version")?)),
+    },
+    BuiltinMethod {
+        object: "external_program",
+        signature: Signature::new("name").since("1.5.0"),
+        call: |i, p, _| Ok(Value::String(i.program(&p).name.clone())),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("get_id"),
+        call: |i, c, _| Ok(Value::String(i.compiler(&c).id.to_string())),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("version"),
+        call: |i, c, _| Ok(Value::String(i.compiler(&c).version.clone())),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("get_linker_id").since("0.53.0"),
+        call: |i, c, _| Ok(Value::String(i.compiler(&c).linker.clone())),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("cmd_array"),
+        call: |i, c, _| {
+            let compiler = i.compiler(&c);
+            let path = compiler.path.to_string_lossy().to_string();
+            Ok(Value::Array(
+                std::iter::once(path)
+                    .chain(compiler.args.iter().cloned())
+                    .map(Value::String)
+                    .collect(),
+            ))
+        },
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("has_argument")
+            .positional(&[Positional::new("argument", STRING)]),
+        call: |i, c, args| {
+            Ok(Value::Boolean(i.has_argument(
+                &c,
+                &args.string(0).unwrap(),
+                false,
+            )?))
+        },
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("has_link_argument")
+            .positional(&[Positional::new("argument", STRING)])
+            .since("0.46.0"),
+        call: |i, c, args| {
+            Ok(Value::Boolean(i.has_argument(
+                &c,
+                &args.string(0).unwrap(),
+                true,
+            )?))
+        },
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("get_supported_arguments")
+            .varargs(Positional::new("arguments", STRINGS))
+            .kwargs(&[Kwarg::new("checked", STRING)
+                .default(Literal::String("off"))
+                .since("0.59.0")]),
+        call: |i, c, args| i.supported_arguments(c, args, false),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("get_supported_link_arguments")
+            .varargs(Positional::new("arguments", STRINGS))
+            .since("0.46.0"),
+        call: |i, c, args| i.supported_arguments(c, args, true),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("first_supported_argument")
+            .varargs(Positional::new("arguments", STRINGS)),
+        call: |i, c, args| {
+            for arg in Value::Array(args.varargs).flatten() {
+                if i.has_argument(&c, arg.as_string().unwrap(), false)? {
+                    return Ok(Value::Array(vec![arg]));
+                }
+            }
+            Ok(Value::Array(vec![]))
+        },
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("has_header")
+            .positional(&[Positional::new("header", STRING)])
+            .kwargs(check_kwargs![Kwarg::new(
+                "required",
+                Type::OneOf(&[Type::Boolean, Type::Feature])
+            )
+            .default(Literal::Boolean(false))
+            .since("0.50.0")]),
+        call: Interpreter::has_header,
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("has_function")
+            .positional(&[Positional::new("function", STRING)])
+            .kwargs(check_kwargs![]),
+        call: Interpreter::has_function,
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("has_member")
+            .positional(&[
+                Positional::new("typename", STRING),
+                Positional::new("membername", STRING),
+            ])
+            .kwargs(check_kwargs![]),
+        call: Interpreter::has_member,
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("sizeof")
+            .positional(&[Positional::new("typename", STRING)])
+            .kwargs(check_kwargs![]),
+        call: Interpreter::sizeof,
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("alignment")
+            .positional(&[Positional::new("typename", STRING)])
+            .kwargs(check_kwargs![]),
+        call: Interpreter::alignment,
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("compiles")
+            .positional(&[Positional::new("code", SOURCE)])
+            .kwargs(CODE_KWARGS),
+        call: |i, c, args| i.builds(c, args, false),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("links")
+            .positional(&[Positional::new("code", SOURCE)])
+            .kwargs(CODE_KWARGS),
+        call: |i, c, args| i.builds(c, args, true),
+    },
+    BuiltinMethod {
+        object: "compiler",
+        signature: Signature::new("run")
+            .positional(&[Positional::new("code", SOURCE)])
+            .kwargs(CODE_KWARGS),
+        call: Interpreter::run_check,
+    },
+    BuiltinMethod {
+        object: "runresult",
+        signature: Signature::new("compiled"),
+        call: |_, r, _| Ok(Value::Boolean(r.as_run_result().unwrap().compiled)),
+    },
+    BuiltinMethod {
+        object: "runresult",
+        signature: Signature::new("returncode"),
+        call: |_, r, _| Ok(Value::Number(r.as_run_result().unwrap().returncode)),
+    },
+    BuiltinMethod {
+        object: "runresult",
+        signature: Signature::new("stdout"),
+        call: |_, r, _| Ok(Value::String(r.as_run_result().unwrap().stdout.clone())),
+    },
+    BuiltinMethod {
+        object: "runresult",
+        signature: Signature::new("stderr"),
+        call: |_, r, _| Ok(Value::String(r.as_run_result().unwrap().stderr.clone())),
+    },
+];
@@ -0,0 +1,357 @@
+use std::path::PathBuf;
+
+use super::{required, signature::Args, value::Value, Error, Interpreter};
+use crate::{
+    build_dir::PRIVATE_DIR,
+    compiler::{checks, CheckOutput, Compiler, Language, Mode},
+    IncludeDirectories,
+};
+
+fn yes_no(found: bool) -> &'static str {
+    if found {
+        "YES"
+    } else {
+        "NO"
+    }
+}
+
+/// Log the result of a check, `message` ends with the colon
+fn report(message: &str, result: impl std::fmt::Display, cached: bool) {
+    let cached = if cached { " (cached)" } else { "" };
+    crate::info!("{} {}{}", message, result, cached);
+}
+
+/// The `prefix` keyword argument as one piece of code
+fn prefix(args: &Args) -> String {
+    args.kwarg_list("prefix")
+        .into_iter()
+        .map(|p| p.into_string().unwrap())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Interpreter {
+    /// Directory compiler checks write their test programs to
+    pub(super) fn scratch_dir(&self) -> PathBuf {
+        self.builder
+            .project
+            .build_dir
+            .join(PRIVATE_DIR)
+            .join("checks")
+    }
+
+    pub(super) fn compiler(&self, value: &Value) -> &Compiler {
+        &self.builder.compilers[*value.as_compiler().unwrap()]
+    }
+
+    pub(super) fn get_compiler(&mut self, args: Args) -> Result<Value, Error> {
+        let language = args
+            .string(0)
+            .unwrap()
+            .parse::<Language>()
+            .map_err(Error::Compiler)?;
+
+        self.builder
+            .compilers
+            .iter()
+            .position(|c| c.language == language)
+            .map(Value::Compiler)
+            .ok_or_else(|| {
+                Error::Compiler(format!(
+                    "Tried to access compiler for language \"{}\", not specified for host machine",
+                    language
+                ))
+            })
+    }
+
+    /// Run a check, or reuse the result of an identical one from an earlier
+    /// configuration, `result` turns the output into the value kept. Also
+    /// tells whether the result came from the cache.
+    fn cached_check(
+        &mut self,
+        compiler: &Value,
+        code: &str,
+        args: &[String],
+        mode: Mode,
+        result: impl FnOnce(CheckOutput) -> String,
+    ) -> Result<(String, bool), Error> {
+        let key = format!(
+            "{}\n{:?}\n{}\n{}",
+            self.compiler(compiler).identity(),
+            mode,
+            args.join(" "),
+            code
+        );
+        if let Some(result) = self.check_cache.get(&key) {
+            return Ok((result.to_string(), true));
+        }
+
+        let output = self
+            .compiler(compiler)
+            .check(&self.scratch_dir(), code, args, mode)
+            .map_err(Error::Compiler)?;
+        let result = result(output);
+        self.check_cache
+            .insert(&key, result.clone())
+            .map_err(|e| Error::Compiler(format!("Failed to cache a compiler check: {}", e)))?;
+
+        Ok((result, false))
+    }
+
+    /// Whether `code` gets through `mode`, and whether that was cached
+    fn passes(
+        &mut self,
+        compiler: &Value,
+        code: &str,
+        args: &[String],
+        mode: Mode,
+    ) -> Result<(bool, bool), Error> {
+        let (result, cached) = self.cached_check(compiler, code, args, mode, |output| {
+            output.compiled.to_string()
+        })?;
+        Ok((result == "true", cached))
+    }
+
+    /// Arguments a check is built with, the language standard unless
+    /// `no_builtin_args` is set, then those of the `include_directories`,
+    /// the `dependencies` and the `args` keyword argument
+    fn check_args(&self, compiler: &Value, args: &Args, mode: Mode) -> Result<Vec<String>, Error> {
+        let compiler = self.compiler(compiler);
+
+        let mut check_args = vec![];
+        if !args.kwarg_bool("no_builtin_args").unwrap_or(false) {
+            let std = format!("{}_std", compiler.language);
+            if let Some(std) = self.builder.options.value(&std) {
+                check_args.extend(compiler.args().std_args(&std.to_string()));
+            }
+        }
+        if args.kwarg_bool("werror").unwrap_or(false) {
+            check_args.extend(compiler.args().werror_args());
+        }
+        let project = &self.builder.project;
+        let include_args = |include_directories: &[IncludeDirectories]| {
+            compiler.args().include_directories_args(
+                include_directories,
+                &project.source_dir,
+                &project.build_dir,
+            )
+        };
+        check_args.extend(include_args(&self.include_directories_kwarg(args)?));
+        for dependency in self.dependencies(args) {
+            let dependency = &self.builder.dependencies[dependency];
+            check_args.extend(include_args(&dependency.include_directories));
+            check_args.extend(dependency.compile_args.iter().cloned());
+            if matches!(mode, Mode::Link | Mode::Run) {
+                check_args.extend(dependency.link_args.iter().cloned());
+            }
+        }
+        check_args.extend(
+            args.kwarg_list("args")
+                .into_iter()
+                .map(|a| a.into_string().unwrap()),
+        );
+
+        Ok(check_args)
+    }
+
+    /// Whether the compiler accepts `arg`, when linking if `link` is set
+    pub(super) fn has_argument(
+        &mut self,
+        compiler: &Value,
+        arg: &str,
+        link: bool,
+    ) -> Result<bool, Error> {
+        let (code, args) = self.compiler(compiler).argument_check(arg);
+        let mode = if link { Mode::Link } else { Mode::Compile };
+        Ok(self.passes(compiler, code, &args, mode)?.0)
+    }
+
+    /// The arguments among `args.varargs` the compiler accepts
+    pub(super) fn supported_arguments(
+        &mut self,
+        compiler: Value,
+        args: Args,
+        link: bool,
+    ) -> Result<Value, Error> {
+        let checked = args.kwarg_string("checked").unwrap_or("off".to_string());
+        if !["off", "warn", "require"].contains(&checked.as_str()) {
+            return Err(Error::InvalidArguments(format!(
+                "checked must be one of off, warn, require, not '{}'",
+                checked
+            )));
+        }
+
+        let mut supported = vec![];
+        for arg in Value::Array(args.varargs).flatten() {
+            let arg = arg.into_string().unwrap();
+            if self.has_argument(&compiler, &arg, link)? {
+                supported.push(Value::String(arg));
+                continue;
+            }
+
+            let message = format!(
+                "Compiler for {} does not support \"{}\"",
+                self.compiler(&compiler).language,
+                arg
+            );
+            match checked.as_str() {
+                "require" => return Err(Error::Compiler(message)),
+                "warn" => crate::warning!("{}", message),
+                _ => {}
+            }
+        }
+
+        Ok(Value::Array(supported))
+    }
+
+    pub(super) fn has_header(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let header = args.string(0).unwrap();
+        let Some(required) = required(&args) else {
+            crate::info!("Has header \"{}\" skipped: feature disabled", header);
+            return Ok(Value::Boolean(false));
+        };
+
+        let code = checks::header_code(&prefix(&args), &header);
+        let check_args = self.check_args(&compiler, &args, Mode::Preprocess)?;
+        let (found, cached) = self.passes(&compiler, &code, &check_args, Mode::Preprocess)?;
+        report(
+            &format!("Has header \"{}\" :", header),
+            yes_no(found),
+            cached,
+        );
+
+        if required && !found {
+            return Err(Error::Compiler(format!(
+                "{} header '{}' not found",
+                self.compiler(&compiler).language.display_name(),
+                header
+            )));
+        }
+        Ok(Value::Boolean(found))
+    }
+
+    pub(super) fn has_function(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let function = args.string(0).unwrap();
+        let code = checks::function_code(&prefix(&args), &function);
+        let check_args = self.check_args(&compiler, &args, Mode::Link)?;
+
+        let (found, cached) = self.passes(&compiler, &code, &check_args, Mode::Link)?;
+        let message = format!("Checking for function \"{}\" :", function);
+        report(&message, yes_no(found), cached);
+        Ok(Value::Boolean(found))
+    }
+
+    pub(super) fn has_member(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let (ty, member) = (args.string(0).unwrap(), args.string(1).unwrap());
+        let code = checks::member_code(&prefix(&args), &ty, &member);
+        let check_args = self.check_args(&compiler, &args, Mode::Compile)?;
+
+        let (found, cached) = self.passes(&compiler, &code, &check_args, Mode::Compile)?;
+        let message = format!(
+            "Checking whether type \"{}\" has member \"{}\" :",
+            ty, member
+        );
+        report(&message, yes_no(found), cached);
+        Ok(Value::Boolean(found))
+    }
+
+    /// Run a program printing a number about a type, -1 when it doesn't
+    /// build, and whether that was cached
+    fn type_property(
+        &mut self,
+        compiler: &Value,
+        args: &Args,
+        code: String,
+    ) -> Result<(i64, bool), Error> {
+        let check_args = self.check_args(compiler, args, Mode::Run)?;
+        let number = |output: CheckOutput| match output.stdout.trim().parse::<i64>() {
+            Ok(n) if output.compiled && output.returncode == 0 => n.to_string(),
+            _ => "-1".to_string(),
+        };
+        let (result, cached) =
+            self.cached_check(compiler, &code, &check_args, Mode::Run, number)?;
+
+        Ok((result.parse().unwrap_or(-1), cached))
+    }
+
+    pub(super) fn sizeof(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let ty = args.string(0).unwrap();
+        let code = checks::sizeof_code(&prefix(&args), &ty);
+
+        let (size, cached) = self.type_property(&compiler, &args, code)?;
+        report(&format!("Checking for size of \"{}\" :", ty), size, cached);
+        Ok(Value::Number(size))
+    }
+
+    pub(super) fn alignment(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let ty = args.string(0).unwrap();
+        let code = checks::alignment_code(&prefix(&args), &ty);
+
+        let (alignment, cached) = self.type_property(&compiler, &args, code)?;
+        if alignment < 0 {
+            return Err(Error::Compiler(format!(
+                "Could not determine alignment of \"{}\"",
+                ty
+            )));
+        }
+        report(
+            &format!("Checking for alignment of \"{}\" :", ty),
+            alignment,
+            cached,
+        );
+        Ok(Value::Number(alignment))
+    }
+
+    /// The code argument of `compiles()`, `links()` and `run()`
+    fn check_code(&self, args: &Args) -> Result<String, Error> {
+        match &args.positional[0] {
+            Value::File(file) => {
+                let project = &self.builder.project;
+                let path = file.absolute_path(&project.source_dir, &project.build_dir);
+                std::fs::read_to_string(&path).map_err(|_| Error::FileNotFound(path))
+            }
+            code => Ok(code.as_string().unwrap().clone()),
+        }
+    }
+
+    /// `compiles()` or `links()`
+    pub(super) fn builds(
+        &mut self,
+        compiler: Value,
+        args: Args,
+        link: bool,
+    ) -> Result<Value, Error> {
+        let code = self.check_code(&args)?;
+        let mode = if link { Mode::Link } else { Mode::Compile };
+        let check_args = self.check_args(&compiler, &args, mode)?;
+
+        let (result, cached) = self.passes(&compiler, &code, &check_args, mode)?;
+        if let Some(name) = args.kwarg_string("name") {
+            let what = if link { "links" } else { "compiles" };
+            let message = format!("Checking if \"{}\" {}:", name, what);
+            report(&message, yes_no(result), cached);
+        }
+        Ok(Value::Boolean(result))
+    }
+
+    /// `run()`, never cached since the program may depend on more than its
+    /// code
+    pub(super) fn run_check(&mut self, compiler: Value, args: Args) -> Result<Value, Error> {
+        let code = self.check_code(&args)?;
+        let check_args = self.check_args(&compiler, &args, Mode::Run)?;
+
+        let output = self
+            .compiler(&compiler)
+            .check(&self.scratch_dir(), &code, &check_args, Mode::Run)
+            .map_err(Error::Compiler)?;
+        if let Some(name) = args.kwarg_string("name") {
+            let runs = output.compiled && output.returncode == 0;
+            report(
+                &format!("Checking if \"{}\" runs:", name),
+                yes_no(runs),
+                false,
+            );
+        }
+        Ok(Value::RunResult(output))
+    }
+}
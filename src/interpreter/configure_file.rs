@@ -0,0 +1,321 @@
+use std::{fs, path::Path, process::Command};
+
+use super::{
+    custom_target::{output_name, path_string, Templates},
+    signature::Args,
+    value::Value,
+    Error, Interpreter,
+};
+use crate::{
+    backend::depfile_prerequisites,
+    configuration::{write_if_changed, ConfigValue, ConfigurationData, Format, HeaderFormat},
+    log, File,
+};
+
+fn config_value(value: &Value) -> Result<ConfigValue, Error> {
+    match value {
+        Value::Boolean(b) => Ok(ConfigValue::Boolean(*b)),
+        Value::Number(n) => Ok(ConfigValue::Number(*n)),
+        Value::String(s) => Ok(ConfigValue::String(s.clone())),
+        v => Err(Error::InvalidArguments(format!(
+            "Configuration values must be strings, integers or booleans, not {}",
+            v.type_name()
+        ))),
+    }
+}
+
+fn from_config_value(value: &ConfigValue) -> Value {
+    match value {
+        ConfigValue::Boolean(b) => Value::Boolean(*b),
+        ConfigValue::Number(n) => Value::Number(*n),
+        ConfigValue::String(s) => Value::String(s.clone()),
+    }
+}
+
+fn from_dict(dict: &[(String, Value)]) -> Result<ConfigurationData, Error> {
+    let mut data = ConfigurationData::default();
+    for (name, value) in dict {
+        data.set(name, config_value(value)?, None);
+    }
+    Ok(data)
+}
+
+/// How `set`, `set10` and `set_quoted` store their value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SetKind {
+    Value,
+    Set10,
+    Quoted,
+}
+
+impl Interpreter {
+    pub(super) fn configuration_data_object(&self, value: &Value) -> &ConfigurationData {
+        &self.builder.configuration_data[*value.as_configuration_data().unwrap()]
+    }
+
+    pub(super) fn configuration_data(&mut self, args: Args) -> Result<Value, Error> {
+        let data = match args.positional.first() {
+            Some(Value::Dict(dict)) => from_dict(dict)?,
+            _ => ConfigurationData::default(),
+        };
+        self.builder.configuration_data.push(data);
+        Ok(Value::ConfigurationData(
+            self.builder.configuration_data.len() - 1,
+        ))
+    }
+
+    pub(super) fn configuration_set(
+        &mut self,
+        data: Value,
+        args: Args,
+        kind: SetKind,
+    ) -> Result<Value, Error> {
+        let name = args.string(0).unwrap();
+        let value = match (kind, &args.positional[1]) {
+            (SetKind::Set10, Value::Boolean(b)) => ConfigValue::Number(*b as i64),
+            (SetKind::Set10, Value::Number(n)) => ConfigValue::Number((*n != 0) as i64),
+            (SetKind::Set10, value) => {
+                return Err(Error::InvalidArguments(format!(
+                    "set10() requires a bool or int value, got {}",
+                    value.type_name()
+                )))
+            }
+            (SetKind::Quoted, Value::String(s)) => {
+                ConfigValue::String(format!("\"{}\"", s.replace('"', "\\\"")))
+            }
+            (_, value) => config_value(value)?,
+        };
+
+        let data = &mut self.builder.configuration_data[*data.as_configuration_data().unwrap()];
+        if data.used {
+            return Err(Error::ConfigureFile(
+                "Can not set values on configuration object that has been used".to_string(),
+            ));
+        }
+        data.set(&name, value, args.kwarg_string("description"));
+        Ok(Value::None)
+    }
+
+    /// `get()` and `get_unquoted()`, which strips surrounding double quotes
+    pub(super) fn configuration_get(
+        &mut self,
+        data: Value,
+        args: Args,
+        unquote: bool,
+    ) -> Result<Value, Error> {
+        let name = args.string(0).unwrap();
+        let value = match self.configuration_data_object(&data).get(&name) {
+            Some(entry) => from_config_value(&entry.value),
+            None => args.positional.get(1).cloned().ok_or_else(|| {
+                Error::ConfigureFile(format!("Entry {} not in configuration data", name))
+            })?,
+        };
+
+        Ok(match value {
+            Value::String(s) if unquote => {
+                let unquoted = s.strip_prefix('"').and_then(|s| s.strip_suffix('"'));
+                Value::String(unquoted.map(str::to_string).unwrap_or(s))
+            }
+            value => value,
+        })
+    }
+
+    pub(super) fn configuration_merge_from(
+        &mut self,
+        data: Value,
+        args: Args,
+    ) -> Result<Value, Error> {
+        let other = self.configuration_data_object(&args.positional[0]).clone();
+        let data = &mut self.builder.configuration_data[*data.as_configuration_data().unwrap()];
+        if data.used {
+            return Err(Error::ConfigureFile(
+                "Can not set values on configuration object that has been used".to_string(),
+            ));
+        }
+        data.merge_from(&other);
+        Ok(Value::None)
+    }
+
+    pub(super) fn configure_file(&mut self, args: Args) -> Result<Value, Error> {
+        let project = &self.builder.project;
+
+        let mut inputs = vec![];
+        for input in args.kwarg_list("input") {
+            inputs.push(self.source_file(&input)?);
+        }
+        let input_paths = inputs
+            .iter()
+            .map(|f| path_string(f.absolute_path(&project.source_dir, &project.build_dir)))
+            .collect::<Vec<_>>();
+
+        let output = args.kwarg_string("output").ok_or_else(|| {
+            Error::InvalidArguments("configure_file() requires an output".to_string())
+        })?;
+        let output = output_name(&output, &input_paths)?;
+        let out_dir = project.build_dir.join(&self.subdir);
+        let out_path = out_dir.join(&output);
+
+        let copy = args.kwarg_bool("copy").unwrap_or(false);
+        let configuration = args.kwarg("configuration").cloned();
+        let command = args.kwarg_list("command");
+        let actions = [
+            configuration.is_some(),
+            copy,
+            args.kwarg("command").is_some(),
+        ];
+        match actions.iter().filter(|a| **a).count() {
+            0 => return Err(Error::InvalidArguments(
+                "Must specify an action with one of these keyword arguments: 'configuration', 'command', or 'copy'".to_string(),
+            )),
+            1 => {}
+            _ => return Err(Error::InvalidArguments(
+                "Must not specify more than one of 'configuration', 'command' and 'copy'".to_string(),
+            )),
+        }
+        if inputs.len() > 1 && args.kwarg("command").is_none() {
+            return Err(Error::InvalidArguments(
+                "At most one input file can be given unless using 'command'".to_string(),
+            ));
+        }
+
+        // Changes to the inputs have to regenerate the outputs
+        for input in &inputs {
+            if !input.is_built {
+                self.builder.build_files.push(input.relative_path());
+            }
+        }
+
+        let read = |path: &str| {
+            fs::read(path)
+                .map_err(|e| Error::ConfigureFile(format!("Could not read {}: {}", path, e)))
+        };
+        let write = |contents: &[u8]| {
+            write_if_changed(&out_path, contents).map_err(|e| {
+                Error::ConfigureFile(format!("Could not write {}: {}", out_path.display(), e))
+            })
+        };
+
+        if let Some(configuration) = configuration {
+            let data = match &configuration {
+                Value::Dict(dict) => from_dict(dict)?,
+                data => {
+                    let index = *data.as_configuration_data().unwrap();
+                    self.builder.configuration_data[index].used = true;
+                    self.builder.configuration_data[index].clone()
+                }
+            };
+
+            let contents = match input_paths.first() {
+                Some(input) => {
+                    let format = args
+                        .kwarg_string("format")
+                        .unwrap_or("meson".to_string())
+                        .parse::<Format>()
+                        .map_err(Error::InvalidArguments)?;
+                    let template = String::from_utf8_lossy(&read(input)?).to_string();
+                    let (contents, missing) = data
+                        .substitute(&template, format)
+                        .map_err(|e| Error::ConfigureFile(format!("{}: {}", input, e)))?;
+                    if !missing.is_empty() {
+                        crate::warning!(
+                            "The variable(s) '{}' in the input file '{}' are not present in the given configuration data",
+                            missing.join("', '"),
+                            inputs[0].relative_path().display()
+                        );
+                    }
+                    contents
+                }
+                None => {
+                    let format = args
+                        .kwarg_string("output_format")
+                        .unwrap_or("c".to_string())
+                        .parse::<HeaderFormat>()
+                        .map_err(Error::InvalidArguments)?;
+                    data.header(format, args.kwarg_string("macro_name").as_deref())
+                }
+            };
+            write(contents.as_bytes())?;
+        } else if copy {
+            let input = input_paths.first().ok_or_else(|| {
+                Error::InvalidArguments("copy requires an input file".to_string())
+            })?;
+            write(&read(input)?)?;
+        } else {
+            self.configure_command(&args, command, input_paths, &out_dir, &output)?;
+        }
+
+        Ok(Value::File(File::built(&self.subdir, Path::new(&output))))
+    }
+
+    /// Run the command of `configure_file()` now, rather than when building
+    fn configure_command(
+        &mut self,
+        args: &Args,
+        command: Vec<Value>,
+        inputs: Vec<String>,
+        out_dir: &Path,
+        output: &str,
+    ) -> Result<(), Error> {
+        let project = &self.builder.project;
+        let out_path = out_dir.join(output);
+        let depfile = args.kwarg_string("depfile");
+        let templates = Templates {
+            inputs,
+            outputs: vec![path_string(out_path.clone())],
+            outdir: path_string(out_dir.to_path_buf()),
+            depfile: depfile.as_ref().map(|d| path_string(out_dir.join(d))),
+            private_dir: path_string(out_dir.to_path_buf()),
+            current_source_dir: path_string(project.source_dir.join(&self.subdir)),
+            source_root: path_string(project.source_dir.clone()),
+            build_root: path_string(project.build_dir.clone()),
+        };
+
+        let mut arguments = vec![];
+        for (i, arg) in command.into_iter().enumerate() {
+            for arg in self.command_arg(arg, i == 0, &mut vec![])? {
+                arguments.extend(templates.substitute(&arg)?);
+            }
+        }
+        // A command of empty lists only turns out empty once flattened
+        let Some((program, arguments)) = arguments.split_first() else {
+            return Err(Error::InvalidArguments(
+                "configure_file() command must not be empty".to_string(),
+            ));
+        };
+
+        fs::create_dir_all(out_dir).map_err(|e| {
+            Error::ConfigureFile(format!("Could not create {}: {}", out_dir.display(), e))
+        })?;
+        let result = log::run(Command::new(program).args(arguments).current_dir(out_dir))
+            .map_err(|e| Error::ConfigureFile(format!("Failed to run {}: {}", program, e)))?;
+        if !result.status.success() {
+            return Err(Error::ConfigureFile(format!(
+                "Running configure command failed: {}\n{}",
+                result.status,
+                String::from_utf8_lossy(&result.stderr).trim_end()
+            )));
+        }
+
+        if args.kwarg_bool("capture").unwrap_or(false) {
+            write_if_changed(&out_path, &result.stdout).map_err(|e| {
+                Error::ConfigureFile(format!("Could not write {}: {}", out_path.display(), e))
+            })?;
+        }
+
+        // What the command read decides whether it has to run again
+        if let Some(depfile) = depfile {
+            let contents = fs::read_to_string(out_dir.join(&depfile)).map_err(|e| {
+                Error::ConfigureFile(format!("Could not read depfile {}: {}", depfile, e))
+            })?;
+            let source_dir = self.builder.project.source_dir.clone();
+            for file in depfile_prerequisites(&contents) {
+                // Relative to where the command ran
+                let file = out_dir.join(file);
+                let file = file.strip_prefix(&source_dir).unwrap_or(&file);
+                self.builder.build_files.push(file.to_path_buf());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use std::path::{Path, PathBuf};
+
+use super::{signature::Args, value::Value, Error, Interpreter};
+use crate::{CustomTarget, File, TargetId, TargetKind};
+
+/// What the templates in a custom target's or generator's command stand for
+pub(super) struct Templates {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub outdir: String,
+    pub depfile: Option<String>,
+    pub private_dir: String,
+    pub current_source_dir: String,
+    pub source_root: String,
+    pub build_root: String,
+}
+
+impl Templates {
+    /// Arguments `arg` stands for, `@INPUT@` and `@OUTPUT@` on their own
+    /// expand to every file
+    pub(super) fn substitute(&self, arg: &str) -> Result<Vec<String>, Error> {
+        match arg {
+            "@INPUT@" => return Ok(self.inputs.clone()),
+            "@OUTPUT@" => return Ok(self.outputs.clone()),
+            _ => {}
+        }
+
+        let mut substituted = String::new();
+        let mut rest = arg;
+        while let Some(start) = rest.find('@') {
+            let Some(end) = rest[start + 1..].find('@').map(|end| start + 1 + end) else {
+                break;
+            };
+            let name = &rest[start + 1..end];
+            match self.template(name)? {
+                Some(value) => {
+                    substituted += &rest[..start];
+                    substituted += &value;
+                    rest = &rest[end + 1..];
+                }
+                // Not a template, the closing `@` may open the next one
+                None => {
+                    substituted += &rest[..end];
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        Ok(vec![substituted + rest])
+    }
+
+    fn template(&self, name: &str) -> Result<Option<String>, Error> {
+        let single = |files: &[String], kind: &str| match files {
+            [file] => Ok(file.clone()),
+            _ => Err(Error::InvalidArguments(format!(
+                "Command has '@{}@' as part of a string and {} {} files",
+                kind,
+                files.len(),
+                kind.to_lowercase()
+            ))),
+        };
+        let indexed = |files: &[String], kind: &str, index: &str| {
+            let index = index.parse::<usize>().ok()?;
+            Some(files.get(index).cloned().ok_or_else(|| {
+                Error::InvalidArguments(format!(
+                    "Index {} out of bounds of {} array of size {}",
+                    index,
+                    kind.to_lowercase(),
+                    files.len()
+                ))
+            }))
+        };
+
+        let value = match name {
+            "INPUT" => single(&self.inputs, "INPUT")?,
+            "OUTPUT" => single(&self.outputs, "OUTPUT")?,
+            "PLAINNAME" | "BASENAME" => {
+                let input = single(&self.inputs, "INPUT")?;
+                name_template(name, &input).unwrap()
+            }
+            "OUTDIR" => self.outdir.clone(),
+            "DEPFILE" => self.depfile.clone().ok_or_else(|| {
+                Error::InvalidArguments(
+                    "Custom target uses @DEPFILE@ but no depfile keyword argument".to_string(),
+                )
+            })?,
+            "PRIVATE_DIR" => self.private_dir.clone(),
+            "CURRENT_SOURCE_DIR" => self.current_source_dir.clone(),
+            "SOURCE_ROOT" => self.source_root.clone(),
+            "BUILD_ROOT" => self.build_root.clone(),
+            name => {
+                let input = name
+                    .strip_prefix("INPUT")
+                    .and_then(|index| indexed(&self.inputs, "INPUT", index));
+                let output = name
+                    .strip_prefix("OUTPUT")
+                    .and_then(|index| indexed(&self.outputs, "OUTPUT", index));
+                match input.or(output) {
+                    Some(value) => value?,
+                    None => return Ok(None),
+                }
+            }
+        };
+
+        Ok(Some(value))
+    }
+}
+
+/// `@PLAINNAME@` is the input's file name, `@BASENAME@` the same without
+/// its extension
+fn name_template(name: &str, input: &str) -> Option<String> {
+    let path = Path::new(input);
+    let value = match name {
+        "PLAINNAME" => path.file_name(),
+        "BASENAME" => path.file_stem(),
+        _ => return None,
+    };
+    Some(value.unwrap_or_default().to_string_lossy().to_string())
+}
+
+/// Substitute the templates allowed in output and depfile names
+pub(super) fn output_name(name: &str, inputs: &[String]) -> Result<String, Error> {
+    let mut name = name.to_string();
+    for template in ["PLAINNAME", "BASENAME"] {
+        let pattern = format!("@{}@", template);
+        if !name.contains(&pattern) {
+            continue;
+        }
+        let [input] = inputs else {
+            return Err(Error::InvalidArguments(format!(
+                "Output '{}' uses {} but the target has {} inputs",
+                name,
+                pattern,
+                inputs.len()
+            )));
+        };
+        name = name.replace(&pattern, &name_template(template, input).unwrap());
+    }
+
+    if name.contains('/') {
+        return Err(Error::InvalidArguments(format!(
+            "Output '{}' must be a file name, not a path",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+impl Interpreter {
+    pub(super) fn custom_target_object(&self, value: &Value) -> &CustomTarget {
+        &self.builder.custom_targets[*value.as_custom_target().unwrap()]
+    }
+
+    /// Files a source argument stands for, all outputs of a custom target
+    pub(super) fn source_files(&self, value: &Value) -> Result<Vec<File>, Error> {
+        match value {
+            Value::CustomTarget(_) => Ok(self.custom_target_object(value).output_files()),
+            value => Ok(vec![self.source_file(value)?]),
+        }
+    }
+
+    pub(super) fn custom_target(&mut self, args: Args) -> Result<Value, Error> {
+        let project = &self.builder.project;
+        let absolute = |file: &File| {
+            file.absolute_path(&project.source_dir, &project.build_dir)
+                .to_string_lossy()
+                .to_string()
+        };
+        let build_subdir = project.build_dir.join(&self.subdir);
+        let in_build_subdir = |name: &str| build_subdir.join(name).to_string_lossy().to_string();
+
+        let mut depends = vec![];
+        let mut inputs = vec![];
+        for input in args.kwarg_list("input") {
+            match input {
+                Value::BuildTarget(index) => {
+                    let target = &self.builder.build_targets[index];
+                    inputs.push(File::built(Path::new(""), &target.output()));
+                    depends.push(TargetId::Build(index));
+                }
+                Value::CustomTarget(index) => {
+                    inputs.extend(self.custom_target_object(&input).output_files());
+                    depends.push(TargetId::Custom(index));
+                }
+                input => inputs.push(self.source_file(&input)?),
+            }
+        }
+        let input_paths = inputs.iter().map(absolute).collect::<Vec<_>>();
+
+        let outputs = args
+            .kwarg_list("output")
+            .into_iter()
+            .map(|output| output_name(output.as_string().unwrap(), &input_paths))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(first_output) = outputs.first() else {
+            return Err(Error::InvalidArguments(
+                "custom_target() requires at least one output".to_string(),
+            ));
+        };
+        let name = args.string(0).unwrap_or(first_output.clone());
+
+        let depfile = args
+            .kwarg_string("depfile")
+            .map(|depfile| output_name(&depfile, &input_paths))
+            .transpose()?;
+
+        let capture = args.kwarg_bool("capture").unwrap_or(false);
+        if capture && outputs.len() != 1 {
+            return Err(Error::InvalidArguments(
+                "Capturing can only output to a single file".to_string(),
+            ));
+        }
+        let feed = args.kwarg_bool("feed").unwrap_or(false);
+        if feed && inputs.len() != 1 {
+            return Err(Error::InvalidArguments(
+                "Feeding can only input from a single file".to_string(),
+            ));
+        }
+
+        let install = args.kwarg_bool("install").unwrap_or(false);
+        let install_dir = args
+            .kwarg_list("install_dir")
+            .into_iter()
+            .map(|dir| dir.into_string().unwrap())
+            .collect::<Vec<_>>();
+        if install && install_dir.is_empty() {
+            return Err(Error::InvalidArguments(
+                "'install_dir' must be specified when installing a target".to_string(),
+            ));
+        }
+
+        let templates = Templates {
+            inputs: input_paths,
+            outputs: outputs.iter().map(|o| in_build_subdir(o)).collect(),
+            outdir: in_build_subdir(""),
+            depfile: depfile.as_deref().map(in_build_subdir),
+            private_dir: in_build_subdir(&format!("{}.p", name)),
+            current_source_dir: project
+                .source_dir
+                .join(&self.subdir)
+                .to_string_lossy()
+                .to_string(),
+            source_root: project.source_dir.to_string_lossy().to_string(),
+            build_root: project.build_dir.to_string_lossy().to_string(),
+        };
+
+        let mut command = vec![];
+        for (i, arg) in args.kwarg_list("command").into_iter().enumerate() {
+            for arg in self.command_arg(arg, i == 0, &mut depends)? {
+                command.extend(templates.substitute(&arg)?);
+            }
+        }
+        if command.is_empty() {
+            return Err(Error::InvalidArguments(
+                "custom_target() requires a command".to_string(),
+            ));
+        }
+
+        for depend in args.kwarg_list("depends") {
+            depends.push(match depend {
+                Value::BuildTarget(index) => TargetId::Build(index),
+                depend => TargetId::Custom(*depend.as_custom_target().unwrap()),
+            });
+        }
+        let mut depend_files = vec![];
+        for file in args.kwarg_list("depend_files") {
+            depend_files.push(self.source_file(&file)?);
+        }
+
+        self.builder.custom_targets.push(CustomTarget {
+            name,
+            subdir: self.subdir.clone(),
+            inputs,
+            outputs,
+            command,
+            capture,
+            feed,
+            depfile,
+            depend_files,
+            depends,
+            build_always_stale: args.kwarg_bool("build_always_stale").unwrap_or(false),
+            build_by_default: args.kwarg_bool("build_by_default").unwrap_or(install),
+            install,
+            install_dir,
+        });
+        Ok(Value::CustomTarget(self.builder.custom_targets.len() - 1))
+    }
+
+    /// Arguments an element of a command stands for, a string in first
+    /// place is the name of a program to find
+    pub(super) fn command_arg(
+        &mut self,
+        arg: Value,
+        first: bool,
+        depends: &mut Vec<TargetId>,
+    ) -> Result<Vec<String>, Error> {
+        let arg = match arg {
+            Value::String(name) if first => match self.program_override(&name) {
+                Some(program) => program,
+                None => {
+                    let (_, command) = self
+                        .search_program(&Value::String(name.clone()), &[])
+                        .ok_or_else(|| {
+                            Error::Program(format!(
+                                "Program '{}' not found or not executable",
+                                name
+                            ))
+                        })?;
+                    return Ok(command);
+                }
+            },
+            arg => arg,
+        };
+
+        let project = &self.builder.project;
+        Ok(match arg {
+            Value::String(arg) => vec![arg],
+            Value::File(file) => vec![path_string(
+                file.absolute_path(&project.source_dir, &project.build_dir),
+            )],
+            Value::Program(_) => {
+                let program = self.program(&arg);
+                if !program.found() {
+                    return Err(Error::Program(format!(
+                        "Program '{}' used in a command was not found",
+                        program.name
+                    )));
+                }
+                depends.extend(program.target.map(TargetId::Build));
+                program.command.clone()
+            }
+            Value::BuildTarget(index) => {
+                let target = &self.builder.build_targets[index];
+                if target.kind != TargetKind::Executable {
+                    return Err(Error::InvalidArguments(format!(
+                        "Only executables can be run in a command, {} is a library",
+                        target.name
+                    )));
+                }
+                depends.push(TargetId::Build(index));
+                vec![path_string(project.build_dir.join(target.output()))]
+            }
+            Value::CustomTarget(index) => {
+                depends.push(TargetId::Custom(index));
+                let target = &self.builder.custom_targets[index];
+                target
+                    .outputs
+                    .iter()
+                    .map(|output| path_string(project.build_dir.join(&target.subdir).join(output)))
+                    .collect()
+            }
+            arg => {
+                return Err(Error::Expected {
+                    expected: "String, File, Program or target".to_string(),
+                    got: arg,
+                })
+            }
+        })
+    }
+}
+
+pub(super) fn path_string(path: PathBuf) -> String {
+    path.to_string_lossy().to_string()
+}
@@ -0,0 +1,322 @@
+use super::{
+    expect_string, required, signature::Args, value::Value, CallKind, Error, Interpreter,
+    Unsupported,
+};
+use crate::{pkgconfig::PkgConfig, version, BuildTarget, Dependency, DependencyKind, TargetKind};
+
+impl Interpreter {
+    pub(super) fn dependency_object(&self, value: &Value) -> &Dependency {
+        &self.builder.dependencies[*value.as_dependency().unwrap()]
+    }
+
+    fn push_dependency(&mut self, dependency: Dependency) -> Value {
+        self.builder.dependencies.push(dependency);
+        Value::Dependency(self.builder.dependencies.len() - 1)
+    }
+
+    /// Where `.pc` files are looked for, the environment variables it reads
+    /// trigger regeneration when they change
+    fn pkg_config(&mut self) -> PkgConfig {
+        let path = self.builder.env("PKG_CONFIG_PATH");
+        let libdir = self.builder.env("PKG_CONFIG_LIBDIR");
+        let target = self.builder.compilers.first().map(|c| c.target.clone());
+        PkgConfig::new(path, libdir, target.as_deref())
+    }
+
+    pub(super) fn dependency(&mut self, args: Args) -> Result<Value, Error> {
+        let names = Value::Array(args.varargs.clone())
+            .flatten()
+            .into_iter()
+            .map(|n| n.into_string().unwrap())
+            .collect::<Vec<_>>();
+        let Some(name) = names.first().cloned() else {
+            return Err(Error::InvalidArguments(
+                "dependency() requires at least one name".to_string(),
+            ));
+        };
+
+        let Some(required) = required(&args) else {
+            crate::info!("Run-time dependency {} skipped: feature disabled", name);
+            return Ok(self.push_dependency(Dependency::not_found(&name)));
+        };
+        // An empty name is the idiom for a dependency that is never found
+        if name.is_empty() {
+            if required {
+                return Err(Error::InvalidArguments(
+                    "Dependency is both required and not-found".to_string(),
+                ));
+            }
+            return Ok(self.push_dependency(Dependency::not_found(&name)));
+        }
+
+        let method = args.kwarg_string("method").unwrap_or("auto".to_string());
+        if !["auto", "pkg-config"].contains(&method.as_str()) {
+            return Err(Error::InvalidArguments(format!(
+                "Dependency method '{}' is not supported, must be one of auto, pkg-config",
+                method
+            )));
+        }
+
+        let versions = args
+            .kwarg_list("version")
+            .into_iter()
+            .map(|v| v.into_string().unwrap())
+            .collect::<Vec<_>>();
+        let r#static = args.kwarg_bool("static").unwrap_or(false);
+
+        let pkg_config = self.pkg_config();
+        let mut reason = "tried pkgconfig".to_string();
+        for name in &names {
+            let package = match pkg_config.load(name, r#static) {
+                Ok(Some(package)) => package,
+                Ok(None) => {
+                    crate::info!("Run-time dependency {} found: NO (tried pkgconfig)", name);
+                    continue;
+                }
+                Err(e) => {
+                    crate::info!("Run-time dependency {} found: NO ({})", name, e);
+                    reason = e;
+                    continue;
+                }
+            };
+
+            if !versions
+                .iter()
+                .all(|v| version::matches(&package.version, v))
+            {
+                reason = format!(
+                    "found {} but need '{}'",
+                    package.version,
+                    versions.join("', '")
+                );
+                crate::info!("Run-time dependency {} found: NO {}", name, reason);
+                continue;
+            }
+
+            crate::info!(
+                "Run-time dependency {} found: YES {}",
+                name,
+                package.version
+            );
+            return Ok(self.push_dependency(Dependency {
+                name: name.clone(),
+                kind: DependencyKind::PkgConfig,
+                version: Some(package.version),
+                compile_args: package.cflags,
+                link_args: package.libs,
+                variables: package.variables,
+                ..Default::default()
+            }));
+        }
+
+        // Subprojects aren't supported, which only matters once a fallback
+        // would be used
+        let fallback = if !args.kwarg_list("fallback").is_empty() {
+            Some("fallback")
+        } else if args.kwarg_bool("allow_fallback") == Some(true) {
+            Some("allow_fallback")
+        } else {
+            None
+        };
+        if let Some(kwarg) = fallback {
+            return self.unsupported_call(Unsupported {
+                kind: CallKind::Kwarg,
+                name: kwarg.to_string(),
+                object: Some("dependency"),
+                file: self.subdir.join("meson.build"),
+                span: self.span,
+            });
+        }
+
+        if required {
+            return Err(Error::Dependency(format!(
+                "Dependency \"{}\" not found, {}",
+                name, reason
+            )));
+        }
+        Ok(self.push_dependency(Dependency::not_found(&name)))
+    }
+
+    pub(super) fn declare_dependency(&mut self, args: Args) -> Result<Value, Error> {
+        let strings = |name: &str| -> Vec<String> {
+            args.kwarg_list(name)
+                .into_iter()
+                .map(|v| v.into_string().unwrap())
+                .collect()
+        };
+
+        let mut dependency = Dependency {
+            name: "internal".to_string(),
+            kind: DependencyKind::Internal,
+            version: Some(
+                args.kwarg_string("version")
+                    .unwrap_or(self.builder.project.version.clone()),
+            ),
+            compile_args: strings("compile_args"),
+            link_args: strings("link_args"),
+            include_directories: self.include_directories_kwarg(&args)?,
+            link_with: self.link_targets(args.kwarg("link_with"), false, false)?,
+            link_whole: self.link_targets(args.kwarg("link_whole"), true, false)?,
+            ..Default::default()
+        };
+        for source in args.kwarg_list("sources") {
+            dependency.sources.extend(self.source_files(&source)?);
+        }
+
+        dependency.variables = match args.kwarg("variables") {
+            Some(Value::Dict(variables)) => variables
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), expect_string(v)?)))
+                .collect::<Result<_, Error>>()?,
+            _ => strings("variables")
+                .into_iter()
+                .map(|variable| match variable.split_once('=') {
+                    Some((k, v)) if !k.trim().is_empty() => {
+                        Ok((k.trim().to_string(), v.trim().to_string()))
+                    }
+                    _ => Err(Error::InvalidArguments(format!(
+                        "Variable '{}' must have a value separated by equals sign",
+                        variable
+                    ))),
+                })
+                .collect::<Result<_, Error>>()?,
+        };
+
+        // Nested dependencies are flattened so users see all of them at once
+        for nested in self.dependencies(&args) {
+            dependency.merge(&self.builder.dependencies[nested]);
+        }
+
+        Ok(self.push_dependency(dependency))
+    }
+
+    /// Copy of a dependency with only the parts asked for
+    pub(super) fn partial_dependency(
+        &mut self,
+        dependency: Value,
+        args: Args,
+    ) -> Result<Value, Error> {
+        let part = |name: &str| args.kwarg_bool(name).unwrap_or(false);
+        let dependency = self.dependency_object(&dependency);
+
+        // Include directories of external dependencies are in their compile
+        // arguments
+        let is_include = |arg: &String| arg.starts_with("-I") || arg.starts_with("-isystem");
+        let compile_args = dependency
+            .compile_args
+            .iter()
+            .filter(|arg| {
+                if is_include(arg) {
+                    part("includes")
+                } else {
+                    part("compile_args")
+                }
+            })
+            .cloned()
+            .collect();
+
+        let mut partial = Dependency {
+            name: dependency.name.clone(),
+            kind: dependency.kind,
+            version: dependency.version.clone(),
+            compile_args,
+            variables: dependency.variables.clone(),
+            ..Default::default()
+        };
+        if part("includes") {
+            partial.include_directories = dependency.include_directories.clone();
+        }
+        if part("link_args") {
+            partial.link_args = dependency.link_args.clone();
+        }
+        if part("links") {
+            partial.link_with = dependency.link_with.clone();
+            partial.link_whole = dependency.link_whole.clone();
+        }
+        if part("sources") {
+            partial.sources = dependency.sources.clone();
+        }
+
+        Ok(self.push_dependency(partial))
+    }
+
+    /// Copy of an internal dependency linking its libraries whole
+    pub(super) fn as_link_whole(&mut self, dependency: Value, _: Args) -> Result<Value, Error> {
+        let mut dependency = self.dependency_object(&dependency).clone();
+        if dependency.kind != DependencyKind::Internal {
+            return Err(Error::InvalidArguments(
+                "as_link_whole() is only valid for dependencies from declare_dependency()"
+                    .to_string(),
+            ));
+        }
+
+        for library in std::mem::take(&mut dependency.link_with) {
+            let target = &self.builder.build_targets[library];
+            if target.kind != TargetKind::StaticLibrary {
+                return Err(Error::InvalidArguments(format!(
+                    "Only static libraries can be linked whole, {} is a shared library",
+                    target.name
+                )));
+            }
+            if !dependency.link_whole.contains(&library) {
+                dependency.link_whole.push(library);
+            }
+        }
+
+        Ok(self.push_dependency(dependency))
+    }
+
+    /// `get_variable()`, the `pkgconfig` or `internal` keyword argument
+    /// matching the dependency takes precedence over the positional name
+    pub(super) fn dependency_variable(
+        &mut self,
+        dependency: Value,
+        args: Args,
+    ) -> Result<Value, Error> {
+        let dependency = self.dependency_object(&dependency);
+        let kwarg = match dependency.kind {
+            DependencyKind::PkgConfig => args.kwarg_string("pkgconfig"),
+            DependencyKind::Internal => args.kwarg_string("internal"),
+            DependencyKind::NotFound => None,
+        };
+        let name = kwarg.or_else(|| args.string(0)).ok_or_else(|| {
+            Error::InvalidArguments("get_variable() requires a variable name".to_string())
+        })?;
+
+        dependency
+            .variables
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.clone())
+            .or_else(|| args.kwarg_string("default_value"))
+            .map(Value::String)
+            .ok_or_else(|| {
+                Error::Dependency(format!(
+                    "Could not get variable '{}' of dependency \"{}\" and no default provided",
+                    name, dependency.name
+                ))
+            })
+    }
+
+    /// The `dependencies` keyword argument as indices into
+    /// `Builder::dependencies`
+    pub(super) fn dependencies(&self, args: &Args) -> Vec<usize> {
+        args.kwarg_list("dependencies")
+            .iter()
+            .map(|d| *d.as_dependency().unwrap())
+            .collect()
+    }
+
+    /// Give a target what its dependencies provide, and what the static
+    /// libraries it links have to link
+    pub(super) fn add_dependencies(&self, target: &mut BuildTarget) {
+        for &dependency in &target.dependencies.clone() {
+            target.add_dependency(&self.builder.dependencies[dependency]);
+        }
+
+        let libraries = target.link_with.iter().chain(&target.link_whole).copied();
+        for library in libraries.collect::<Vec<_>>() {
+            target.link_through(&self.builder.build_targets[library]);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::path::PathBuf;
+
+use super::{
+    custom_target::{output_name, path_string, Templates},
+    signature::Args,
+    value::Value,
+    Error, Interpreter,
+};
+use crate::{BuildTarget, File, GeneratedList, GeneratedSource, Generator, TargetId};
+
+impl Interpreter {
+    pub(super) fn generator(&mut self, args: Args) -> Result<Value, Error> {
+        let mut depends = vec![];
+        let command = self.command_arg(args.positional[0].clone(), false, &mut depends)?;
+
+        let strings = |name: &str| -> Vec<String> {
+            args.kwarg_list(name)
+                .into_iter()
+                .map(|v| v.into_string().unwrap())
+                .collect()
+        };
+        let outputs = strings("output");
+        if outputs.is_empty() {
+            return Err(Error::InvalidArguments(
+                "generator() requires at least one output".to_string(),
+            ));
+        }
+        for output in &outputs {
+            if !output.contains("@BASENAME@") && !output.contains("@PLAINNAME@") {
+                return Err(Error::InvalidArguments(format!(
+                    "Output '{}' of a generator must contain @BASENAME@ or @PLAINNAME@",
+                    output
+                )));
+            }
+            output_name(output, &["input".to_string()])?;
+        }
+
+        let capture = args.kwarg_bool("capture").unwrap_or(false);
+        if capture && outputs.len() != 1 {
+            return Err(Error::InvalidArguments(
+                "Capturing can only output to a single file".to_string(),
+            ));
+        }
+
+        for depend in args.kwarg_list("depends") {
+            depends.push(match depend {
+                Value::BuildTarget(index) => TargetId::Build(index),
+                depend => TargetId::Custom(*depend.as_custom_target().unwrap()),
+            });
+        }
+
+        self.builder.generators.push(Generator {
+            command,
+            arguments: strings("arguments"),
+            outputs,
+            depfile: args.kwarg_string("depfile"),
+            capture,
+            depends,
+        });
+        Ok(Value::Generator(self.builder.generators.len() - 1))
+    }
+
+    /// `generator.process()`, the files are only generated for the targets
+    /// that use the result
+    pub(super) fn generator_process(
+        &mut self,
+        generator: Value,
+        args: Args,
+    ) -> Result<Value, Error> {
+        let project = &self.builder.project;
+
+        let preserve_path_from = args.kwarg_string("preserve_path_from").map(PathBuf::from);
+        if let Some(dir) = &preserve_path_from {
+            if !dir.is_absolute() {
+                return Err(Error::InvalidArguments(format!(
+                    "preserve_path_from must be an absolute path, got '{}'",
+                    dir.display()
+                )));
+            }
+        }
+
+        let mut inputs = vec![];
+        for input in Value::Array(args.varargs.clone()).flatten() {
+            for file in self.source_files(&input)? {
+                let path = file.absolute_path(&project.source_dir, &project.build_dir);
+                if let Some(dir) = &preserve_path_from {
+                    if !path.starts_with(dir) {
+                        return Err(Error::InvalidArguments(format!(
+                            "Input {} is not in preserve_path_from directory {}",
+                            path.display(),
+                            dir.display()
+                        )));
+                    }
+                }
+                inputs.push(file);
+            }
+        }
+
+        self.builder.generated_lists.push(GeneratedList {
+            generator: *generator.as_generator().unwrap(),
+            inputs,
+            extra_args: args
+                .kwarg_list("extra_args")
+                .into_iter()
+                .map(|v| v.into_string().unwrap())
+                .collect(),
+            preserve_path_from,
+        });
+        Ok(Value::GeneratedList(self.builder.generated_lists.len() - 1))
+    }
+
+    /// Run the generators of the given lists for a target, their outputs
+    /// go in its private directory and become its sources
+    pub(super) fn generate_sources(
+        &self,
+        target: &mut BuildTarget,
+        lists: &[usize],
+    ) -> Result<(), Error> {
+        let project = &self.builder.project;
+        let absolute =
+            |file: &File| path_string(file.absolute_path(&project.source_dir, &project.build_dir));
+
+        for list in lists {
+            let list = &self.builder.generated_lists[*list];
+            let generator = &self.builder.generators[list.generator];
+
+            for input in &list.inputs {
+                let input_path = absolute(input);
+                let mut outdir = target.private_dir();
+                if let Some(dir) = &list.preserve_path_from {
+                    let relative = input.absolute_path(&project.source_dir, &project.build_dir);
+                    if let Some(parent) = relative.strip_prefix(dir).unwrap().parent() {
+                        outdir.push(parent);
+                    }
+                }
+
+                let in_outdir = |name: &String| -> Result<File, Error> {
+                    let name = output_name(name, std::slice::from_ref(&input_path))?;
+                    Ok(File::built(&outdir, name.as_ref()))
+                };
+                let outputs = generator
+                    .outputs
+                    .iter()
+                    .map(in_outdir)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let depfile = generator.depfile.as_ref().map(in_outdir).transpose()?;
+
+                let templates = Templates {
+                    inputs: vec![input_path.clone()],
+                    outputs: outputs.iter().map(absolute).collect(),
+                    outdir: path_string(project.build_dir.join(&outdir)),
+                    depfile: depfile.as_ref().map(absolute),
+                    private_dir: path_string(project.build_dir.join(target.private_dir())),
+                    current_source_dir: path_string(project.source_dir.join(&self.subdir)),
+                    source_root: path_string(project.source_dir.clone()),
+                    build_root: path_string(project.build_dir.clone()),
+                };
+                let mut command = generator.command.clone();
+                for arg in &generator.arguments {
+                    if arg == "@EXTRA_ARGS@" {
+                        command.extend(list.extra_args.iter().cloned());
+                        continue;
+                    }
+                    // Only generators have these, @BUILD_DIR@ is where the
+                    // outputs go
+                    let arg = arg
+                        .replace("@BUILD_DIR@", &templates.outdir)
+                        .replace("@SOURCE_DIR@", &templates.source_root);
+                    command.extend(templates.substitute(&arg)?);
+                }
+
+                target.files.extend(outputs.iter().cloned());
+                target.generated.push(GeneratedSource {
+                    input: input.clone(),
+                    outputs,
+                    command,
+                    depfile,
+                    capture: generator.capture,
+                    depends: generator.depends.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use super::{location, signature::Args, value::Value, Error, Interpreter};
+use crate::log::{self, Level};
+
+/// A key and the lines of its value
+type Entry = (String, Vec<String>);
+
+/// Entries of `summary()` by section, in the order they were added
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    sections: Vec<(String, Vec<Entry>)>,
+}
+
+impl Summary {
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty()
+    }
+
+    fn add(&mut self, section: &str, key: String, lines: Vec<String>) -> Result<(), Error> {
+        let index = match self.sections.iter().position(|(s, _)| s == section) {
+            Some(index) => index,
+            None => {
+                self.sections.push((section.to_string(), vec![]));
+                self.sections.len() - 1
+            }
+        };
+        let entries = &mut self.sections[index].1;
+        if entries.iter().any(|(k, _)| *k == key) {
+            return Err(Error::InvalidArguments(format!(
+                "Summary section '{}' already has key '{}'",
+                section, key
+            )));
+        }
+        entries.push((key, lines));
+        Ok(())
+    }
+
+    /// The summary under a `name version` heading, with the values of each
+    /// section aligned
+    pub fn render(&self, name: &str, version: &str) -> String {
+        let mut out = format!("{} {}\n", name, version);
+        for (section, entries) in &self.sections {
+            out.push('\n');
+            let indent = if section.is_empty() {
+                "  "
+            } else {
+                out += &format!("  {}\n", section);
+                "    "
+            };
+
+            let width = entries.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+            for (key, lines) in entries {
+                let mut lines = lines.iter();
+                let first = lines.next().map(String::as_str).unwrap_or("");
+                out += &format!("{}{:width$} : {}\n", indent, key, first, width = width);
+                for line in lines {
+                    out += &format!("{}{:width$}   {}\n", indent, "", line, width = width);
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Interpreter {
+    /// Where the current call is, for messages
+    fn call_location(&self) -> String {
+        location(&self.subdir.join("meson.build"), &self.span)
+    }
+
+    /// The arguments of `message()` and friends as one line, strings in
+    /// lists and dicts are quoted
+    fn stringify(&self, value: &Value, nested: bool) -> Result<String, Error> {
+        Ok(match value {
+            Value::String(s) if nested => format!("'{}'", s),
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Feature(f) => f.to_string(),
+            Value::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(|v| self.stringify(v, true))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!("[{}]", values.join(", "))
+            }
+            Value::Dict(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|(k, v)| Ok(format!("'{}' : {}", k, self.stringify(v, true)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                format!("{{{}}}", entries.join(", "))
+            }
+            v => {
+                return Err(Error::InvalidArguments(format!(
+                    "Value other than strings, integers, bools, features, dictionaries and lists thereof: {}",
+                    v.type_name()
+                )))
+            }
+        })
+    }
+
+    fn stringify_args(&self, args: &Args) -> Result<String, Error> {
+        let strings = args
+            .positional
+            .iter()
+            .chain(&args.varargs)
+            .map(|v| self.stringify(v, false))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(strings.join(" "))
+    }
+
+    pub(super) fn message(&mut self, args: Args) -> Result<Value, Error> {
+        let text = self.stringify_args(&args)?;
+        log::log_at(
+            Level::Info,
+            &self.call_location(),
+            format_args!("Message: {}", text),
+        );
+        Ok(Value::None)
+    }
+
+    pub(super) fn warning(&mut self, args: Args) -> Result<Value, Error> {
+        let text = self.stringify_args(&args)?;
+        log::log_at(
+            Level::Warning,
+            &self.call_location(),
+            format_args!("{}", text),
+        );
+        Ok(Value::None)
+    }
+
+    pub(super) fn error(&mut self, args: Args) -> Result<Value, Error> {
+        Err(Error::Problem(self.stringify_args(&args)?))
+    }
+
+    pub(super) fn assert(&mut self, args: Args) -> Result<Value, Error> {
+        if *args.positional[0].as_boolean().unwrap() {
+            return Ok(Value::None);
+        }
+        Err(Error::Assert(match args.string(1) {
+            Some(message) => format!("Assert failed: {}", message),
+            None => "Assert failed".to_string(),
+        }))
+    }
+
+    /// `summary(key, value)` or `summary(dict)`
+    pub(super) fn add_summary(&mut self, args: Args) -> Result<Value, Error> {
+        let entries = match (&args.positional[0], args.positional.get(1)) {
+            (Value::String(key), Some(value)) => vec![(key.clone(), value.clone())],
+            (Value::Dict(entries), None) => entries.clone(),
+            _ => {
+                return Err(Error::InvalidArguments(
+                    "summary() takes either a key and a value, or a dictionary".to_string(),
+                ))
+            }
+        };
+
+        let section = args.kwarg_string("section").unwrap_or_default();
+        let bool_yn = args.kwarg_bool("bool_yn").unwrap_or(false);
+        let list_sep = args.kwarg_string("list_sep");
+        for (key, value) in entries {
+            let mut lines = vec![];
+            for value in value.flatten() {
+                lines.push(self.summary_value(&value, bool_yn)?);
+            }
+            if let Some(separator) = &list_sep {
+                lines = vec![lines.join(separator)];
+            }
+            self.summary.add(&section, key, lines)?;
+        }
+
+        Ok(Value::None)
+    }
+
+    fn summary_value(&self, value: &Value, bool_yn: bool) -> Result<String, Error> {
+        Ok(match value {
+            Value::Boolean(b) if bool_yn => if *b { "YES" } else { "NO" }.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.clone(),
+            Value::Feature(f) => f.to_string(),
+            Value::Dependency(_) => {
+                let dependency = self.dependency_object(value);
+                match (&dependency.version, dependency.found()) {
+                    (_, false) => "NO".to_string(),
+                    (Some(version), true) => format!("YES {}", version),
+                    (None, true) => "YES".to_string(),
+                }
+            }
+            Value::Program(_) => {
+                let program = self.program(value);
+                if program.found() {
+                    program.path.display().to_string()
+                } else {
+                    "NO".to_string()
+                }
+            }
+            v => {
+                return Err(Error::InvalidArguments(format!(
+                    "Summary values must be str, int, bool, feature, dep, external_program or lists of those, not {}",
+                    v.type_name()
+                )))
+            }
+        })
+    }
+
+    /// What `summary()` collected, rendered for the end of setup
+    pub fn summary(&self) -> Option<String> {
+        if self.summary.is_empty() {
+            return None;
+        }
+        let project = &self.builder.project;
+        Some(self.summary.render(&project.name, &project.version))
+    }
+}
@@ -1,31 +1,236 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use crate::{parser, BuildTarget, Builder, Project};
-use ast::{Function, Node, Program};
+use crate::{
+    build_dir::CheckCache,
+    compiler::{Compiler, Language},
+    machine_file::MachineFile,
+    options::Feature,
+    parser, BuildTarget, Builder, DefaultLibrary, File, IncludeDirectories, Project,
+    SymbolVisibility, TargetKind,
+};
+use ast::{
+    Arguments, Arithmetic, Assignment, CompareOp, Comparison, Function, IfClause, MathOp,
+    MethodCall, Node, Program, Span,
+};
+use messages::Summary;
+use signature::{Args, Kwargs};
 use thiserror::Error;
+use value::Value;
 
 pub mod ast;
+mod builtins;
+mod compiler;
+mod configure_file;
+mod custom_target;
+mod dependency;
+mod generator;
+mod messages;
+mod options;
+mod program;
+pub mod signature;
+mod test;
+pub mod value;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Couldn't find a meson.build file at the path {0}")]
     MesonBuildNotFound(PathBuf),
 
-    #[error("Arguments passed to function don't match with required")]
+    #[error("Arguments passed to function don't match with required: {0}")]
     InvalidArguments(String),
 
-    #[error("Parse error")]
+    #[error("Parse error: {0}")]
     Parse(#[from] parser::Error),
 
     #[error("Invalid node expected: `{0:?}`")]
     InvalidNode(Node),
 
     #[error("Expected {}, got {:?}", expected, got)]
-    Expected { expected: String, got: Node },
+    Expected { expected: String, got: Value },
+
+    #[error("Unknown variable {0}")]
+    UnknownVariable(String),
+
+    #[error("{}: {error}", location(file, span))]
+    At {
+        file: PathBuf,
+        span: Span,
+        error: Box<Error>,
+    },
+
+    #[error("Tried to enter directory \"{0}\", which has already been visited")]
+    SubdirVisited(PathBuf),
+
+    #[error("Nonexistent build file '{0}'")]
+    SubdirNotFound(PathBuf),
+
+    #[error("File {0} does not exist")]
+    FileNotFound(PathBuf),
+
+    #[error("{0}")]
+    InvalidOption(String),
+
+    #[error("{0}")]
+    Compiler(String),
+
+    #[error("{0}")]
+    Dependency(String),
+
+    #[error("{0}")]
+    Program(String),
+
+    #[error("{0}")]
+    ConfigureFile(String),
+
+    /// From `error()` in a build file
+    #[error("Problem encountered: {0}")]
+    Problem(String),
+
+    #[error("{0}")]
+    Assert(String),
+
+    /// Not located in a build file, so never wrapped in `Error::At`
+    #[error("{0}")]
+    MachineFile(String),
+
+    /// Not located in a build file, so never wrapped in `Error::At`
+    #[error("{0}")]
+    CommandLineOption(String),
+
+    #[error("Unknown function \"{name}\"{suggestion}")]
+    UnknownFunction {
+        name: String,
+        span: Span,
+        suggestion: DidYouMean,
+    },
+
+    #[error("Unknown method \"{name}\" in object of type {object}{suggestion}")]
+    UnknownMethod {
+        name: String,
+        object: &'static str,
+        span: Span,
+        suggestion: DidYouMean,
+    },
+
+    #[error("{function}() keyword argument '{kwarg}' is not supported yet")]
+    UnsupportedKwarg {
+        function: &'static str,
+        kwarg: String,
+        span: Span,
+    },
+
+    #[error("{function}() got unknown keyword argument '{kwarg}'{suggestion}")]
+    UnknownKwarg {
+        function: String,
+        kwarg: String,
+        suggestion: DidYouMean,
+    },
+
+    #[error("{function}() takes {expected} positional arguments ({given} given)")]
+    ArgumentCount {
+        function: String,
+        expected: String,
+        given: usize,
+    },
+
+    #[error("{function}() {argument} was of type \"{got}\" but should have been \"{expected}\"")]
+    ArgumentType {
+        function: String,
+        argument: String,
+        got: &'static str,
+        expected: String,
+    },
+}
+
+fn location(file: &Path, span: &Span) -> String {
+    if span.line == 0 {
+        file.display().to_string()
+    } else {
+        format!("{}:{}", file.display(), span)
+    }
+}
+
+/// Optional closest match, displayed as a hint after an error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidYouMean(pub Option<String>);
+
+impl std::fmt::Display for DidYouMean {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(s) => write!(f, ", did you mean \"{}\"?", s),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Function,
+    Method,
+    /// A keyword argument of the function in `Unsupported::object`
+    Kwarg,
+}
+
+impl std::fmt::Display for CallKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallKind::Function => write!(f, "function"),
+            CallKind::Method => write!(f, "method"),
+            CallKind::Kwarg => write!(f, "keyword argument"),
+        }
+    }
+}
+
+/// A call to a function or method reson doesn't implement, or a keyword
+/// argument it doesn't implement in a call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    pub kind: CallKind,
+    pub name: String,
+    /// Type name of the object a method was called on
+    pub object: Option<&'static str>,
+    /// Build file the call is in, relative to the source directory
+    pub file: PathBuf,
+    pub span: Span,
 }
 
 pub struct Interpreter {
     builder: Builder,
+    variables: HashMap<String, Value>,
+
+    /// Directory of the build file being evaluated, relative to the source
+    /// directory
+    subdir: PathBuf,
+    visited_subdirs: HashSet<PathBuf>,
+    /// Location of the most recent call, for error reporting
+    span: Span,
+    /// Options given on the command line as `(name, value)`, these take
+    /// precedence over the project's default_options
+    option_overrides: Vec<(String, String)>,
+
+    /// Results of compiler checks from this and earlier configurations
+    check_cache: CheckCache,
+
+    /// Native files given on the command line, in order
+    native_files: Vec<PathBuf>,
+    machine_file: MachineFile,
+    /// Replacements from `meson.override_find_program()`
+    program_overrides: Vec<(String, Value)>,
+    /// Names `find_program()` looked for, which can't be overridden anymore
+    searched_programs: HashSet<String>,
+
+    report_unsupported: bool,
+    unsupported: Vec<Unsupported>,
+    /// Variables holding the placeholder result of an unsupported call
+    unsupported_variables: HashSet<String>,
+    /// Whether the current statement used an unsupported call's result, its
+    /// errors are then a consequence of that rather than a real problem
+    statement_unsupported: bool,
+
+    summary: Summary,
 }
 
 impl Interpreter {
@@ -39,97 +244,968 @@ impl Interpreter {
                 },
                 ..Default::default()
             },
+            variables: HashMap::from([("meson".to_string(), Value::Meson)]),
+            subdir: PathBuf::new(),
+            visited_subdirs: HashSet::new(),
+            span: Span::default(),
+            option_overrides: vec![],
+            check_cache: CheckCache::load(build_dir),
+            native_files: vec![],
+            machine_file: MachineFile::default(),
+            program_overrides: vec![],
+            searched_programs: HashSet::new(),
+            report_unsupported: false,
+            unsupported: vec![],
+            unsupported_variables: HashSet::new(),
+            statement_unsupported: false,
+            summary: Summary::default(),
         }
     }
 
+    pub fn builder(&self) -> &Builder {
+        &self.builder
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    /// Options from the command line, as `(name, value)` pairs
+    pub fn set_option_overrides(&mut self, overrides: Vec<(String, String)>) {
+        self.option_overrides = overrides;
+    }
+
+    /// Native files to read `[binaries]` from, later ones take precedence
+    pub fn set_native_files(&mut self, native_files: Vec<PathBuf>) {
+        self.native_files = native_files;
+    }
+
+    /// Read `name` as `value` instead of from the process environment,
+    /// `None` makes it unset
+    pub fn set_env(&mut self, name: &str, value: Option<&str>) {
+        self.builder.env_lookup.set(name, value);
+    }
+
+    /// Record unsupported functions and methods instead of stopping at the
+    /// first one
+    pub fn set_report_unsupported(&mut self, report: bool) {
+        self.report_unsupported = report;
+    }
+
+    pub fn unsupported(&self) -> &[Unsupported] {
+        &self.unsupported
+    }
+
     pub fn interpret(&mut self) -> Result<(), Error> {
         let meson_build = self.builder.project.source_dir.join("meson.build");
         if !meson_build.exists() {
             return Err(Error::MesonBuildNotFound(meson_build));
         }
 
-        // TODO check if file exists
-        let prog = parser::parse_file(&meson_build)?;
+        for native_file in &self.native_files {
+            self.machine_file
+                .read(native_file)
+                .map_err(Error::MachineFile)?;
+            self.builder.build_files.push(native_file.clone());
+        }
 
-        self.interpret_program(&prog)?;
+        self.visited_subdirs.insert(PathBuf::new());
+        self.interpret_file(&meson_build)
+    }
 
-        Ok(())
+    /// Evaluate the build file of the current subdir, locating any error in it
+    fn interpret_file(&mut self, path: &Path) -> Result<(), Error> {
+        let file = self.subdir.join("meson.build");
+        self.builder.build_files.push(file.clone());
+
+        let result = parser::parse_file(&path.to_path_buf())
+            .map_err(|e| match e {
+                parser::Error::At { span, error } => Error::At {
+                    file: file.clone(),
+                    span,
+                    error: Box::new(Error::Parse(*error)),
+                },
+                e => Error::Parse(e),
+            })
+            .and_then(|prog| {
+                self.span = Span::default();
+                self.interpret_program(&prog)
+            });
+
+        result.map_err(|e| match e {
+            Error::At { .. } | Error::CommandLineOption(_) | Error::MachineFile(_) => e,
+            e => Error::At {
+                file,
+                span: self.span,
+                error: Box::new(e),
+            },
+        })
+    }
+
+    fn subdir(&mut self, args: Args) -> Result<Value, Error> {
+        for dep in args.kwarg_list("if_found") {
+            if !self.is_found(&dep)? {
+                return Ok(Value::None);
+            }
+        }
+
+        let dir = PathBuf::from(args.string(0).unwrap());
+        if dir.is_absolute()
+            || dir
+                .components()
+                .any(|c| c == std::path::Component::ParentDir)
+        {
+            return Err(Error::InvalidArguments(format!(
+                "Subdir {} must be a relative path inside the current directory",
+                dir.display()
+            )));
+        }
+
+        let subdir = self.subdir.join(&dir);
+        let normalized = subdir
+            .components()
+            .filter(|c| *c != std::path::Component::CurDir)
+            .collect::<PathBuf>();
+        if !self.visited_subdirs.insert(normalized.clone()) {
+            return Err(Error::SubdirVisited(normalized));
+        }
+
+        let meson_build = self
+            .builder
+            .project
+            .source_dir
+            .join(&normalized)
+            .join("meson.build");
+        if !meson_build.exists() {
+            return Err(Error::SubdirNotFound(normalized.join("meson.build")));
+        }
+
+        let parent = std::mem::replace(&mut self.subdir, normalized);
+        let result = self.interpret_file(&meson_build);
+        self.subdir = parent;
+
+        result.map(|_| Value::None)
     }
 
     fn interpret_program(&mut self, program: &Program) -> Result<(), Error> {
-        for node in &program.nodes {
-            match node {
-                ast::Node::None => break,
-                ast::Node::Boolean(_) => todo!(),
-                ast::Node::Number(_) => todo!(),
-                ast::Node::String(_) => todo!(),
-                ast::Node::Identifier(_) => todo!(),
-                ast::Node::Assignment(_) => todo!(),
-                ast::Node::Comparison(_) => todo!(),
-                ast::Node::Arithmetic(_) => todo!(),
-                ast::Node::Or => todo!(),
-                ast::Node::IfClause(_) => todo!(),
-                ast::Node::And => todo!(),
-                ast::Node::Function(function) => self.interpret_function(function)?,
-                ast::Node::Program(_) => todo!(),
-                ast::Node::Codeblock(_) => todo!(),
+        self.codeblock(&program.nodes)
+    }
+
+    fn codeblock(&mut self, nodes: &[Node]) -> Result<(), Error> {
+        for node in nodes {
+            self.statement_unsupported = false;
+            if let Err(e) = self.statement(node) {
+                // When auditing, keep going past what unsupported calls
+                // break to find every one of them
+                if !(self.report_unsupported && self.statement_unsupported) {
+                    return Err(e);
+                }
             }
         }
 
         Ok(())
     }
 
-    fn interpret_function(&mut self, func: &Function) -> Result<(), Error> {
-        println!("Interpret function");
-        match func.name.as_str() {
-            "project" => self.project(func)?,
-            "executable" => self.executable(func)?,
-            _ => todo!("Unknown function"),
+    fn statement(&mut self, node: &Node) -> Result<(), Error> {
+        match node {
+            Node::None => Ok(()),
+            Node::Assignment(assignment) => self.assignment(assignment),
+            Node::IfClause(clause) => self.if_clause(clause),
+            Node::Program(program) => self.interpret_program(program),
+            Node::Codeblock(nodes) => self.codeblock(nodes),
+            n => self.eval(n).map(|_| ()),
         }
+    }
+
+    fn assignment(&mut self, assignment: &Assignment) -> Result<(), Error> {
+        self.span = assignment.span;
+        let name = match assignment.left.as_ref() {
+            Node::Identifier(name) => name.clone(),
+            n => return Err(Error::InvalidNode(n.clone())),
+        };
+
+        let value = self.eval(&assignment.right)?;
+        if self.statement_unsupported {
+            self.unsupported_variables.insert(name.clone());
+        } else {
+            self.unsupported_variables.remove(&name);
+        }
+        self.variables.insert(name, value);
 
         Ok(())
     }
 
-    fn project(&mut self, func: &Function) -> Result<(), Error> {
-        let args = &func.args.args;
-        let kwargs = &func.args.kwargs;
+    fn if_clause(&mut self, clause: &IfClause) -> Result<(), Error> {
+        for branch in &clause.ifs {
+            self.span = branch.span;
+            let condition = self.eval(&branch.condition)?;
+            self.span = branch.span;
+            if expect_bool(&condition)? {
+                return self.statement(&branch.block);
+            }
+        }
 
-        self.builder.project.name = if let Some(n) = args.get(0) {
-            n.clone().into_string().map_err(|e| Error::Expected {
-                expected: "String".to_string(),
-                got: e,
-            })?
-        } else {
-            return Err(Error::InvalidArguments(
-                "Project requires arguments".to_string(),
-            ));
+        Ok(())
+    }
+
+    fn eval(&mut self, node: &Node) -> Result<Value, Error> {
+        match node {
+            Node::Boolean(b) => Ok(Value::Boolean(*b)),
+            Node::Number(n) => Ok(Value::Number(*n)),
+            Node::String(s) => Ok(Value::String(s.clone())),
+            Node::Array(nodes) => Ok(Value::Array(
+                nodes
+                    .iter()
+                    .map(|n| self.eval(n))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Node::Dict(entries) => {
+                let mut dict: Vec<(String, Value)> = vec![];
+                for (key, value) in entries {
+                    let key = expect_string(&self.eval(key)?)?;
+                    if dict.iter().any(|(k, _)| *k == key) {
+                        return Err(Error::InvalidArguments(format!(
+                            "Duplicate dictionary key: {}",
+                            key
+                        )));
+                    }
+                    dict.push((key, self.eval(value)?));
+                }
+                Ok(Value::Dict(dict))
+            }
+            Node::Identifier(name) => {
+                if self.unsupported_variables.contains(name) {
+                    self.statement_unsupported = true;
+                }
+                self.variables
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| Error::UnknownVariable(name.clone()))
+            }
+            Node::Comparison(comparison) => self.comparison(comparison),
+            Node::Arithmetic(arithmetic) => self.arithmetic(arithmetic),
+            Node::Function(function) => self.interpret_function(function),
+            Node::MethodCall(method) => self.method_call(method),
+            n => Err(Error::InvalidNode(n.clone())),
+        }
+    }
+
+    fn comparison(&mut self, comparison: &Comparison) -> Result<Value, Error> {
+        self.span = comparison.span;
+        let left = self.eval(&comparison.left)?;
+        let right = self.eval(&comparison.right)?;
+        // Calls in the operands move the span
+        self.span = comparison.span;
+
+        let result = match (&comparison.op, &left, &right) {
+            (CompareOp::Equal, l, r) => l == r,
+            (op, Value::Number(l), Value::Number(r)) => match op {
+                CompareOp::Less => l < r,
+                CompareOp::LessEq => l <= r,
+                CompareOp::Greater => l > r,
+                CompareOp::GreaterEq => l >= r,
+                CompareOp::Equal => unreachable!(),
+            },
+            (_, Value::Number(_), v) | (_, v, _) => {
+                return Err(Error::Expected {
+                    expected: "Number".to_string(),
+                    got: v.clone(),
+                })
+            }
+        };
+
+        Ok(Value::Boolean(result))
+    }
+
+    fn arithmetic(&mut self, arithmetic: &Arithmetic) -> Result<Value, Error> {
+        self.span = arithmetic.span;
+        let left = self.eval(&arithmetic.left)?;
+        let right = self.eval(&arithmetic.right)?;
+        self.span = arithmetic.span;
+
+        match (&arithmetic.op, left, right) {
+            (MathOp::Add, Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+            (MathOp::Add, Value::Array(mut l), r) => {
+                l.extend(r.flatten());
+                Ok(Value::Array(l))
+            }
+            (op, Value::Number(l), Value::Number(r)) => Ok(Value::Number(match op {
+                MathOp::Add => l + r,
+                MathOp::Sub => l - r,
+                MathOp::Mul => l * r,
+                MathOp::Div => {
+                    if r == 0 {
+                        return Err(Error::InvalidArguments("Division by zero".to_string()));
+                    }
+                    l / r
+                }
+            })),
+            (_, Value::Number(_), v) | (_, v, _) => Err(Error::Expected {
+                expected: "Number".to_string(),
+                got: v,
+            }),
+        }
+    }
+
+    fn method_call(&mut self, method: &MethodCall) -> Result<Value, Error> {
+        let mut object = self.eval(&method.object)?;
+        // A both_libraries() result also has the methods of its default library
+        if object.is_both_libraries() && builtins::method(&object, &method.name).is_none() {
+            object = self.default_libraries(object);
+        }
+
+        let Some(builtin) = builtins::method(&object, &method.name) else {
+            return self.unsupported_call(Unsupported {
+                kind: CallKind::Method,
+                name: method.name.clone(),
+                object: Some(object.type_name()),
+                file: self.subdir.join("meson.build"),
+                span: method.span,
+            });
         };
 
+        self.span = method.span;
+        let args = self.eval_args(&method.args)?;
+        self.span = method.span;
+        let args = builtin.signature.validate(args.0, args.1)?;
+        self.check_features(&args);
+        let args = self.resolve_libraries(args);
+
+        (builtin.call)(self, object, args)
+    }
+
+    /// Errors on a call to something reson doesn't implement, or records it
+    /// and carries on when auditing
+    fn unsupported_call(&mut self, call: Unsupported) -> Result<Value, Error> {
+        self.span = call.span;
+        if self.report_unsupported {
+            self.unsupported.push(call);
+            self.statement_unsupported = true;
+            return Ok(Value::None);
+        }
+
+        Err(match call.kind {
+            CallKind::Function => Error::UnknownFunction {
+                suggestion: DidYouMean(suggest(&call.name, &builtins::function_names())),
+                name: call.name,
+                span: call.span,
+            },
+            CallKind::Method => {
+                let object = call.object.unwrap_or("void");
+                Error::UnknownMethod {
+                    suggestion: DidYouMean(suggest(&call.name, &builtins::method_names(object))),
+                    name: call.name,
+                    object,
+                    span: call.span,
+                }
+            }
+            CallKind::Kwarg => Error::UnsupportedKwarg {
+                function: call.object.unwrap_or("void"),
+                kwarg: call.name,
+                span: call.span,
+            },
+        })
+    }
+
+    /// Warn about features newer than the project's minimum meson_version
+    fn check_features(&self, args: &Args) {
+        let Some(minimum) = self
+            .builder
+            .project
+            .meson_version
+            .as_ref()
+            .and_then(|v| v.trim().strip_prefix(">="))
+        else {
+            return;
+        };
+
+        for (feature, since) in &args.features {
+            if crate::version::compare(since, minimum.trim()).is_gt() {
+                crate::warning!(
+                    "Project targets meson_version '>={}' but uses feature introduced in '{}': {}",
+                    minimum.trim(),
+                    since,
+                    feature
+                );
+            }
+        }
+    }
+
+    /// Evaluate call arguments, expanding a `kwargs:` dictionary into
+    /// keyword arguments
+    fn eval_args(&mut self, args: &Arguments) -> Result<(Vec<Value>, Kwargs), Error> {
+        let positional = args
+            .args
+            .iter()
+            .map(|n| self.eval(n))
+            .collect::<Result<_, _>>()?;
+
+        let mut kwargs = vec![];
+        let mut splat = vec![];
+        for (key, node) in &args.kwargs {
+            let value = self.eval(node)?;
+            if key == "kwargs" {
+                splat = value.into_dict().map_err(|v| Error::Expected {
+                    expected: "Dict".to_string(),
+                    got: v,
+                })?;
+            } else {
+                kwargs.push((key.clone(), value));
+            }
+        }
+
+        for (key, value) in splat {
+            if kwargs.iter().any(|(k, _)| *k == key) {
+                return Err(Error::InvalidArguments(format!(
+                    "Entry \"{}\" defined both as a keyword argument and in a \"kwarg\" entry",
+                    key
+                )));
+            }
+            kwargs.push((key, value));
+        }
+
+        Ok((positional, kwargs))
+    }
+
+    fn interpret_function(&mut self, func: &Function) -> Result<Value, Error> {
+        crate::debug!("Interpret function");
+        let Some(builtin) = builtins::function(&func.name) else {
+            return self.unsupported_call(Unsupported {
+                kind: CallKind::Function,
+                name: func.name.clone(),
+                object: None,
+                file: self.subdir.join("meson.build"),
+                span: func.span,
+            });
+        };
+
+        self.span = func.span;
+        let args = self.eval_args(&func.args)?;
+        self.span = func.span;
+        let args = builtin.signature.validate(args.0, args.1)?;
+        self.check_features(&args);
+        let args = self.resolve_libraries(args);
+
+        (builtin.call)(self, args)
+    }
+
+    fn project(&mut self, args: Args) -> Result<Value, Error> {
+        self.builder.project.name = args.string(0).unwrap();
+
+        if let Some(version) = args.kwarg_string("version") {
+            self.builder.project.version = version;
+        }
+        self.builder.project.meson_version = args.kwarg_string("meson_version");
+        let default_options = args.kwarg_list("default_options");
+
+        // Languages come first so their options can be set
+        for language in Value::Array(args.varargs).flatten() {
+            let language = language
+                .into_string()
+                .unwrap()
+                .parse::<Language>()
+                .map_err(Error::Compiler)?;
+            self.add_language(language)?;
+        }
+
+        self.setup_options(default_options)?;
+
+        Ok(Value::None)
+    }
+
+    /// Detect the compiler for `language` unless the project already has one
+    fn add_language(&mut self, language: Language) -> Result<(), Error> {
+        if self
+            .builder
+            .compilers
+            .iter()
+            .any(|c| c.language == language)
+        {
+            return Ok(());
+        }
+
+        let env = self.builder.env(language.env_var());
+        let compiler =
+            Compiler::detect(language, env, &self.scratch_dir()).map_err(Error::Compiler)?;
+        self.builder.compilers.push(compiler);
+
+        for option in language.options() {
+            self.builder
+                .options
+                .add(option)
+                .map_err(Error::InvalidOption)?;
+        }
+
         Ok(())
     }
 
-    fn executable(&mut self, func: &Function) -> Result<(), Error> {
-        println!("Add executable build target");
+    fn library(&mut self, args: Args) -> Result<Value, Error> {
+        let default_library = self
+            .builder
+            .options
+            .value("default_library")
+            .map(|v| v.to_string())
+            .unwrap_or_default();
 
-        let args = &func.args.args;
-        let kwargs = &func.args.kwargs;
+        match default_library
+            .parse::<DefaultLibrary>()
+            .map_err(Error::InvalidOption)?
+        {
+            DefaultLibrary::Shared => self.build_target(args, TargetKind::SharedLibrary),
+            DefaultLibrary::Static => self.build_target(args, TargetKind::StaticLibrary),
+            DefaultLibrary::Both => self.both_libraries(args),
+        }
+    }
 
-        let target_name = if let Some(n) = args.get(0) {
-            n.clone().into_string().map_err(|e| Error::Expected {
-                expected: "String".to_string(),
-                got: e,
-            })?
-        } else {
-            return Err(Error::InvalidArguments(
-                "Executable requires arguments".to_string(),
-            ));
+    fn both_libraries(&mut self, args: Args) -> Result<Value, Error> {
+        let shared = self.build_target(args.clone(), TargetKind::SharedLibrary)?;
+        let r#static = self.build_target(args, TargetKind::StaticLibrary)?;
+
+        Ok(Value::BothLibraries {
+            shared: shared.into_build_target().unwrap(),
+            r#static: r#static.into_build_target().unwrap(),
+        })
+    }
+
+    /// The `default_both_libraries` option, `auto` picks the static library
+    /// only when `static_user` links to it
+    fn prefer_static(&self, static_user: bool) -> bool {
+        let option = self.builder.options.value("default_both_libraries");
+        match option.map(|v| v.to_string()).as_deref() {
+            Some("static") => true,
+            Some("auto") => static_user,
+            _ => false,
+        }
+    }
+
+    /// Replace `both_libraries()` results with the library that uses other
+    /// than linking default to
+    fn default_libraries(&self, value: Value) -> Value {
+        match value {
+            Value::BothLibraries { shared, r#static } => {
+                Value::BuildTarget(if self.prefer_static(false) {
+                    r#static
+                } else {
+                    shared
+                })
+            }
+            Value::Array(values) => Value::Array(
+                values
+                    .into_iter()
+                    .map(|v| self.default_libraries(v))
+                    .collect(),
+            ),
+            Value::Dict(entries) => Value::Dict(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, self.default_libraries(v)))
+                    .collect(),
+            ),
+            v => v,
+        }
+    }
+
+    /// Resolve `both_libraries()` results in validated arguments, except in
+    /// `link_with` and `link_whole` which pick a library for what links
+    fn resolve_libraries(&self, mut args: Args) -> Args {
+        let resolve = |values: Vec<Value>| {
+            values
+                .into_iter()
+                .map(|v| self.default_libraries(v))
+                .collect()
         };
+        args.positional = resolve(args.positional);
+        args.varargs = resolve(args.varargs);
+        for (name, value) in &mut args.kwargs {
+            if name != "link_with" && name != "link_whole" {
+                *value = self.default_libraries(std::mem::replace(value, Value::None));
+            }
+        }
+        args
+    }
+
+    fn build_target(&mut self, args: Args, kind: TargetKind) -> Result<Value, Error> {
+        crate::debug!("Add {:?} build target", kind);
+
+        let target_name = args.string(0).unwrap();
+
+        let mut files = vec![];
+        let mut generated = vec![];
+        for file in args
+            .varargs
+            .iter()
+            .cloned()
+            .chain(args.kwarg("sources").cloned())
+            .flat_map(Value::flatten)
+        {
+            match file {
+                Value::GeneratedList(index) => generated.push(index),
+                file => files.extend(self.source_files(&file)?),
+            }
+        }
 
-        self.builder.build_targets.push(BuildTarget {
+        let mut target = BuildTarget {
             name: target_name,
-            files: vec![],
-        });
+            kind,
+            subdir: self.subdir.clone(),
+            files,
+            pic: kind == TargetKind::SharedLibrary,
+            ..Default::default()
+        };
 
-        Ok(())
+        if let Some(version) = args.kwarg_string("version") {
+            let valid = version.split('.').count() <= 3
+                && version
+                    .split('.')
+                    .all(|c| !c.is_empty() && c.chars().all(|c| c.is_ascii_digit()));
+            if !valid {
+                return Err(Error::InvalidArguments(format!(
+                    "Invalid Shared library version \"{}\". Must be of the form X.Y.Z where all three are numbers",
+                    version
+                )));
+            }
+            target.version = Some(version);
+        }
+
+        if let Some(soversion) = args.kwarg("soversion") {
+            target.soversion = Some(match soversion {
+                Value::Number(n) => n.to_string(),
+                v => expect_string(v)?,
+            });
+        } else if let Some(version) = &target.version {
+            target.soversion = version.split('.').next().map(str::to_string);
+        }
+
+        if let Some(pic) = args.kwarg_bool("pic") {
+            if kind == TargetKind::SharedLibrary && !pic {
+                return Err(Error::InvalidArguments(
+                    "Shared libraries must be position independent".to_string(),
+                ));
+            }
+            target.pic = pic;
+        } else if kind == TargetKind::StaticLibrary {
+            target.pic = self.builder.options.value("b_staticpic")
+                == Some(&crate::options::OptionValue::Boolean(true));
+        }
+
+        if let Some(visibility) = args.kwarg_string("gnu_symbol_visibility") {
+            target.gnu_symbol_visibility = visibility
+                .parse::<SymbolVisibility>()
+                .map_err(Error::InvalidArguments)?;
+        }
+
+        if let Some(defs) = args.kwarg("vs_module_defs") {
+            if kind == TargetKind::StaticLibrary {
+                return Err(Error::InvalidArguments(
+                    "vs_module_defs is only valid for shared libraries and executables".to_string(),
+                ));
+            }
+            target.vs_module_defs = Some(self.source_file(defs)?);
+        }
+
+        let r#static = kind == TargetKind::StaticLibrary;
+        target.link_with = self.link_targets(args.kwarg("link_with"), false, r#static)?;
+        target.link_whole = self.link_targets(args.kwarg("link_whole"), true, r#static)?;
+        target.link_args = args
+            .kwarg_list("link_args")
+            .into_iter()
+            .map(|a| a.into_string().unwrap())
+            .collect();
+        target.include_directories = self.include_directories_kwarg(&args)?;
+        target.implicit_include_directories = args
+            .kwarg_bool("implicit_include_directories")
+            .unwrap_or(true);
+        for (language, kwarg) in [(Language::C, "c_args"), (Language::Cpp, "cpp_args")] {
+            let language_args = args
+                .kwarg_list(kwarg)
+                .into_iter()
+                .map(|a| a.into_string().unwrap())
+                .collect::<Vec<_>>();
+            if !language_args.is_empty() {
+                target.language_args.push((language, language_args));
+            }
+        }
+        for (kwarg, files) in [
+            ("objects", &mut target.objects),
+            ("link_depends", &mut target.link_depends),
+        ] {
+            for value in args.kwarg_list(kwarg) {
+                files.extend(self.source_files(&value)?);
+            }
+        }
+
+        target.override_options = self.override_options(args.kwarg("override_options"))?;
+        target
+            .options(&self.builder.options)
+            .map_err(Error::InvalidOption)?;
+
+        // An empty list keeps the default prefix or suffix
+        for (kwarg, name) in [
+            ("name_prefix", &mut target.name_prefix),
+            ("name_suffix", &mut target.name_suffix),
+        ] {
+            match args.kwarg(kwarg) {
+                Some(Value::String(value)) => *name = Some(value.clone()),
+                Some(Value::Array(values)) if values.is_empty() => {}
+                Some(v) => {
+                    return Err(Error::InvalidArguments(format!(
+                        "{} must be a string or an empty array, got {}",
+                        kwarg,
+                        v.type_name()
+                    )))
+                }
+                None => {}
+            }
+        }
+
+        target.build_by_default = args.kwarg_bool("build_by_default").unwrap_or(true);
+        target.install = args.kwarg_bool("install").unwrap_or(false);
+        target.install_dir = match args.kwarg("install_dir") {
+            Some(Value::Boolean(false)) | None => None,
+            Some(v) => Some(expect_string(v)?),
+        };
+
+        target.dependencies = self.dependencies(&args);
+        self.add_dependencies(&mut target);
+        // The outputs' paths depend on the target's file name
+        self.generate_sources(&mut target, &generated)?;
+
+        self.builder.build_targets.push(target);
+
+        Ok(Value::BuildTarget(self.builder.build_targets.len() - 1))
+    }
+
+    fn files(&mut self, args: Args) -> Result<Value, Error> {
+        let files = args
+            .varargs
+            .iter()
+            .flat_map(|v| v.clone().flatten())
+            .map(|f| self.source_file(&f).map(Value::File))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Value::Array(files))
+    }
+
+    fn include_directories(&mut self, args: Args) -> Result<Value, Error> {
+        let dirs = Value::Array(args.varargs.clone())
+            .flatten()
+            .into_iter()
+            .map(|d| d.into_string().unwrap())
+            .collect::<Vec<_>>();
+        let include_directories =
+            self.new_include_directories(&dirs, args.kwarg_bool("is_system").unwrap_or(false))?;
+
+        self.builder.include_directories.push(include_directories);
+        Ok(Value::IncludeDirectories(
+            self.builder.include_directories.len() - 1,
+        ))
+    }
+
+    /// Include directories relative to the current subdir, which must exist
+    /// in the source or build directory
+    fn new_include_directories(
+        &self,
+        dirs: &[String],
+        is_system: bool,
+    ) -> Result<IncludeDirectories, Error> {
+        let project = &self.builder.project;
+        let source_subdir = project.source_dir.join(&self.subdir);
+        let build_subdir = project.build_dir.join(&self.subdir);
+
+        for dir in dirs {
+            let path = Path::new(dir);
+            if path.is_absolute() && path.starts_with(&project.source_dir) {
+                return Err(Error::InvalidArguments(format!(
+                    "Tried to form an absolute path to {} in the source tree, use a path relative to the current directory instead",
+                    dir
+                )));
+            }
+            if !source_subdir.join(path).is_dir() && !build_subdir.join(path).is_dir() {
+                return Err(Error::FileNotFound(self.subdir.join(path)));
+            }
+        }
+
+        Ok(IncludeDirectories {
+            subdir: self.subdir.clone(),
+            dirs: dirs.iter().map(PathBuf::from).collect(),
+            is_system,
+        })
+    }
+
+    /// The `include_directories` keyword argument, strings are taken as
+    /// include directories of the current subdir
+    fn include_directories_kwarg(&self, args: &Args) -> Result<Vec<IncludeDirectories>, Error> {
+        args.kwarg_list("include_directories")
+            .into_iter()
+            .map(|inc| match inc {
+                Value::IncludeDirectories(index) => {
+                    Ok(self.builder.include_directories[index].clone())
+                }
+                dir => self.new_include_directories(&[dir.into_string().unwrap()], false),
+            })
+            .collect()
+    }
+
+    /// Resolve a source argument, strings are relative to the current subdir
+    /// and must exist at configure time
+    fn source_file(&self, value: &Value) -> Result<File, Error> {
+        let file = match value {
+            Value::File(file) => return Ok(file.clone()),
+            Value::String(name) => File::source(&self.subdir, Path::new(name)),
+            v => {
+                return Err(Error::Expected {
+                    expected: "String or File".to_string(),
+                    got: v.clone(),
+                })
+            }
+        };
+
+        let project = &self.builder.project;
+        if !file
+            .absolute_path(&project.source_dir, &project.build_dir)
+            .exists()
+        {
+            return Err(Error::FileNotFound(file.relative_path()));
+        }
+
+        Ok(file)
+    }
+
+    /// `name=value` strings or a dictionary of option values
+    fn override_options(&self, value: Option<&Value>) -> Result<Vec<(String, String)>, Error> {
+        let Some(value) = value else {
+            return Ok(vec![]);
+        };
+
+        match value {
+            Value::Dict(entries) => entries
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Boolean(b) => b.to_string(),
+                        Value::Number(n) => n.to_string(),
+                        v => expect_string(v)?,
+                    };
+                    Ok((name.clone(), value))
+                })
+                .collect(),
+            value => value
+                .clone()
+                .flatten()
+                .into_iter()
+                .map(|option| {
+                    let option = expect_string(&option)?;
+                    option
+                        .split_once('=')
+                        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                        .ok_or_else(|| {
+                            Error::InvalidOption(format!(
+                                "Override option '{}' must be of the form name=value",
+                                option
+                            ))
+                        })
+                })
+                .collect(),
+        }
     }
+
+    /// Libraries to link with, `both_libraries()` results giving the static
+    /// library when linked whole or `default_both_libraries` prefers it for
+    /// a `static_user`
+    fn link_targets(
+        &self,
+        value: Option<&Value>,
+        whole: bool,
+        static_user: bool,
+    ) -> Result<Vec<usize>, Error> {
+        let Some(value) = value else {
+            return Ok(vec![]);
+        };
+
+        let mut targets = vec![];
+        for v in value.clone().flatten() {
+            let index = match v {
+                Value::BuildTarget(index) => index,
+                Value::BothLibraries { shared, r#static } => {
+                    if whole || self.prefer_static(static_user) {
+                        r#static
+                    } else {
+                        shared
+                    }
+                }
+                v => {
+                    return Err(Error::Expected {
+                        expected: "BuildTarget".to_string(),
+                        got: v,
+                    })
+                }
+            };
+
+            let target = &self.builder.build_targets[index];
+            if !target.is_library() {
+                return Err(Error::InvalidArguments(format!(
+                    "Link target {} is not a library",
+                    target.name
+                )));
+            }
+            if whole && target.kind != TargetKind::StaticLibrary {
+                return Err(Error::InvalidArguments(format!(
+                    "Only static libraries can be linked whole, {} is a shared library",
+                    target.name
+                )));
+            }
+
+            targets.push(index);
+        }
+
+        Ok(targets)
+    }
+
+    /// Whether a dependency-like object was found
+    fn is_found(&self, value: &Value) -> Result<bool, Error> {
+        match value {
+            Value::Dependency(_) => Ok(self.dependency_object(value).found()),
+            Value::Program(_) => Ok(self.program(value).found()),
+            v => Err(Error::Expected {
+                expected: "an object with a found() method".to_string(),
+                got: v.clone(),
+            }),
+        }
+    }
+}
+
+/// Closest candidate to `name`, if any is close enough to be a likely typo
+fn suggest(name: &str, candidates: &[&str]) -> Option<String> {
+    candidates
+        .iter()
+        .map(|c| (strsim::normalized_damerau_levenshtein(name, c), c))
+        .filter(|(score, _)| *score >= 0.6)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, c)| c.to_string())
+}
+
+/// The `required` keyword argument, `None` when a disabled feature skips
+/// the lookup altogether
+fn required(args: &Args) -> Option<bool> {
+    match args.kwarg("required") {
+        Some(Value::Boolean(required)) => Some(*required),
+        Some(Value::Feature(Feature::Enabled)) => Some(true),
+        Some(Value::Feature(Feature::Disabled)) => None,
+        _ => Some(false),
+    }
+}
+
+fn expect_string(value: &Value) -> Result<String, Error> {
+    value.as_string().cloned().ok_or_else(|| Error::Expected {
+        expected: "String".to_string(),
+        got: value.clone(),
+    })
+}
+
+fn expect_bool(value: &Value) -> Result<bool, Error> {
+    value.as_boolean().copied().ok_or_else(|| Error::Expected {
+        expected: "Boolean".to_string(),
+        got: value.clone(),
+    })
 }
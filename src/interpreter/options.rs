@@ -0,0 +1,222 @@
+use super::{ast::Node, builtins, signature::Args, value::Value, Error, Interpreter};
+use crate::{
+    options::{BuildOption, Feature, OptionType, OptionValue, Options, Section},
+    parser,
+};
+
+impl Interpreter {
+    /// Declare the project's options and give them their values, command line
+    /// overrides win over the project's default_options
+    pub(super) fn setup_options(&mut self, default_options: Vec<Value>) -> Result<(), Error> {
+        self.read_options_file()?;
+
+        for option in default_options {
+            let option = option.into_string().unwrap();
+            let (name, value) = option.split_once('=').ok_or_else(|| {
+                Error::InvalidOption(format!(
+                    "Default option '{}' must be of the form name=value",
+                    option
+                ))
+            })?;
+            self.builder
+                .options
+                .set(name.trim(), value.trim())
+                .map_err(Error::InvalidOption)?;
+        }
+
+        for (name, value) in &self.option_overrides {
+            self.builder
+                .options
+                .set(name, value)
+                .map_err(Error::CommandLineOption)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the command line overrides against `saved`, the options of the
+    /// last configuration, with the project's options read again from its
+    /// option file. meson.build isn't interpreted, so nothing else happens.
+    pub fn check_option_overrides(&mut self, saved: &Options) -> Result<(), Error> {
+        let others = saved.iter().filter(|o| o.section != Section::Project);
+        self.builder.options = Options::from_saved(others.cloned().collect());
+        self.read_options_file()?;
+
+        for (name, value) in &self.option_overrides {
+            self.builder
+                .options
+                .set(name, value)
+                .map_err(Error::CommandLineOption)?;
+        }
+        Ok(())
+    }
+
+    /// Read project options from `meson.options`, or `meson_options.txt` for
+    /// older projects
+    pub(super) fn read_options_file(&mut self) -> Result<(), Error> {
+        let source_dir = &self.builder.project.source_dir;
+        let Some(name) = ["meson.options", "meson_options.txt"]
+            .into_iter()
+            .find(|name| source_dir.join(name).exists())
+        else {
+            return Ok(());
+        };
+        let path = source_dir.join(name);
+        self.builder.build_files.push(name.into());
+
+        let located = |span, e| Error::At {
+            file: name.into(),
+            span,
+            error: Box::new(e),
+        };
+
+        let program = parser::parse_file(&path).map_err(|e| match e {
+            parser::Error::At { span, error } => located(span, Error::Parse(*error)),
+            e => located(Default::default(), Error::Parse(e)),
+        })?;
+        for node in &program.nodes {
+            let Node::Function(func) = node else {
+                return Err(located(
+                    node.span().unwrap_or_default(),
+                    Error::InvalidArguments(
+                        "Option files may only contain option() calls".to_string(),
+                    ),
+                ));
+            };
+            if func.name != "option" {
+                return Err(located(
+                    func.span,
+                    Error::InvalidArguments(format!(
+                        "Only calls to option() are allowed in option files, not {}()",
+                        func.name
+                    )),
+                ));
+            }
+
+            let option = self
+                .eval_args(&func.args)
+                .and_then(|(args, kwargs)| builtins::OPTION.validate(args, kwargs))
+                .and_then(|args| self.option(args))
+                .map_err(|e| located(func.span, e))?;
+
+            self.builder
+                .options
+                .add(option)
+                .map_err(|e| located(func.span, Error::InvalidOption(e)))?;
+        }
+
+        Ok(())
+    }
+
+    fn option(&mut self, args: Args) -> Result<BuildOption, Error> {
+        let name = args.string(0).unwrap();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Error::InvalidOption(format!(
+                "Option name {} must only contain letters, numbers, dashes and underscores",
+                name
+            )));
+        }
+
+        let strings = |name: &str| -> Option<Vec<String>> {
+            args.kwarg(name).map(|v| {
+                v.clone()
+                    .flatten()
+                    .into_iter()
+                    .filter_map(|v| v.into_string().ok())
+                    .collect()
+            })
+        };
+
+        let invalid = |message: &str| Error::InvalidOption(format!("Option {}: {}", name, message));
+
+        let ty = args
+            .kwarg_string("type")
+            .ok_or_else(|| invalid("the type keyword argument is required"))?;
+        let ty = match ty.as_str() {
+            "string" => OptionType::String,
+            "boolean" => OptionType::Boolean,
+            "combo" => OptionType::Combo(
+                strings("choices")
+                    .filter(|c| !c.is_empty())
+                    .ok_or_else(|| invalid("combo options must have choices"))?,
+            ),
+            "integer" => OptionType::Integer {
+                min: args.kwarg("min").and_then(|v| v.as_number()).copied(),
+                max: args.kwarg("max").and_then(|v| v.as_number()).copied(),
+            },
+            "array" => OptionType::Array(strings("choices")),
+            "feature" => OptionType::Feature,
+            t => return Err(invalid(&format!("unknown option type '{}'", t))),
+        };
+
+        let default = match (&ty, args.kwarg("value")) {
+            (OptionType::String, None) => OptionValue::String(String::new()),
+            (OptionType::String, Some(Value::String(s))) => OptionValue::String(s.clone()),
+            (OptionType::Boolean, None) => OptionValue::Boolean(true),
+            (OptionType::Boolean, Some(Value::Boolean(b))) => OptionValue::Boolean(*b),
+            (OptionType::Boolean, Some(Value::String(s))) => {
+                ty.parse(&name, s).map_err(Error::InvalidOption)?
+            }
+            (OptionType::Combo(choices), None) => OptionValue::String(choices[0].clone()),
+            (OptionType::Combo(_), Some(Value::String(s))) => OptionValue::String(s.clone()),
+            (OptionType::Integer { min, .. }, None) => OptionValue::Integer(min.unwrap_or(0)),
+            (OptionType::Integer { .. }, Some(Value::Number(n))) => OptionValue::Integer(*n),
+            (OptionType::Array(choices), None) => {
+                OptionValue::Array(choices.clone().unwrap_or_default())
+            }
+            (OptionType::Array(_), Some(v @ (Value::Array(_) | Value::String(_)))) => {
+                OptionValue::Array(
+                    v.clone()
+                        .flatten()
+                        .into_iter()
+                        .map(|v| {
+                            v.into_string()
+                                .map_err(|_| invalid("array values must be strings"))
+                        })
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            (OptionType::Feature, None) => OptionValue::Feature(Feature::Auto),
+            (OptionType::Feature, Some(Value::String(s))) => {
+                ty.parse(&name, s).map_err(Error::InvalidOption)?
+            }
+            (ty, Some(v)) => {
+                return Err(invalid(&format!(
+                    "value of type {} is not valid for a {} option",
+                    v.type_name(),
+                    ty.name()
+                )))
+            }
+        };
+        let default = ty.validate(&name, default).map_err(Error::InvalidOption)?;
+
+        Ok(BuildOption::new(
+            &name,
+            &args.kwarg_string("description").unwrap_or_default(),
+            Section::Project,
+            ty,
+            default,
+        ))
+    }
+
+    pub(super) fn get_option(&mut self, args: Args) -> Result<Value, Error> {
+        let name = args.string(0).unwrap();
+        let value = self.builder.options.value(&name).ok_or_else(|| {
+            Error::InvalidOption(format!("Tried to access unknown option \"{}\"", name))
+        })?;
+
+        Ok(match value {
+            OptionValue::String(s) => Value::String(s.clone()),
+            OptionValue::Boolean(b) => Value::Boolean(*b),
+            OptionValue::Integer(i) => Value::Number(*i),
+            OptionValue::Array(items) => {
+                Value::Array(items.iter().cloned().map(Value::String).collect())
+            }
+            OptionValue::Feature(feature) => Value::Feature(*feature),
+        })
+    }
+}
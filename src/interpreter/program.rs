@@ -0,0 +1,285 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use super::{required, signature::Args, value::Value, Error, Interpreter};
+use crate::{compiler::which, log, version, Program, TargetKind};
+
+/// How to run the file at `path`, scripts that aren't executable are run
+/// with the interpreter of their `#!` line
+fn command(path: &Path) -> Option<Vec<String>> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+
+    let path = path.to_string_lossy().to_string();
+    if metadata.permissions().mode() & 0o111 != 0 {
+        return Some(vec![path]);
+    }
+
+    let contents = fs::read(&path).ok()?;
+    let first_line = contents.split(|b| *b == b'\n').next()?;
+    let shebang = String::from_utf8_lossy(first_line.strip_prefix(b"#!")?).to_string();
+    let mut command = shebang
+        .split_whitespace()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if command.is_empty() {
+        return None;
+    }
+    command.push(path);
+    Some(command)
+}
+
+/// First thing looking like a version number in a program's output
+fn version_number(output: &str) -> Option<String> {
+    let mut rest = output;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(candidate.len());
+        let number = candidate[..end].trim_end_matches('.');
+        if number.contains('.') {
+            return Some(number.to_string());
+        }
+        rest = &candidate[end..];
+    }
+
+    None
+}
+
+impl Interpreter {
+    pub(super) fn program(&self, value: &Value) -> &Program {
+        &self.builder.programs[*value.as_program().unwrap()]
+    }
+
+    fn push_program(&mut self, program: Program) -> Value {
+        self.builder.programs.push(program);
+        Value::Program(self.builder.programs.len() - 1)
+    }
+
+    /// Where `name` would be run from: the native files, `dirs`, the current
+    /// source directory and finally `PATH`
+    pub(super) fn search_program(
+        &self,
+        name: &Value,
+        dirs: &[PathBuf],
+    ) -> Option<(PathBuf, Vec<String>)> {
+        let project = &self.builder.project;
+        let found = |path: PathBuf| command(&path).map(|command| (path, command));
+
+        let name = match name {
+            Value::File(file) => {
+                return found(file.absolute_path(&project.source_dir, &project.build_dir))
+            }
+            name => name.as_string().unwrap(),
+        };
+
+        if let Some(binary) = self.machine_file.binary(name) {
+            let (program, args) = binary.split_first()?;
+            let path = which(program)?;
+            let mut command = command(&path)?;
+            command.extend(args.iter().cloned());
+            return Some((path, command));
+        }
+
+        let source_dir = project.source_dir.join(&self.subdir);
+        if name.contains('/') {
+            return found(source_dir.join(name));
+        }
+        let in_dirs = dirs
+            .iter()
+            .chain([&source_dir])
+            .find_map(|dir| found(dir.join(name)));
+        in_dirs.or_else(|| found(which(name)?))
+    }
+
+    pub(super) fn find_program(&mut self, args: Args) -> Result<Value, Error> {
+        let names = Value::Array(args.varargs.clone()).flatten();
+        let display = |name: &Value| match name {
+            Value::File(file) => file.relative_path().display().to_string(),
+            name => name.as_string().unwrap().clone(),
+        };
+        let Some(first) = names.first().map(display) else {
+            return Err(Error::InvalidArguments(
+                "find_program() requires at least one name".to_string(),
+            ));
+        };
+
+        let Some(required) = required(&args) else {
+            crate::info!("Program {} skipped: feature disabled", first);
+            return Ok(self.push_program(Program::not_found(&first)));
+        };
+
+        let versions = args
+            .kwarg_list("version")
+            .into_iter()
+            .map(|v| v.into_string().unwrap())
+            .collect::<Vec<_>>();
+        let version_argument = args
+            .kwarg_string("version_argument")
+            .unwrap_or("--version".to_string());
+        let dirs = args
+            .kwarg_list("dirs")
+            .into_iter()
+            .map(|dir| {
+                let dir = PathBuf::from(dir.into_string().unwrap());
+                self.builder.project.source_dir.join(&self.subdir).join(dir)
+            })
+            .collect::<Vec<_>>();
+
+        let mut reason = String::new();
+        for name in &names {
+            let overridden = name.as_string().and_then(|n| self.program_override(n));
+            let (found, location) = match overridden {
+                Some(program) => (program, "overridden".to_string()),
+                None => {
+                    let Some((path, command)) = self.search_program(name, &dirs) else {
+                        crate::info!("Program {} found: NO", display(name));
+                        continue;
+                    };
+                    let location = path.display().to_string();
+                    let program = Program {
+                        name: display(name),
+                        path,
+                        command,
+                        ..Default::default()
+                    };
+                    (self.push_program(program), location)
+                }
+            };
+            if let Some(name) = name.as_string() {
+                self.searched_programs.insert(name.clone());
+            }
+
+            if versions.is_empty() {
+                crate::info!("Program {} found: YES ({})", display(name), location);
+                return Ok(found);
+            }
+
+            let version = self.program_version(&found, &version_argument)?;
+            if versions.iter().all(|v| version::matches(&version, v)) {
+                crate::info!(
+                    "Program {} found: YES {} ({})",
+                    display(name),
+                    version,
+                    location
+                );
+                return Ok(found);
+            }
+            reason = format!(", found {} but need '{}'", version, versions.join("', '"));
+            crate::info!("Program {} found: NO{}", display(name), reason);
+        }
+
+        if required {
+            return Err(Error::Program(format!(
+                "Program '{}' not found or not executable{}",
+                first, reason
+            )));
+        }
+        Ok(self.push_program(Program::not_found(&first)))
+    }
+
+    /// What `meson.override_find_program()` replaced `name` with
+    pub(super) fn program_override(&mut self, name: &str) -> Option<Value> {
+        let (_, program) = self.program_overrides.iter().find(|(n, _)| n == name)?;
+        Some(match program.clone() {
+            Value::BuildTarget(index) => {
+                let target = &self.builder.build_targets[index];
+                let path = self.builder.project.build_dir.join(target.output());
+                self.push_program(Program {
+                    name: name.to_string(),
+                    command: vec![path.to_string_lossy().to_string()],
+                    path,
+                    target: Some(index),
+                    ..Default::default()
+                })
+            }
+            program => program,
+        })
+    }
+
+    pub(super) fn override_find_program(&mut self, args: Args) -> Result<Value, Error> {
+        let name = args.string(0).unwrap();
+        if self.searched_programs.contains(&name) {
+            return Err(Error::Program(format!(
+                "Tried to override finding of executable \"{}\" which has already been found",
+                name
+            )));
+        }
+        if self.program_overrides.iter().any(|(n, _)| *n == name) {
+            return Err(Error::Program(format!(
+                "Tried to override executable \"{}\" which has already been overridden",
+                name
+            )));
+        }
+
+        let program = args.positional[1].clone();
+        match &program {
+            Value::BuildTarget(index)
+                if self.builder.build_targets[*index].kind != TargetKind::Executable =>
+            {
+                return Err(Error::InvalidArguments(format!(
+                    "Only executables can override programs, {} is a library",
+                    self.builder.build_targets[*index].name
+                )));
+            }
+            Value::Program(_) if !self.program(&program).found() => {
+                return Err(Error::InvalidArguments(format!(
+                    "Can't override \"{}\" with a program that wasn't found",
+                    name
+                )));
+            }
+            _ => {}
+        }
+
+        self.program_overrides.push((name, program));
+        Ok(Value::None)
+    }
+
+    /// Version the program reports when run with `argument`, a program the
+    /// project builds has the project's version
+    pub(super) fn program_version(
+        &mut self,
+        value: &Value,
+        argument: &str,
+    ) -> Result<String, Error> {
+        let program = self.program(value);
+        if let Some(version) = &program.version {
+            return Ok(version.clone());
+        }
+        if program.target.is_some() {
+            return Ok(self.builder.project.version.clone());
+        }
+
+        let Some((executable, args)) = program.command.split_first() else {
+            return Err(Error::Program(format!(
+                "Program '{}' was not found, so it has no version",
+                program.name
+            )));
+        };
+        let output = log::run(Command::new(executable).args(args).arg(argument))
+            .map_err(|e| Error::Program(format!("Failed to run {}: {}", executable, e)))?;
+
+        // Some programs print their version to stderr
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let version = version_number(&stdout)
+            .or_else(|| version_number(&stderr))
+            .ok_or_else(|| {
+                Error::Program(format!(
+                    "Could not find a version number in the output of {} {}",
+                    program.path.display(),
+                    argument
+                ))
+            })?;
+
+        self.builder.programs[*value.as_program().unwrap()].version = Some(version.clone());
+        Ok(version)
+    }
+}
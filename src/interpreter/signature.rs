@@ -0,0 +1,356 @@
+use super::{suggest, value::Value, DidYouMean, Error};
+
+/// Evaluated keyword arguments in source order
+pub type Kwargs = Vec<(String, Value)>;
+
+/// Type an argument is checked against before a builtin sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Any,
+    Boolean,
+    Number,
+    String,
+    File,
+    BuildTarget,
+    CustomTarget,
+    GeneratedList,
+    ConfigurationData,
+    IncludeDirectories,
+    Dependency,
+    Program,
+    Dict,
+    Feature,
+    /// A single value or a (possibly nested) array of values, always handed
+    /// to the builtin as a flat array
+    List(&'static Type),
+    OneOf(&'static [Type]),
+}
+
+impl Type {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            Type::Any => true,
+            Type::Boolean => value.is_boolean(),
+            Type::Number => value.is_number(),
+            Type::String => value.is_string(),
+            Type::File => value.is_file(),
+            Type::BuildTarget => value.is_build_target() || value.is_both_libraries(),
+            Type::CustomTarget => value.is_custom_target(),
+            Type::GeneratedList => value.is_generated_list(),
+            Type::ConfigurationData => value.is_configuration_data(),
+            Type::IncludeDirectories => value.is_include_directories(),
+            Type::Dependency => value.is_dependency(),
+            Type::Program => value.is_program(),
+            Type::Dict => value.is_dict(),
+            Type::Feature => value.is_feature(),
+            Type::List(inner) => value.clone().flatten().iter().all(|v| inner.matches(v)),
+            Type::OneOf(types) => types.iter().any(|t| t.matches(value)),
+        }
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Number => write!(f, "int"),
+            Type::String => write!(f, "str"),
+            Type::File => write!(f, "file"),
+            Type::BuildTarget => write!(f, "build_tgt"),
+            Type::CustomTarget => write!(f, "custom_tgt"),
+            Type::GeneratedList => write!(f, "generated_list"),
+            Type::ConfigurationData => write!(f, "cfg_data"),
+            Type::IncludeDirectories => write!(f, "inc"),
+            Type::Dependency => write!(f, "dep"),
+            Type::Program => write!(f, "external_program"),
+            Type::List(inner) => write!(f, "list[{}]", inner),
+            Type::Dict => write!(f, "dict"),
+            Type::Feature => write!(f, "feature"),
+            Type::OneOf(types) => {
+                let types = types.iter().map(Type::to_string).collect::<Vec<_>>();
+                write!(f, "{}", types.join(" | "))
+            }
+        }
+    }
+}
+
+/// Constant default for a keyword argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Literal {
+    Boolean(bool),
+    Number(i64),
+    String(&'static str),
+    EmptyArray,
+}
+
+impl From<Literal> for Value {
+    fn from(literal: Literal) -> Self {
+        match literal {
+            Literal::Boolean(b) => Value::Boolean(b),
+            Literal::Number(n) => Value::Number(n),
+            Literal::String(s) => Value::String(s.to_string()),
+            Literal::EmptyArray => Value::Array(vec![]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Positional {
+    pub name: &'static str,
+    pub ty: Type,
+    pub optional: bool,
+}
+
+impl Positional {
+    pub const fn new(name: &'static str, ty: Type) -> Self {
+        Self {
+            name,
+            ty,
+            optional: false,
+        }
+    }
+
+    pub const fn optional(self) -> Self {
+        Self {
+            optional: true,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Kwarg {
+    pub name: &'static str,
+    pub ty: Type,
+    pub default: Option<Literal>,
+    pub since: Option<&'static str>,
+    pub deprecated: Option<&'static str>,
+}
+
+impl Kwarg {
+    pub const fn new(name: &'static str, ty: Type) -> Self {
+        Self {
+            name,
+            ty,
+            default: None,
+            since: None,
+            deprecated: None,
+        }
+    }
+
+    pub const fn default(self, default: Literal) -> Self {
+        Self {
+            default: Some(default),
+            ..self
+        }
+    }
+
+    pub const fn since(self, version: &'static str) -> Self {
+        Self {
+            since: Some(version),
+            ..self
+        }
+    }
+
+    pub const fn deprecated(self, message: &'static str) -> Self {
+        Self {
+            deprecated: Some(message),
+            ..self
+        }
+    }
+}
+
+/// Declarative description of what a builtin function or method accepts
+#[derive(Debug, Clone, Copy)]
+pub struct Signature {
+    pub name: &'static str,
+    pub positional: &'static [Positional],
+    pub varargs: Option<Positional>,
+    pub kwargs: &'static [Kwarg],
+    pub since: Option<&'static str>,
+    pub deprecated: Option<&'static str>,
+}
+
+impl Signature {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            positional: &[],
+            varargs: None,
+            kwargs: &[],
+            since: None,
+            deprecated: None,
+        }
+    }
+
+    pub const fn positional(self, positional: &'static [Positional]) -> Self {
+        Self { positional, ..self }
+    }
+
+    pub const fn varargs(self, varargs: Positional) -> Self {
+        Self {
+            varargs: Some(varargs),
+            ..self
+        }
+    }
+
+    pub const fn kwargs(self, kwargs: &'static [Kwarg]) -> Self {
+        Self { kwargs, ..self }
+    }
+
+    pub const fn since(self, version: &'static str) -> Self {
+        Self {
+            since: Some(version),
+            ..self
+        }
+    }
+
+    pub const fn deprecated(self, message: &'static str) -> Self {
+        Self {
+            deprecated: Some(message),
+            ..self
+        }
+    }
+
+    /// Check a call against the signature, converting list arguments to flat
+    /// arrays and filling in keyword defaults
+    pub fn validate(&self, positional: Vec<Value>, kwargs: Kwargs) -> Result<Args, Error> {
+        let required = self.positional.iter().filter(|p| !p.optional).count();
+        if positional.len() < required
+            || (self.varargs.is_none() && positional.len() > self.positional.len())
+        {
+            let expected = if self.varargs.is_some() {
+                format!("at least {}", required)
+            } else if required == self.positional.len() {
+                required.to_string()
+            } else {
+                format!("between {} and {}", required, self.positional.len())
+            };
+
+            return Err(Error::ArgumentCount {
+                function: self.name.to_string(),
+                expected,
+                given: positional.len(),
+            });
+        }
+
+        let mut args = Args::default();
+        for (i, value) in positional.into_iter().enumerate() {
+            let spec = self.positional.get(i).or(self.varargs.as_ref()).unwrap();
+            let value = self.check(spec.ty, value, || format!("argument {}", i + 1))?;
+
+            if i < self.positional.len() {
+                args.positional.push(value);
+            } else {
+                args.varargs.push(value);
+            }
+        }
+
+        for (name, value) in kwargs {
+            let Some(spec) = self.kwargs.iter().find(|k| k.name == name) else {
+                let names = self.kwargs.iter().map(|k| k.name).collect::<Vec<_>>();
+                return Err(Error::UnknownKwarg {
+                    function: self.name.to_string(),
+                    suggestion: DidYouMean(suggest(&name, &names)),
+                    kwarg: name,
+                });
+            };
+
+            if let Some(message) = spec.deprecated {
+                crate::warning!(
+                    "{}() keyword argument '{}' is deprecated: {}",
+                    self.name,
+                    name,
+                    message
+                );
+            }
+            if let Some(since) = spec.since {
+                args.features
+                    .push((format!("{}() {}", self.name, name), since));
+            }
+
+            let value = self.check(spec.ty, value, || format!("keyword argument '{}'", name))?;
+            args.kwargs.push((name, value));
+        }
+
+        for spec in self.kwargs {
+            if let Some(default) = spec.default {
+                if args.get(spec.name).is_none() {
+                    args.kwargs.push((spec.name.to_string(), default.into()));
+                }
+            }
+        }
+
+        if let Some(message) = self.deprecated {
+            crate::warning!("{}() is deprecated: {}", self.name, message);
+        }
+        if let Some(since) = self.since {
+            args.features.push((format!("{}()", self.name), since));
+        }
+
+        Ok(args)
+    }
+
+    fn check(&self, ty: Type, value: Value, argument: impl Fn() -> String) -> Result<Value, Error> {
+        if !ty.matches(&value) {
+            return Err(Error::ArgumentType {
+                function: self.name.to_string(),
+                argument: argument(),
+                got: value.type_name(),
+                expected: ty.to_string(),
+            });
+        }
+
+        Ok(match ty {
+            Type::List(_) => Value::Array(value.flatten()),
+            _ => value,
+        })
+    }
+}
+
+/// Arguments of a call that passed validation
+#[derive(Debug, Default, Clone)]
+pub struct Args {
+    pub positional: Vec<Value>,
+    pub varargs: Vec<Value>,
+    /// In source order, followed by the defaults of those not given
+    pub kwargs: Kwargs,
+
+    /// Versioned features the call used, as `(feature, since)`
+    pub features: Vec<(String, &'static str)>,
+}
+
+impl Args {
+    pub fn string(&self, index: usize) -> Option<String> {
+        self.positional
+            .get(index)
+            .and_then(|v| v.as_string())
+            .cloned()
+    }
+
+    /// A keyword argument as given, including an explicit `none`
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.kwargs.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    pub fn kwarg(&self, name: &str) -> Option<&Value> {
+        self.get(name).filter(|v| !v.is_none())
+    }
+
+    pub fn kwarg_string(&self, name: &str) -> Option<String> {
+        self.kwarg(name).and_then(|v| v.as_string()).cloned()
+    }
+
+    pub fn kwarg_bool(&self, name: &str) -> Option<bool> {
+        self.kwarg(name).and_then(|v| v.as_boolean()).copied()
+    }
+
+    /// A `Type::List` keyword argument, empty when not given
+    pub fn kwarg_list(&self, name: &str) -> Vec<Value> {
+        self.kwarg(name)
+            .cloned()
+            .map(Value::flatten)
+            .unwrap_or_default()
+    }
+}
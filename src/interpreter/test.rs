@@ -0,0 +1,113 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use super::{signature::Args, value::Value, Error, Interpreter};
+use crate::{Protocol, TargetId, Test};
+
+impl Interpreter {
+    /// `test()` and `benchmark()`, which only differ in how they're run
+    pub(super) fn test(&mut self, args: Args, is_benchmark: bool) -> Result<Value, Error> {
+        let name = args.string(0).unwrap();
+
+        let mut targets = vec![];
+        let mut command = self.command_arg(args.positional[1].clone(), false, &mut targets)?;
+        for arg in args.kwarg_list("args") {
+            command.extend(self.command_arg(arg, false, &mut targets)?);
+        }
+        for depend in args.kwarg_list("depends") {
+            targets.push(match depend {
+                Value::BuildTarget(index) => TargetId::Build(index),
+                depend => TargetId::Custom(*depend.as_custom_target().unwrap()),
+            });
+        }
+
+        let env = match args.kwarg("env") {
+            Some(Value::Dict(entries)) => entries
+                .iter()
+                .map(|(name, value)| match value {
+                    Value::String(value) => Ok((name.clone(), value.clone())),
+                    value => Err(Error::InvalidArguments(format!(
+                        "Value of environment variable '{}' must be a string, not {}",
+                        name,
+                        value.type_name()
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(list) => list
+                .clone()
+                .flatten()
+                .into_iter()
+                .map(|v| {
+                    let v = v.into_string().unwrap();
+                    v.split_once('=')
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .ok_or_else(|| {
+                            Error::InvalidArguments(format!(
+                                "Environment variable '{}' must be of the form NAME=value",
+                                v
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![],
+        };
+
+        let workdir = args.kwarg_string("workdir").map(PathBuf::from);
+        if let Some(dir) = &workdir {
+            if !dir.is_absolute() {
+                return Err(Error::InvalidArguments(format!(
+                    "workdir must be an absolute path, got '{}'",
+                    dir.display()
+                )));
+            }
+        }
+
+        let protocol = args
+            .kwarg_string("protocol")
+            .unwrap_or_default()
+            .parse::<Protocol>()
+            .map_err(Error::InvalidArguments)?;
+
+        let project = &self.builder.project.name;
+        let mut suites = vec![project.clone()];
+        for suite in args.kwarg_list("suite") {
+            suites.push(format!(
+                "{}:{}",
+                project,
+                suite.into_string().unwrap().replace(' ', "_")
+            ));
+        }
+
+        let mut depends = vec![];
+        for target in targets {
+            match target {
+                TargetId::Build(index) => depends.push(self.builder.build_targets[index].output()),
+                TargetId::Custom(index) => depends.extend(
+                    self.builder.custom_targets[index]
+                        .output_files()
+                        .iter()
+                        .map(|f| f.relative_path()),
+                ),
+            }
+        }
+        // The same target can be used anywhere in the command
+        let mut seen = HashSet::new();
+        depends.retain(|depend| seen.insert(depend.clone()));
+
+        self.builder.tests.push(Test {
+            name,
+            is_benchmark,
+            command,
+            env,
+            timeout: *args.kwarg("timeout").unwrap().as_number().unwrap(),
+            suites,
+            should_fail: args.kwarg_bool("should_fail").unwrap_or(false),
+            // Benchmarks are timed, so they never share the machine
+            is_parallel: !is_benchmark && args.kwarg_bool("is_parallel").unwrap_or(true),
+            protocol,
+            depends,
+            workdir,
+            priority: *args.kwarg("priority").unwrap().as_number().unwrap(),
+        });
+        Ok(Value::None)
+    }
+}
@@ -0,0 +1,83 @@
+use enum_as_inner::EnumAsInner;
+
+use crate::{compiler::CheckOutput, options::Feature, File};
+
+/// The result of evaluating a node
+#[derive(PartialEq, Eq, Debug, Clone, EnumAsInner)]
+pub enum Value {
+    None,
+
+    Boolean(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<Value>),
+    /// Keys in insertion order
+    Dict(Vec<(String, Value)>),
+
+    File(File),
+    Feature(Feature),
+    /// Index into `Builder::build_targets`
+    BuildTarget(usize),
+    /// Result of `both_libraries()`, indices into `Builder::build_targets`
+    BothLibraries {
+        shared: usize,
+        r#static: usize,
+    },
+    /// Index into `Builder::custom_targets`
+    CustomTarget(usize),
+    /// Index into `Builder::generators`
+    Generator(usize),
+    /// Index into `Builder::generated_lists`
+    GeneratedList(usize),
+    /// Index into `Builder::configuration_data`
+    ConfigurationData(usize),
+    /// Index into `Builder::include_directories`
+    IncludeDirectories(usize),
+    /// Index into `Builder::dependencies`
+    Dependency(usize),
+    /// Index into `Builder::programs`
+    Program(usize),
+
+    /// The `meson` object
+    Meson,
+    /// Index into `Builder::compilers`
+    Compiler(usize),
+    /// Result of `compiler.run()`
+    RunResult(CheckOutput),
+}
+
+impl Value {
+    /// Name of the type as shown to users
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::None => "void",
+            Value::Boolean(_) => "bool",
+            Value::Number(_) => "int",
+            Value::String(_) => "str",
+            Value::Array(_) => "array",
+            Value::Dict(_) => "dict",
+            Value::File(_) => "file",
+            Value::Feature(_) => "feature",
+            Value::BuildTarget(_) => "build_tgt",
+            Value::BothLibraries { .. } => "both_libs",
+            Value::CustomTarget(_) => "custom_tgt",
+            Value::Generator(_) => "generator",
+            Value::GeneratedList(_) => "generated_list",
+            Value::ConfigurationData(_) => "cfg_data",
+            Value::IncludeDirectories(_) => "inc",
+            Value::Dependency(_) => "dep",
+            Value::Program(_) => "external_program",
+            Value::Meson => "meson",
+            Value::Compiler(_) => "compiler",
+            Value::RunResult(_) => "runresult",
+        }
+    }
+
+    /// Meson flattens nested arrays wherever a list is accepted
+    pub fn flatten(self) -> Vec<Value> {
+        match self {
+            Value::Array(values) => values.into_iter().flat_map(Value::flatten).collect(),
+            v => vec![v],
+        }
+    }
+}
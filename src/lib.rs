@@ -1,35 +1,636 @@
 #![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
 
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-mod compiler;
+pub mod backend;
+pub mod build_dir;
+pub mod compiler;
+pub mod configuration;
 pub mod interpreter;
+pub mod log;
+pub mod machine_file;
+pub mod options;
 pub mod parser;
+pub mod pkgconfig;
+pub mod test_runner;
+pub mod version;
 
 #[derive(Default)]
 pub struct Builder {
     pub project: Project,
-    pub compiler: Compiler,
+    /// Compilers for the project's languages
+    pub compilers: Vec<compiler::Compiler>,
+    pub options: options::Options,
 
     pub build_targets: Vec<BuildTarget>,
+    pub custom_targets: Vec<CustomTarget>,
+    pub generators: Vec<Generator>,
+    pub generated_lists: Vec<GeneratedList>,
+    pub configuration_data: Vec<configuration::ConfigurationData>,
+    pub include_directories: Vec<IncludeDirectories>,
+    pub dependencies: Vec<Dependency>,
+    pub programs: Vec<Program>,
+    /// From `test()` and `benchmark()`
+    pub tests: Vec<Test>,
+
+    /// Every build and option file read, relative to the source directory
+    pub build_files: Vec<PathBuf>,
+    /// Environment variables consulted while configuring, `None` when unset
+    pub environment: Vec<(String, Option<String>)>,
+    /// Where environment variables are read from
+    pub env_lookup: EnvLookup,
+}
+
+impl Builder {
+    /// Read an environment variable, remembering it so a change to it
+    /// triggers regeneration
+    pub fn env(&mut self, name: &str) -> Option<String> {
+        let value = self.env_lookup.get(name);
+        if !self.environment.iter().any(|(n, _)| n == name) {
+            self.environment.push((name.to_string(), value.clone()));
+        }
+        value
+    }
+}
+
+/// Reads environment variables from the process environment, except those
+/// overridden, so tests can set variables without changing the process
+#[derive(Debug, Clone, Default)]
+pub struct EnvLookup {
+    /// `None` makes a variable unset
+    overrides: HashMap<String, Option<String>>,
+}
+
+impl EnvLookup {
+    pub fn set(&mut self, name: &str, value: Option<&str>) {
+        self.overrides
+            .insert(name.to_string(), value.map(str::to_string));
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.overrides.get(name) {
+            Some(value) => value.clone(),
+            None => std::env::var(name).ok(),
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Project {
     pub name: String,
     pub version: String,
+    pub meson_version: Option<String>,
     pub source_dir: PathBuf,
     pub build_dir: PathBuf,
 }
 
-#[derive(Default)]
-pub struct Compiler {
+/// What `library()` builds
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultLibrary {
+    #[default]
+    Shared,
+    Static,
+    Both,
+}
+
+impl std::str::FromStr for DefaultLibrary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(Self::Shared),
+            "static" => Ok(Self::Static),
+            "both" => Ok(Self::Both),
+            _ => Err(format!(
+                "Value '{}' for default_library is invalid, must be one of shared, static, both",
+                s
+            )),
+        }
+    }
+}
+
+/// A source or generated file, remembering the directory it was declared in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct File {
+    /// Generated in the build directory rather than part of the source tree
+    pub is_built: bool,
+    /// Directory the file belongs to, relative to the source or build directory
+    pub subdir: PathBuf,
+    pub name: PathBuf,
+}
+
+impl File {
+    pub fn source(subdir: &Path, name: &Path) -> Self {
+        Self {
+            is_built: false,
+            subdir: subdir.to_path_buf(),
+            name: name.to_path_buf(),
+        }
+    }
+
+    pub fn built(subdir: &Path, name: &Path) -> Self {
+        Self {
+            is_built: true,
+            subdir: subdir.to_path_buf(),
+            name: name.to_path_buf(),
+        }
+    }
+
+    /// Path relative to the source directory for sources, or the build
+    /// directory for built files
+    pub fn relative_path(&self) -> PathBuf {
+        self.subdir.join(&self.name)
+    }
+
+    pub fn absolute_path(&self, source_dir: &Path, build_dir: &Path) -> PathBuf {
+        if self.is_built {
+            build_dir.join(self.relative_path())
+        } else {
+            source_dir.join(self.relative_path())
+        }
+    }
+}
+
+/// Where a dependency's arguments come from
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    #[default]
+    NotFound,
+    PkgConfig,
+    /// Declared in the project with `declare_dependency()`
+    Internal,
+}
+
+impl std::fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyKind::NotFound => write!(f, "not-found"),
+            DependencyKind::PkgConfig => write!(f, "pkgconfig"),
+            DependencyKind::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+/// The result of `include_directories()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct IncludeDirectories {
+    /// Directory `include_directories()` was called in
+    pub subdir: PathBuf,
+    /// Relative to `subdir` in both the source and build directory, or
+    /// absolute for directories outside the source tree
+    pub dirs: Vec<PathBuf>,
+    /// Included with `-isystem`, silencing warnings in their headers
+    pub is_system: bool,
+}
+
+impl IncludeDirectories {
+    /// Directories to search in order, the build directory comes first so
+    /// generated headers take precedence over the source tree's
+    pub fn paths(&self, source_dir: &Path, build_dir: &Path) -> Vec<PathBuf> {
+        let mut paths = vec![];
+        for dir in &self.dirs {
+            if dir.is_absolute() {
+                paths.push(dir.clone());
+            } else {
+                paths.push(build_dir.join(&self.subdir).join(dir));
+                paths.push(source_dir.join(&self.subdir).join(dir));
+            }
+        }
+        paths
+    }
+}
+
+/// The result of `dependency()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub kind: DependencyKind,
+    pub version: Option<String>,
+    pub compile_args: Vec<String>,
+    pub link_args: Vec<String>,
+    pub include_directories: Vec<IncludeDirectories>,
+    /// Added to the sources of targets using the dependency
+    pub sources: Vec<File>,
+    /// Indices into `Builder::build_targets`
+    pub link_with: Vec<usize>,
+    pub link_whole: Vec<usize>,
+    /// Variables of the pkg-config file or `declare_dependency()`, for
+    /// `get_variable()`
+    pub variables: Vec<(String, String)>,
+}
+
+impl Dependency {
+    pub fn not_found(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn found(&self) -> bool {
+        self.kind != DependencyKind::NotFound
+    }
+
+    /// Add what `other` provides, dependencies are transitive
+    pub fn merge(&mut self, other: &Dependency) {
+        extend_unique(&mut self.compile_args, &other.compile_args);
+        extend_unique(&mut self.link_args, &other.link_args);
+        extend_unique(&mut self.include_directories, &other.include_directories);
+        extend_unique(&mut self.sources, &other.sources);
+        extend_unique(&mut self.link_with, &other.link_with);
+        extend_unique(&mut self.link_whole, &other.link_whole);
+    }
+}
+
+/// Append the items of `new` that `items` doesn't have yet
+fn extend_unique<T: Clone + PartialEq>(items: &mut Vec<T>, new: &[T]) {
+    for item in new {
+        if !items.contains(item) {
+            items.push(item.clone());
+        }
+    }
+}
+
+/// The result of `find_program()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Program {
     pub name: String,
     pub path: PathBuf,
+    /// What to run, the program itself or e.g. the interpreter of a script
+    /// followed by the script, empty when not found
+    pub command: Vec<String>,
+    /// Index into `Builder::build_targets` when the project builds it
+    pub target: Option<usize>,
+    /// Reported by the program, once it was asked
+    pub version: Option<String>,
 }
 
-#[derive(Default)]
+impl Program {
+    pub fn not_found(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn found(&self) -> bool {
+        !self.command.is_empty()
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    #[default]
+    Executable,
+    StaticLibrary,
+    SharedLibrary,
+}
+
+/// Values accepted by `gnu_symbol_visibility`
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolVisibility {
+    #[default]
+    Unset,
+    Default,
+    Internal,
+    Hidden,
+    Protected,
+    InlinesHidden,
+}
+
+impl std::str::FromStr for SymbolVisibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Ok(Self::Unset),
+            "default" => Ok(Self::Default),
+            "internal" => Ok(Self::Internal),
+            "hidden" => Ok(Self::Hidden),
+            "protected" => Ok(Self::Protected),
+            "inlineshidden" => Ok(Self::InlinesHidden),
+            _ => Err(format!(
+                "Invalid gnu_symbol_visibility '{}', must be one of default, internal, hidden, protected, inlineshidden",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct BuildTarget {
     pub name: String,
-    pub files: Vec<PathBuf>,
+    pub kind: TargetKind,
+    /// Directory the target was defined in, relative to the source directory,
+    /// outputs go in the same directory under the build directory
+    pub subdir: PathBuf,
+    pub files: Vec<File>,
+    /// Generator runs producing some of `files`
+    pub generated: Vec<GeneratedSource>,
+
+    pub version: Option<String>,
+    pub soversion: Option<String>,
+    pub pic: bool,
+    pub gnu_symbol_visibility: SymbolVisibility,
+    pub vs_module_defs: Option<File>,
+
+    /// Indices into `Builder::build_targets`
+    pub link_with: Vec<usize>,
+    pub link_whole: Vec<usize>,
+    /// Indices into `Builder::dependencies`
+    pub dependencies: Vec<usize>,
+
+    /// Arguments for compiling the target's sources in every language,
+    /// including those of its dependencies
+    pub compile_args: Vec<String>,
+    /// Arguments for one language only, from `c_args` and `cpp_args`
+    pub language_args: Vec<(compiler::Language, Vec<String>)>,
+    pub include_directories: Vec<IncludeDirectories>,
+    /// Search the target's own directory in the source and build directory
+    pub implicit_include_directories: bool,
+    pub link_args: Vec<String>,
+    /// Object files linked in as they are
+    pub objects: Vec<File>,
+    /// Files linking reads besides the objects, e.g. linker scripts
+    pub link_depends: Vec<File>,
+    /// Options set for this target only, as `(name, value)`
+    pub override_options: Vec<(String, String)>,
+
+    /// Replace the `lib` prefix and the extension of the file name
+    pub name_prefix: Option<String>,
+    pub name_suffix: Option<String>,
+    pub build_by_default: bool,
+    pub install: bool,
+    pub install_dir: Option<String>,
+}
+
+impl BuildTarget {
+    pub fn is_library(&self) -> bool {
+        self.kind != TargetKind::Executable
+    }
+
+    /// Take on what a dependency provides
+    pub fn add_dependency(&mut self, dependency: &Dependency) {
+        extend_unique(&mut self.compile_args, &dependency.compile_args);
+        extend_unique(
+            &mut self.include_directories,
+            &dependency.include_directories,
+        );
+        extend_unique(&mut self.link_args, &dependency.link_args);
+        extend_unique(&mut self.files, &dependency.sources);
+        extend_unique(&mut self.link_with, &dependency.link_with);
+        extend_unique(&mut self.link_whole, &dependency.link_whole);
+    }
+
+    /// A static library isn't linked itself, so whoever links it also has
+    /// to link what it links
+    pub fn link_through(&mut self, library: &BuildTarget) {
+        if library.kind != TargetKind::StaticLibrary {
+            return;
+        }
+        extend_unique(&mut self.link_with, &library.link_with);
+        extend_unique(&mut self.link_args, &library.link_args);
+    }
+
+    /// Name of the file the target produces
+    pub fn filename(&self) -> String {
+        let prefix = self.prefix();
+        if let Some(suffix) = &self.name_suffix {
+            return match suffix.as_str() {
+                "" => format!("{}{}", prefix, self.name),
+                suffix => format!("{}{}.{}", prefix, self.name, suffix),
+            };
+        }
+
+        match self.kind {
+            TargetKind::Executable => format!("{}{}", prefix, self.name),
+            TargetKind::StaticLibrary => format!("{}{}.a", prefix, self.name),
+            TargetKind::SharedLibrary => match (&self.version, &self.soversion) {
+                (Some(version), _) => format!("{}{}.so.{}", prefix, self.name, version),
+                (None, Some(soversion)) => format!("{}{}.so.{}", prefix, self.name, soversion),
+                (None, None) => format!("{}{}.so", prefix, self.name),
+            },
+        }
+    }
+
+    fn prefix(&self) -> &str {
+        match (&self.name_prefix, self.kind) {
+            (Some(prefix), _) => prefix,
+            (None, TargetKind::Executable) => "",
+            (None, _) => "lib",
+        }
+    }
+
+    /// The project's options with those the target overrides
+    pub fn options(&self, project: &options::Options) -> Result<options::Options, String> {
+        let mut options = project.clone();
+        for (name, value) in &self.override_options {
+            options.set(name, value)?;
+        }
+        Ok(options)
+    }
+
+    /// Output path relative to the build directory
+    pub fn output(&self) -> PathBuf {
+        self.subdir.join(self.filename())
+    }
+
+    /// Directory for files only this target uses, relative to the build
+    /// directory
+    pub fn private_dir(&self) -> PathBuf {
+        self.subdir.join(format!("{}.p", self.filename()))
+    }
+
+    /// Name the dynamic loader looks a shared library up by
+    pub fn soname(&self) -> Option<String> {
+        if self.kind != TargetKind::SharedLibrary {
+            return None;
+        }
+        match (&self.soversion, &self.name_suffix) {
+            (Some(soversion), None) => {
+                Some(format!("{}{}.so.{}", self.prefix(), self.name, soversion))
+            }
+            _ => Some(self.filename()),
+        }
+    }
+
+    /// Symlinks to create next to a shared library as `(link, target)` pairs,
+    /// e.g. `libfoo.so -> libfoo.so.1 -> libfoo.so.1.2.3`
+    pub fn aliases(&self) -> Vec<(String, String)> {
+        // A custom suffix has no versioned names
+        if self.kind != TargetKind::SharedLibrary || self.name_suffix.is_some() {
+            return vec![];
+        }
+
+        let prefix = self.prefix();
+        let mut chain = vec![format!("{}{}.so", prefix, self.name)];
+        if let Some(soversion) = &self.soversion {
+            chain.push(format!("{}{}.so.{}", prefix, self.name, soversion));
+        }
+        if let Some(version) = &self.version {
+            chain.push(format!("{}{}.so.{}", prefix, self.name, version));
+        }
+        chain.dedup();
+
+        chain
+            .windows(2)
+            .map(|w| (w[0].clone(), w[1].clone()))
+            .collect()
+    }
+}
+
+/// A target of either kind, for what can depend on both
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetId {
+    /// Index into `Builder::build_targets`
+    Build(usize),
+    /// Index into `Builder::custom_targets`
+    Custom(usize),
+}
+
+/// Files generated by running a command, from `custom_target()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct CustomTarget {
+    pub name: String,
+    /// Directory the target was defined in, outputs go in the same directory
+    /// under the build directory
+    pub subdir: PathBuf,
+    pub inputs: Vec<File>,
+    /// File names, all in `subdir`
+    pub outputs: Vec<String>,
+    /// Program and arguments with every template substituted
+    pub command: Vec<String>,
+    /// Write the command's standard output to the single output
+    pub capture: bool,
+    /// Give the single input to the command on its standard input
+    pub feed: bool,
+    /// Dependency file the command writes, in `subdir`
+    pub depfile: Option<String>,
+    /// Files the command reads besides its inputs
+    pub depend_files: Vec<File>,
+    /// Targets to build before running the command, besides those in it
+    pub depends: Vec<TargetId>,
+    pub build_always_stale: bool,
+    pub build_by_default: bool,
+    pub install: bool,
+    /// One directory per output
+    pub install_dir: Vec<String>,
+}
+
+impl CustomTarget {
+    /// The outputs as files a target can take as sources
+    pub fn output_files(&self) -> Vec<File> {
+        self.outputs
+            .iter()
+            .map(|output| File::built(&self.subdir, Path::new(output)))
+            .collect()
+    }
+}
+
+/// A program run on each file it's given, from `generator()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Generator {
+    /// How to run the program
+    pub command: Vec<String>,
+    /// Arguments with templates still to substitute for each input
+    pub arguments: Vec<String>,
+    /// File names with templates still to substitute for each input
+    pub outputs: Vec<String>,
+    pub depfile: Option<String>,
+    pub capture: bool,
+    /// Targets to build before running the program
+    pub depends: Vec<TargetId>,
+}
+
+/// Files given to `generator.process()`, the outputs only exist once a
+/// target uses them
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedList {
+    /// Index into `Builder::generators`
+    pub generator: usize,
+    pub inputs: Vec<File>,
+    pub extra_args: Vec<String>,
+    /// Directory, relative to the source directory, whose structure below
+    /// it is kept in the output paths
+    pub preserve_path_from: Option<PathBuf>,
+}
+
+/// A generator's run on one input for a target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedSource {
+    pub input: File,
+    /// In the target's private directory
+    pub outputs: Vec<File>,
+    /// Program and arguments with every template substituted
+    pub command: Vec<String>,
+    pub depfile: Option<File>,
+    pub capture: bool,
+    pub depends: Vec<TargetId>,
+}
+
+/// How a test reports its result
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Exit code 0 passes, 77 skips and 99 is an error
+    #[default]
+    ExitCode,
+    /// Test Anything Protocol on standard output
+    Tap,
+    Gtest,
+    Rust,
+}
+
+impl std::str::FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exitcode" => Ok(Self::ExitCode),
+            "tap" => Ok(Self::Tap),
+            "gtest" => Ok(Self::Gtest),
+            "rust" => Ok(Self::Rust),
+            _ => Err(format!(
+                "Protocol '{}' is invalid, must be one of exitcode, tap, gtest, rust",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::ExitCode => write!(f, "exitcode"),
+            Protocol::Tap => write!(f, "tap"),
+            Protocol::Gtest => write!(f, "gtest"),
+            Protocol::Rust => write!(f, "rust"),
+        }
+    }
+}
+
+/// A program to run after building, from `test()` or `benchmark()`
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Test {
+    pub name: String,
+    pub is_benchmark: bool,
+    /// Program and arguments, with absolute paths to files and targets
+    pub command: Vec<String>,
+    /// Variables set in addition to the inherited environment
+    pub env: Vec<(String, String)>,
+    /// Seconds before the test is stopped, zero or less for no limit
+    pub timeout: i64,
+    /// The project's name, plus `project:suite` for each suite given
+    pub suites: Vec<String>,
+    pub should_fail: bool,
+    pub is_parallel: bool,
+    pub protocol: Protocol,
+    /// Built files the test needs, relative to the build directory
+    pub depends: Vec<PathBuf>,
+    /// Directory to run in, the build directory when unset
+    pub workdir: Option<PathBuf>,
+    /// Tests with a higher priority are started first
+    pub priority: i64,
 }
@@ -0,0 +1,243 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, IsTerminal, Write},
+    path::Path,
+    process::{Command, Output},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Mutex,
+    },
+};
+
+/// ANSI escapes for level prefixes and test results
+pub const RED: &str = "\x1b[1;31m";
+pub const GREEN: &str = "\x1b[1;32m";
+pub const YELLOW: &str = "\x1b[1;33m";
+pub const DIM: &str = "\x1b[2m";
+
+/// Name of the log file in the build directory's `meson-logs`
+pub const LOG_FILE: &str = "meson-log.txt";
+
+/// How much is shown, each level includes the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl Level {
+    fn prefix(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR: ",
+            Level::Warning => "WARNING: ",
+            Level::Info => "",
+            Level::Debug => "DEBUG: ",
+        }
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Level::Error => RED,
+            Level::Warning => YELLOW,
+            Level::Info => "",
+            Level::Debug => DIM,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+/// Gets every message regardless of the level, once opened
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Start writing everything logged to `build_dir/meson-logs/meson-log.txt`,
+/// replacing the log of an earlier configuration
+pub fn open_file(build_dir: &Path) -> io::Result<()> {
+    let dir = build_dir.join("meson-logs");
+    fs::create_dir_all(&dir)?;
+    let file = File::create(dir.join(LOG_FILE))?;
+    *LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Print a message if its level is shown, errors and warnings go to stderr
+pub fn log(level: Level, args: fmt::Arguments) {
+    write(level, None, args);
+}
+
+/// Like `log`, prefixed with where in a build file the message comes from
+pub fn log_at(level: Level, location: &str, args: fmt::Arguments) {
+    write(level, Some(location), args);
+}
+
+/// A message as printed, with its prefix coloured when `colour` is set
+fn line(level: Level, location: Option<&str>, args: fmt::Arguments, colour: bool) -> String {
+    let location = location.map(|l| format!("{}: ", l)).unwrap_or_default();
+    let prefix = match level.prefix() {
+        "" => String::new(),
+        prefix if colour => format!("{}{}\x1b[0m", level.colour(), prefix),
+        prefix => prefix.to_string(),
+    };
+    format!("{}{}{}", location, prefix, args)
+}
+
+/// Colours are only used on terminals, and never with `NO_COLOR` set
+fn use_colour(terminal: bool) -> bool {
+    terminal && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// `text` in one of the colours above when standard output is a terminal
+pub fn paint(colour: &str, text: &str) -> String {
+    if use_colour(io::stdout().is_terminal()) {
+        format!("{}{}\x1b[0m", colour, text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn write(level: Level, location: Option<&str>, args: fmt::Arguments) {
+    if let Some(file) = LOG.lock().unwrap().as_mut() {
+        // The log is a diagnostic aid, failing to write it isn't fatal
+        let _ = writeln!(file, "{}", line(level, location, args, false));
+    }
+
+    if !enabled(level) {
+        return;
+    }
+    // Unlike println!, a closed pipe doesn't abort
+    let _ = match level {
+        Level::Error | Level::Warning => {
+            let colour = use_colour(io::stderr().is_terminal());
+            writeln!(io::stderr(), "{}", line(level, location, args, colour))
+        }
+        Level::Info | Level::Debug => {
+            let colour = use_colour(io::stdout().is_terminal());
+            writeln!(io::stdout(), "{}", line(level, location, args, colour))
+        }
+    };
+}
+
+/// Run a command to completion, logging its command line, exit code and
+/// output at debug level
+pub fn run(command: &mut Command) -> io::Result<Output> {
+    let mut command_line = vec![command.get_program().to_string_lossy()];
+    command_line.extend(command.get_args().map(|arg| arg.to_string_lossy()));
+    log(
+        Level::Debug,
+        format_args!("Running command: {}", command_line.join(" ")),
+    );
+    if let Some(dir) = command.get_current_dir() {
+        log(
+            Level::Debug,
+            format_args!("Working directory: {}", dir.display()),
+        );
+    }
+
+    let output = command.output();
+    match &output {
+        Ok(output) => {
+            let code = output
+                .status
+                .code()
+                .map_or("none".to_string(), |c| c.to_string());
+            log(Level::Debug, format_args!("Exit code: {}", code));
+            for (name, contents) in [("stdout", &output.stdout), ("stderr", &output.stderr)] {
+                let contents = String::from_utf8_lossy(contents);
+                if !contents.trim().is_empty() {
+                    log(
+                        Level::Debug,
+                        format_args!("-----{}-----\n{}", name, contents.trim_end()),
+                    );
+                }
+            }
+        }
+        Err(e) => log(Level::Debug, format_args!("Failed to run: {}", e)),
+    }
+    output
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Warning, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Info, format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::log($crate::log::Level::Debug, format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let cases = [
+            (Level::Info, None, false, "text"),
+            (Level::Warning, None, false, "WARNING: text"),
+            (
+                Level::Error,
+                Some("meson.build:1:1"),
+                false,
+                "meson.build:1:1: ERROR: text",
+            ),
+            (Level::Warning, None, true, "\x1b[1;33mWARNING: \x1b[0mtext"),
+            (Level::Info, None, true, "text"),
+        ];
+        for (level, location, colour, expected) in cases {
+            assert_eq!(
+                line(level, location, format_args!("text"), colour),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn log_file() {
+        let build_dir = tempfile::tempdir().unwrap();
+        open_file(build_dir.path()).unwrap();
+        run(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"])).unwrap();
+        debug!("only in the log");
+        *LOG.lock().unwrap() = None;
+
+        let contents =
+            fs::read_to_string(build_dir.path().join("meson-logs").join(LOG_FILE)).unwrap();
+        for expected in [
+            "DEBUG: Running command: sh -c echo out; echo err >&2; exit 3\n",
+            "DEBUG: Exit code: 3\n",
+            "DEBUG: -----stdout-----\nout\n",
+            "DEBUG: -----stderr-----\nerr\n",
+            "DEBUG: only in the log\n",
+        ] {
+            assert!(contents.contains(expected), "{}", contents);
+        }
+    }
+}
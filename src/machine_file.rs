@@ -0,0 +1,119 @@
+use std::{fs, path::Path};
+
+/// What reson uses from a native file given to setup, later files override
+/// earlier ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MachineFile {
+    /// `[binaries]` entries as `(name, command)`
+    pub binaries: Vec<(String, Vec<String>)>,
+}
+
+impl MachineFile {
+    pub fn read(&mut self, path: &Path) -> Result<(), String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read machine file {}: {}", path.display(), e))?;
+        self.parse(path, &contents)
+    }
+
+    fn parse(&mut self, path: &Path, contents: &str) -> Result<(), String> {
+        let invalid = |line: &str| format!("Malformed line in {}: {}", path.display(), line);
+
+        let mut section = None;
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim());
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            // Only the binaries are used, but every section has to be valid
+            let value = strings(value.trim()).ok_or_else(|| invalid(line))?;
+            match section {
+                Some("binaries") => {
+                    let key = key.trim().to_string();
+                    self.binaries.retain(|(name, _)| *name != key);
+                    self.binaries.push((key, value));
+                }
+                Some(_) => {}
+                None => return Err(invalid(line)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The command for `name` in `[binaries]`
+    pub fn binary(&self, name: &str) -> Option<&[String]> {
+        self.binaries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, command)| command.as_slice())
+    }
+}
+
+/// A quoted string or an array of them, other values are kept as written
+fn strings(value: &str) -> Option<Vec<String>> {
+    let quoted = |s: &str| {
+        let s = s.trim();
+        s.strip_prefix('\'')
+            .and_then(|s| s.strip_suffix('\''))
+            .map(|s| s.replace("\\'", "'"))
+    };
+
+    match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(items) => items
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(quoted)
+            .collect(),
+        None => Some(vec![quoted(value).unwrap_or(value.to_string())]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let mut machine_file = MachineFile::default();
+        machine_file
+            .parse(
+                Path::new("native.ini"),
+                "# tools\n[constants]\nprefix = '/opt'\n\n[binaries]\n\
+                 python = '/usr/bin/python3'\nwrapper = ['/usr/bin/env', 'FOO=1', 'tool']\n\
+                 [properties]\nneeds_exe_wrapper = false\n",
+            )
+            .unwrap();
+        machine_file
+            .parse(
+                Path::new("override.ini"),
+                "[binaries]\npython = 'python3.11'\n",
+            )
+            .unwrap();
+
+        assert_eq!(machine_file.binary("python").unwrap(), ["python3.11"]);
+        assert_eq!(
+            machine_file.binary("wrapper").unwrap(),
+            ["/usr/bin/env", "FOO=1", "tool"]
+        );
+        assert_eq!(machine_file.binary("perl"), None);
+
+        for contents in [
+            "python = 'x'",
+            "[binaries]\npython",
+            "[binaries]\nx = ['a', b]",
+        ] {
+            assert!(
+                MachineFile::default()
+                    .parse(Path::new("bad.ini"), contents)
+                    .is_err(),
+                "{}",
+                contents
+            );
+        }
+    }
+}
@@ -1,7 +1,81 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
+use reson::{
+    backend::{self, Graph},
+    build_dir::{self, CheckCache, CmdLine, State},
+    info,
+    interpreter::{CallKind, Interpreter},
+    log::{self, Level},
+    test_runner::{self, RunOptions},
+    EnvLookup,
+};
 
-use std::path::PathBuf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Option values given on the command line
+#[derive(clap::Args)]
+struct OptionArgs {
+    /// Set the value of an option, can be used several times
+    #[arg(short = 'D', value_name = "option=value", value_parser = parse_option)]
+    options: Vec<(String, String)>,
+
+    /// Installation prefix
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Build type to use
+    #[arg(long)]
+    buildtype: Option<String>,
+
+    /// Default library type
+    #[arg(long)]
+    default_library: Option<String>,
+
+    /// Compiler warning level to use
+    #[arg(long)]
+    warnlevel: Option<String>,
+
+    /// Treat warnings as errors
+    #[arg(long)]
+    werror: bool,
+}
+
+fn parse_option(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("Option '{}' must be of the form name=value", s)),
+    }
+}
+
+impl OptionArgs {
+    /// All options as `(name, value)`, shorthand flags included
+    fn into_options(self) -> Result<Vec<(String, String)>> {
+        let mut options = self.options;
+
+        let shorthands = [
+            ("prefix", "prefix", self.prefix),
+            ("buildtype", "buildtype", self.buildtype),
+            ("default_library", "default-library", self.default_library),
+            ("warning_level", "warnlevel", self.warnlevel),
+            ("werror", "werror", self.werror.then(|| "true".to_string())),
+        ];
+        for (name, flag, value) in shorthands {
+            let Some(value) = value else {
+                continue;
+            };
+
+            if options.iter().any(|(n, _)| n == name) {
+                bail!("Got argument {} as both -D{} and --{}", name, name, flag);
+            }
+            options.push((name.to_string(), value));
+        }
+
+        Ok(options)
+    }
+}
 
 #[derive(Subcommand)]
 enum Commands {
@@ -9,25 +83,356 @@ enum Commands {
         #[arg(short = 'C')]
         source_dir: Option<PathBuf>,
         build_dir: PathBuf,
+
+        /// List every unsupported function and method the project uses
+        #[arg(long)]
+        report_unsupported: bool,
+
+        /// Remove an existing build directory's contents and configure it
+        /// again, keeping its command line options
+        #[arg(long)]
+        wipe: bool,
+
+        /// Configure an existing build directory again, keeping its command
+        /// line options
+        #[arg(long)]
+        reconfigure: bool,
+
+        /// Forget cached compiler check results
+        #[arg(long)]
+        clearcache: bool,
+
+        /// Native file describing the machine's tools, may be given more
+        /// than once with later files taking precedence
+        #[arg(long = "native-file")]
+        native_files: Vec<PathBuf>,
+
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Show or change the options of an existing build directory
+    Configure {
+        build_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: OptionArgs,
+    },
+    /// Build the targets built by default, or the given outputs
+    Build {
+        #[arg(short = 'C')]
+        build_dir: Option<PathBuf>,
+
+        /// Outputs to build, relative to the build directory
+        targets: Vec<PathBuf>,
+    },
+    /// Build what the project's tests use, then run them, or its benchmarks
+    Test {
+        #[arg(short = 'C')]
+        build_dir: Option<PathBuf>,
+
+        /// Only run the tests with these names
+        names: Vec<String>,
+
+        /// Only run tests in this suite, can be used several times
+        #[arg(long)]
+        suite: Vec<String>,
+
+        /// Skip tests in this suite, can be used several times
+        #[arg(long)]
+        no_suite: Vec<String>,
+
+        /// Run every test this many times
+        #[arg(long, default_value_t = 1)]
+        repeat: usize,
+
+        /// How many tests to run at once, defaults to MESON_TESTTHREADS or
+        /// the number of CPUs
+        #[arg(short = 'j', long)]
+        num_processes: Option<usize>,
+
+        /// Multiply every timeout by this, zero or less disables them
+        #[arg(short = 't', long, default_value_t = 1.0)]
+        timeout_multiplier: f64,
+
+        /// Run the benchmarks instead of the tests
+        #[arg(long)]
+        benchmark: bool,
     },
-    Build,
+}
+
+/// How much is printed, accepted by every subcommand
+#[derive(clap::Args)]
+struct LogArgs {
+    /// Also print debug messages, such as the commands run by checks
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print warnings and errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+}
+
+impl LogArgs {
+    fn level(&self) -> Level {
+        if self.verbose {
+            Level::Debug
+        } else if self.quiet {
+            Level::Warning
+        } else {
+            Level::Info
+        }
+    }
 }
 
 #[derive(Parser)]
 struct CliArgs {
     #[command(subcommand)]
     command: Commands,
+
+    #[command(flatten)]
+    log: LogArgs,
+}
+
+/// Interpret the project and record the result in the build directory
+fn configure(
+    cmd_line: &CmdLine,
+    build_dir: &Path,
+    report_unsupported: bool,
+) -> Result<Interpreter> {
+    open_log(build_dir)?;
+    // Commands run from the build directory, so paths in them can't be
+    // relative to where reson was started
+    let build_dir = &build_dir.canonicalize()?;
+    let mut interpreter = Interpreter::new(&cmd_line.source_dir, build_dir);
+    interpreter.set_report_unsupported(report_unsupported);
+    interpreter.set_option_overrides(cmd_line.options.clone());
+    interpreter.set_native_files(cmd_line.native_files.clone());
+    interpreter.interpret()?;
+
+    // Skipping unsupported calls leaves the configuration incomplete
+    if !interpreter.unsupported().is_empty() {
+        return Ok(interpreter);
+    }
+    cmd_line.write(build_dir)?;
+    State::new(interpreter.builder()).write(build_dir)?;
+    build_dir::write_tests(build_dir, &interpreter.builder().tests)?;
+    let graph = Graph::new(interpreter.builder()).map_err(anyhow::Error::msg)?;
+    build_dir::write_graph(build_dir, &graph)?;
+
+    Ok(interpreter)
+}
+
+/// Log everything from interpreting to the build directory's log file
+fn open_log(build_dir: &Path) -> Result<()> {
+    fs::create_dir_all(build_dir)?;
+    log::open_file(build_dir)?;
+    Ok(())
+}
+
+/// Configure the build directory again if anything it depends on changed
+fn regenerate_if_outdated(build_dir: &Path) -> Result<()> {
+    let state = State::read(build_dir).map_err(anyhow::Error::msg)?;
+    let cmd_line = CmdLine::read(build_dir).map_err(anyhow::Error::msg)?;
+
+    if let Some(reason) = state.outdated(&cmd_line.source_dir, build_dir, &EnvLookup::default()) {
+        info!("Regenerating build files, {}", reason);
+        configure(&cmd_line, build_dir, false)?;
+    }
+    Ok(())
+}
+
+fn current_dir_or(dir: Option<PathBuf>) -> Result<PathBuf> {
+    Ok(match dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?,
+    })
 }
 
-fn main() -> Result<()> {
+fn main() {
     let cli = CliArgs::parse();
+    log::set_level(cli.log.level());
 
-    match cli.command {
+    if let Err(e) = run(cli.command) {
+        reson::error!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(command: Commands) -> Result<()> {
+    match command {
         Commands::Setup {
             build_dir,
             source_dir,
-        } => todo!(),
-        Commands::Build => todo!(),
+            report_unsupported,
+            wipe,
+            reconfigure,
+            clearcache,
+            native_files,
+            options,
+        } => {
+            let source_dir = current_dir_or(source_dir)?.canonicalize()?;
+            let mut cmd_line = CmdLine::new(&source_dir, vec![]);
+
+            if build_dir.join(build_dir::PRIVATE_DIR).exists() {
+                if !wipe && !reconfigure {
+                    bail!(
+                        "Directory {} is already configured, use configure to change its \
+                         options, or setup --reconfigure or --wipe to configure it again",
+                        build_dir.display()
+                    );
+                }
+
+                if let Ok(old) = CmdLine::read(&build_dir) {
+                    cmd_line.options = old.options;
+                    cmd_line.native_files = old.native_files;
+                }
+                if wipe {
+                    for entry in fs::read_dir(&build_dir)? {
+                        let path = entry?.path();
+                        if path.is_dir() {
+                            fs::remove_dir_all(path)?;
+                        } else {
+                            fs::remove_file(path)?;
+                        }
+                    }
+                }
+            }
+            if clearcache {
+                CheckCache::clear(&build_dir)?;
+            }
+            cmd_line.merge(options.into_options()?);
+            // Native files given again replace the ones used before
+            if !native_files.is_empty() {
+                cmd_line.native_files = vec![];
+                for native_file in native_files {
+                    let Ok(path) = native_file.canonicalize() else {
+                        bail!("Native file {} does not exist", native_file.display());
+                    };
+                    cmd_line.native_files.push(path);
+                }
+            }
+
+            let interpreter = configure(&cmd_line, &build_dir, report_unsupported)?;
+            if let Some(summary) = interpreter.summary() {
+                info!("\n{}", summary.trim_end());
+            }
+
+            if report_unsupported {
+                let unsupported = interpreter.unsupported();
+                println!("Found {} unsupported calls", unsupported.len());
+                for call in unsupported {
+                    let name = match (call.kind, call.object) {
+                        (CallKind::Kwarg, Some(function)) => {
+                            format!("{} of {}()", call.name, function)
+                        }
+                        _ => format!("{}()", call.name),
+                    };
+                    println!(
+                        "{}:{}: {} {}",
+                        call.file.display(),
+                        call.span,
+                        call.kind,
+                        name
+                    );
+                }
+                if !unsupported.is_empty() {
+                    bail!("Build directory not configured, the project uses unsupported calls");
+                }
+            }
+        }
+        Commands::Configure { build_dir, options } => {
+            let build_dir = current_dir_or(build_dir)?;
+            let options = options.into_options()?;
+
+            let mut cmd_line = CmdLine::read(&build_dir).map_err(anyhow::Error::msg)?;
+            if options.is_empty() {
+                // Listing uses what the last configuration saved, with values
+                // set since then that it hasn't picked up yet
+                let mut saved = State::read(&build_dir).map_err(anyhow::Error::msg)?.options;
+                for (name, value) in &cmd_line.options {
+                    saved.set(name, value).map_err(anyhow::Error::msg)?;
+                }
+                print!("{}", saved);
+                return Ok(());
+            }
+            cmd_line.merge(options);
+
+            // Only the values are checked here, regenerating before the next
+            // build applies them
+            let saved = State::read(&build_dir).map_err(anyhow::Error::msg)?.options;
+            let mut interpreter = Interpreter::new(&cmd_line.source_dir, &build_dir);
+            interpreter.set_option_overrides(cmd_line.options.clone());
+            interpreter.check_option_overrides(&saved)?;
+
+            cmd_line.write(&build_dir)?;
+            build_dir::mark_regenerate(&build_dir)?;
+        }
+        Commands::Build { build_dir, targets } => {
+            let build_dir = current_dir_or(build_dir)?;
+            regenerate_if_outdated(&build_dir)?;
+
+            let graph = build_dir::read_graph(&build_dir).map_err(anyhow::Error::msg)?;
+            let targets = if targets.is_empty() {
+                graph.defaults.clone()
+            } else {
+                targets
+            };
+            backend::build(&graph, &build_dir, &targets).map_err(anyhow::Error::msg)?;
+        }
+        Commands::Test {
+            build_dir,
+            names,
+            suite,
+            no_suite,
+            repeat,
+            num_processes,
+            timeout_multiplier,
+            benchmark,
+        } => {
+            let build_dir = current_dir_or(build_dir)?;
+            regenerate_if_outdated(&build_dir)?;
+
+            let num_processes = num_processes
+                .or_else(|| std::env::var("MESON_TESTTHREADS").ok()?.parse().ok())
+                .or_else(|| std::thread::available_parallelism().ok().map(Into::into))
+                .unwrap_or(1);
+            let options = RunOptions {
+                benchmark,
+                names,
+                suites: suite,
+                no_suites: no_suite,
+                repeat,
+                // Benchmarks are timed, so they run one at a time
+                num_processes: if benchmark { 1 } else { num_processes.max(1) },
+                timeout_multiplier,
+            };
+
+            let tests = build_dir::read_tests(&build_dir).map_err(anyhow::Error::msg)?;
+            let tests = test_runner::select(&tests, &options);
+            if tests.is_empty() {
+                info!(
+                    "No {} defined",
+                    if benchmark { "benchmarks" } else { "tests" }
+                );
+                return Ok(());
+            }
+            let mut depends = vec![];
+            for test in &tests {
+                for depend in &test.depends {
+                    if !depends.contains(depend) {
+                        depends.push(depend.clone());
+                    }
+                }
+            }
+            let graph = build_dir::read_graph(&build_dir).map_err(anyhow::Error::msg)?;
+            backend::build(&graph, &build_dir, &depends).map_err(anyhow::Error::msg)?;
+
+            let results = test_runner::run(&tests, &build_dir, &options)?;
+            let failed = results.iter().filter(|r| r.outcome.is_failure()).count();
+            if failed > 0 {
+                bail!("{} of {} tests failed", failed, results.len());
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,637 @@
+use std::fmt::Display;
+
+/// Where an option comes from, used to group them when listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Builtin,
+    Base,
+    Compiler,
+    Project,
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Section::Builtin => write!(f, "Core options"),
+            Section::Base => write!(f, "Base options"),
+            Section::Compiler => write!(f, "Compiler options"),
+            Section::Project => write!(f, "Project options"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Enabled,
+    Disabled,
+    Auto,
+}
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Feature::Enabled => write!(f, "enabled"),
+            Feature::Disabled => write!(f, "disabled"),
+            Feature::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionType {
+    String,
+    Boolean,
+    Combo(Vec<String>),
+    Integer {
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    /// Restricted to `choices` when given
+    Array(Option<Vec<String>>),
+    Feature,
+}
+
+impl OptionType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptionType::String => "string",
+            OptionType::Boolean => "boolean",
+            OptionType::Combo(_) => "combo",
+            OptionType::Integer { .. } => "integer",
+            OptionType::Array(_) => "array",
+            OptionType::Feature => "feature",
+        }
+    }
+
+    /// Allowed values, as listed by `configure`
+    pub fn choices(&self) -> String {
+        match self {
+            OptionType::String => String::new(),
+            OptionType::Boolean => "[true, false]".to_string(),
+            OptionType::Combo(choices) | OptionType::Array(Some(choices)) => {
+                format!("[{}]", choices.join(", "))
+            }
+            OptionType::Integer { min, max } => {
+                let bound = |b: &Option<i64>| b.map(|b| b.to_string()).unwrap_or_default();
+                format!("{}..{}", bound(min), bound(max))
+            }
+            OptionType::Array(None) => String::new(),
+            OptionType::Feature => "[enabled, disabled, auto]".to_string(),
+        }
+    }
+
+    /// Parse a value given as a string, e.g. from the command line
+    pub fn parse(&self, name: &str, value: &str) -> Result<OptionValue, String> {
+        let invalid = || {
+            format!(
+                "Value '{}' for {} option '{}' is invalid",
+                value,
+                self.name(),
+                name
+            )
+        };
+
+        let parsed = match self {
+            OptionType::String => OptionValue::String(value.to_string()),
+            OptionType::Boolean => match value.to_lowercase().as_str() {
+                "true" => OptionValue::Boolean(true),
+                "false" => OptionValue::Boolean(false),
+                _ => return Err(invalid()),
+            },
+            OptionType::Combo(_) => OptionValue::String(value.to_string()),
+            OptionType::Integer { .. } => {
+                OptionValue::Integer(value.parse().map_err(|_| invalid())?)
+            }
+            OptionType::Array(_) => {
+                let value = value.trim();
+                let items =
+                    if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                        list.split(',')
+                            .map(|i| i.trim().trim_matches('\'').to_string())
+                            .filter(|i| !i.is_empty())
+                            .collect()
+                    } else if value.is_empty() {
+                        vec![]
+                    } else {
+                        value.split(',').map(|i| i.trim().to_string()).collect()
+                    };
+                OptionValue::Array(items)
+            }
+            OptionType::Feature => OptionValue::Feature(match value {
+                "enabled" => Feature::Enabled,
+                "disabled" => Feature::Disabled,
+                "auto" => Feature::Auto,
+                _ => return Err(invalid()),
+            }),
+        };
+
+        self.validate(name, parsed)
+    }
+
+    /// Check a value is of the right type and within the allowed range
+    pub fn validate(&self, name: &str, value: OptionValue) -> Result<OptionValue, String> {
+        let invalid = |reason: String| {
+            Err(format!(
+                "Value '{}' for {} option '{}' is invalid{}",
+                value,
+                self.name(),
+                name,
+                reason
+            ))
+        };
+
+        match (self, &value) {
+            (OptionType::String, OptionValue::String(_))
+            | (OptionType::Boolean, OptionValue::Boolean(_))
+            | (OptionType::Feature, OptionValue::Feature(_)) => Ok(value),
+            (OptionType::Combo(choices), OptionValue::String(s)) => {
+                if choices.contains(s) {
+                    Ok(value)
+                } else {
+                    invalid(format!(", possible choices are: {}", choices.join(", ")))
+                }
+            }
+            (OptionType::Integer { min, max }, OptionValue::Integer(i)) => {
+                if min.is_some_and(|min| *i < min) {
+                    invalid(format!(", must be at least {}", min.unwrap()))
+                } else if max.is_some_and(|max| *i > max) {
+                    invalid(format!(", must be at most {}", max.unwrap()))
+                } else {
+                    Ok(value)
+                }
+            }
+            (OptionType::Array(choices), OptionValue::Array(items)) => {
+                match (
+                    choices,
+                    items
+                        .iter()
+                        .find(|i| choices.as_ref().is_some_and(|c| !c.contains(i))),
+                ) {
+                    (Some(choices), Some(item)) => invalid(format!(
+                        ", '{}' is not one of the choices: {}",
+                        item,
+                        choices.join(", ")
+                    )),
+                    _ => Ok(value),
+                }
+            }
+            _ => invalid(String::new()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    String(String),
+    Boolean(bool),
+    Integer(i64),
+    Array(Vec<String>),
+    Feature(Feature),
+}
+
+impl Display for OptionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionValue::String(s) => write!(f, "{}", s),
+            OptionValue::Boolean(b) => write!(f, "{}", b),
+            OptionValue::Integer(i) => write!(f, "{}", i),
+            OptionValue::Array(items) => write!(f, "[{}]", items.join(", ")),
+            OptionValue::Feature(feature) => write!(f, "{}", feature),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildOption {
+    pub name: String,
+    pub description: String,
+    pub section: Section,
+    pub ty: OptionType,
+    pub default: OptionValue,
+    pub value: OptionValue,
+}
+
+impl BuildOption {
+    pub fn new(
+        name: &str,
+        description: &str,
+        section: Section,
+        ty: OptionType,
+        default: OptionValue,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            section,
+            ty,
+            value: default.clone(),
+            default,
+        }
+    }
+}
+
+/// Every option known to a project, built-in ones included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    options: Vec<BuildOption>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn combo(choices: &[&str]) -> OptionType {
+    OptionType::Combo(choices.iter().map(|c| c.to_string()).collect())
+}
+
+fn string(s: &str) -> OptionValue {
+    OptionValue::String(s.to_string())
+}
+
+impl Options {
+    pub fn builtin() -> Self {
+        use OptionType::Boolean;
+        use OptionValue::Boolean as Bool;
+        use Section::{Base, Builtin};
+
+        let dirs = [
+            ("prefix", "Installation prefix", "/usr/local"),
+            ("bindir", "Executable directory", "bin"),
+            ("libdir", "Library directory", "lib"),
+            ("libexecdir", "Library executable directory", "libexec"),
+            ("includedir", "Header file directory", "include"),
+            ("datadir", "Data file directory", "share"),
+            ("mandir", "Manual page directory", "share/man"),
+            ("sysconfdir", "Sysconf data directory", "etc"),
+            ("localstatedir", "Localstate data directory", "var"),
+        ];
+
+        let mut options = dirs
+            .iter()
+            .map(|(name, description, default)| {
+                BuildOption::new(
+                    name,
+                    description,
+                    Builtin,
+                    OptionType::String,
+                    string(default),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        options.extend([
+            BuildOption::new(
+                "buildtype",
+                "Build type to use",
+                Builtin,
+                combo(&[
+                    "plain",
+                    "debug",
+                    "debugoptimized",
+                    "release",
+                    "minsize",
+                    "custom",
+                ]),
+                string("debug"),
+            ),
+            BuildOption::new(
+                "debug",
+                "Enable debug symbols and other information",
+                Builtin,
+                Boolean,
+                Bool(true),
+            ),
+            BuildOption::new(
+                "optimization",
+                "Optimization level",
+                Builtin,
+                combo(&["plain", "0", "g", "1", "2", "3", "s"]),
+                string("0"),
+            ),
+            BuildOption::new(
+                "warning_level",
+                "Compiler warning level to use",
+                Builtin,
+                combo(&["0", "1", "2", "3", "everything"]),
+                string("1"),
+            ),
+            BuildOption::new(
+                "werror",
+                "Treat warnings as errors",
+                Builtin,
+                Boolean,
+                Bool(false),
+            ),
+            BuildOption::new(
+                "default_library",
+                "Default library type",
+                Builtin,
+                combo(&["shared", "static", "both"]),
+                string("shared"),
+            ),
+            BuildOption::new(
+                "default_both_libraries",
+                "Default library type for both_libraries",
+                Builtin,
+                combo(&["shared", "static", "auto"]),
+                string("shared"),
+            ),
+            BuildOption::new(
+                "b_ndebug",
+                "Disable asserts",
+                Base,
+                combo(&["true", "false", "if-release"]),
+                string("false"),
+            ),
+            BuildOption::new(
+                "b_pie",
+                "Build executables as position independent",
+                Base,
+                Boolean,
+                Bool(false),
+            ),
+            BuildOption::new(
+                "b_staticpic",
+                "Build static libraries as position independent",
+                Base,
+                Boolean,
+                Bool(true),
+            ),
+            BuildOption::new(
+                "b_lto",
+                "Use link time optimization",
+                Base,
+                Boolean,
+                Bool(false),
+            ),
+            BuildOption::new(
+                "b_sanitize",
+                "Code sanitizer to use",
+                Base,
+                combo(&[
+                    "none",
+                    "address",
+                    "thread",
+                    "undefined",
+                    "memory",
+                    "leak",
+                    "address,undefined",
+                ]),
+                string("none"),
+            ),
+            BuildOption::new(
+                "b_colorout",
+                "Use colored output",
+                Base,
+                combo(&["auto", "always", "never"]),
+                string("always"),
+            ),
+            BuildOption::new(
+                "b_lundef",
+                "Use -Wl,--no-undefined when linking",
+                Base,
+                Boolean,
+                Bool(true),
+            ),
+            BuildOption::new(
+                "b_asneeded",
+                "Use -Wl,--as-needed when linking",
+                Base,
+                Boolean,
+                Bool(true),
+            ),
+        ]);
+
+        Self { options }
+    }
+
+    /// Exactly `options`, as saved in a build directory
+    pub fn from_saved(options: Vec<BuildOption>) -> Self {
+        Self { options }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BuildOption> {
+        self.options.iter().find(|o| o.name == name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&OptionValue> {
+        self.get(name).map(|o| &o.value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &BuildOption> {
+        self.options.iter()
+    }
+
+    /// Add a project option, names can't shadow existing options
+    pub fn add(&mut self, option: BuildOption) -> Result<(), String> {
+        if self.get(&option.name).is_some() {
+            return Err(format!("Option name {} is reserved", option.name));
+        }
+
+        self.options.push(option);
+        Ok(())
+    }
+
+    /// Set an option from its string form, as in `name=value`
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let option = self
+            .get(name)
+            .ok_or_else(|| format!("Unknown option: \"{}\"", name))?;
+        let value = option.ty.parse(name, value)?;
+
+        self.set_value(name, value)
+    }
+
+    pub fn set_value(&mut self, name: &str, value: OptionValue) -> Result<(), String> {
+        let option = self
+            .options
+            .iter_mut()
+            .find(|o| o.name == name)
+            .ok_or_else(|| format!("Unknown option: \"{}\"", name))?;
+        option.value = option.ty.validate(name, value)?;
+
+        // buildtype is shorthand for a debug and optimization pair
+        if name == "buildtype" {
+            let (debug, optimization) = match option.value.to_string().as_str() {
+                "plain" => (false, "plain"),
+                "debug" => (true, "0"),
+                "debugoptimized" => (true, "2"),
+                "release" => (false, "3"),
+                "minsize" => (true, "s"),
+                _ => return Ok(()),
+            };
+            self.set_value("debug", OptionValue::Boolean(debug))?;
+            self.set_value("optimization", string(optimization))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Options grouped by section as a table of name, value, default, choices
+/// and description
+impl Display for Options {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let header = [
+            "Option",
+            "Current value",
+            "Default",
+            "Possible values",
+            "Description",
+        ];
+        let rows = self
+            .options
+            .iter()
+            .map(|o| {
+                [
+                    o.name.clone(),
+                    o.value.to_string(),
+                    o.default.to_string(),
+                    o.ty.choices(),
+                    o.description.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let write_row = |f: &mut std::fmt::Formatter<'_>, row: &[&str]| {
+            let cells = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell))
+                .collect::<Vec<_>>();
+            writeln!(f, "  {}", cells.join("  ").trim_end())
+        };
+
+        let mut sections = self.options.iter().map(|o| o.section).collect::<Vec<_>>();
+        sections.sort();
+        sections.dedup();
+        for (i, section) in sections.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}:", section)?;
+            write_row(f, &header)?;
+            for (option, row) in self.options.iter().zip(&rows) {
+                if option.section == section {
+                    write_row(f, &row.each_ref().map(String::as_str))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let mut options = Options::builtin();
+        options
+            .add(BuildOption::new(
+                "docs",
+                "",
+                Section::Project,
+                OptionType::Boolean,
+                OptionValue::Boolean(false),
+            ))
+            .unwrap();
+        options
+            .add(BuildOption::new(
+                "jobs",
+                "",
+                Section::Project,
+                OptionType::Integer {
+                    min: Some(1),
+                    max: Some(8),
+                },
+                OptionValue::Integer(1),
+            ))
+            .unwrap();
+        options
+            .add(BuildOption::new(
+                "backends",
+                "",
+                Section::Project,
+                OptionType::Array(Some(vec!["x11".to_string(), "wayland".to_string()])),
+                OptionValue::Array(vec![]),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            options.set("docs", "maybe").unwrap_err(),
+            "Value 'maybe' for boolean option 'docs' is invalid"
+        );
+        options.set("docs", "true").unwrap();
+        assert_eq!(options.value("docs"), Some(&OptionValue::Boolean(true)));
+
+        assert_eq!(
+            options.set("jobs", "9").unwrap_err(),
+            "Value '9' for integer option 'jobs' is invalid, must be at most 8"
+        );
+
+        options.set("backends", "['x11', 'wayland']").unwrap();
+        assert_eq!(
+            options.value("backends"),
+            Some(&OptionValue::Array(vec![
+                "x11".to_string(),
+                "wayland".to_string()
+            ]))
+        );
+        assert!(options.set("backends", "x11,cocoa").is_err());
+
+        options.set("buildtype", "release").unwrap();
+        assert_eq!(options.value("debug"), Some(&OptionValue::Boolean(false)));
+        assert_eq!(options.value("optimization"), Some(&string("3")));
+
+        assert!(options.set("nope", "1").is_err());
+        assert!(options
+            .add(BuildOption::new(
+                "prefix",
+                "",
+                Section::Project,
+                OptionType::String,
+                string("")
+            ))
+            .is_err());
+    }
+
+    #[test]
+    fn table() {
+        let mut options = Options::builtin();
+        options
+            .add(BuildOption::new(
+                "jobs",
+                "Parallel jobs",
+                Section::Project,
+                OptionType::Integer {
+                    min: Some(1),
+                    max: None,
+                },
+                OptionValue::Integer(1),
+            ))
+            .unwrap();
+        options.set("jobs", "4").unwrap();
+
+        let table = options.to_string();
+        assert!(table.starts_with("Core options:\n  Option "));
+        assert!(table.contains("\nBase options:\n"));
+        assert!(table.contains("\nProject options:\n"));
+
+        let jobs = table.lines().find(|l| l.contains("jobs")).unwrap();
+        assert_eq!(
+            jobs.split_whitespace().collect::<Vec<_>>(),
+            ["jobs", "4", "1", "1..", "Parallel", "jobs"]
+        );
+    }
+}
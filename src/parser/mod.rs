@@ -1,11 +1,11 @@
 mod tokens;
 
 use crate::interpreter::ast::{
-    Arguments, Arithmetic, Assignment, CompareOp, Comparison, Function, If, IfClause, MathOp, Node,
-    Program,
+    Arguments, Arithmetic, Assignment, CompareOp, Comparison, Function, If, IfClause, MathOp,
+    MethodCall, Node, Program, Span,
 };
 use logos::{Lexer, Logos};
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf};
+use std::{fs::read_to_string, path::PathBuf};
 use thiserror::Error;
 use tokens::Token;
 
@@ -17,6 +17,26 @@ pub enum Error {
     ReadError(PathBuf),
     #[error("IO Error")]
     Io(#[from] std::io::Error),
+    #[error("Keyword argument {0} given multiple times")]
+    DuplicateKwarg(String),
+    #[error("Positional argument after keyword argument {0}")]
+    PositionalAfterKwarg(String),
+    #[error("Keyword argument name must be an identifier, got {0:?}")]
+    InvalidKwarg(Node),
+    #[error("{span}: {error}")]
+    At { span: Span, error: Box<Error> },
+}
+
+impl Error {
+    fn at(self, span: Span) -> Self {
+        match self {
+            Error::At { .. } => self,
+            error => Error::At {
+                span,
+                error: Box::new(error),
+            },
+        }
+    }
 }
 
 pub fn parse_file(path: &PathBuf) -> Result<Program, Error> {
@@ -24,9 +44,26 @@ pub fn parse_file(path: &PathBuf) -> Result<Program, Error> {
     parse(&content)
 }
 
+/// Line and column of a byte offset into `source`
+fn span_at(source: &str, offset: usize) -> Span {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    Span {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
 struct Parser<'source> {
+    source: &'source str,
     lexer: Lexer<'source, Token>,
     current: Token,
+    // Byte offset of the current token
+    offset: usize,
+    // Nesting depth of parentheses and brackets, newlines inside them are
+    // not statement terminators
+    depth: usize,
 }
 
 impl<'source> Parser<'source> {
@@ -34,17 +71,25 @@ impl<'source> Parser<'source> {
         let mut l = Token::lexer(input);
         let t = match l.next().unwrap_or(Ok(Token::EOF)) {
             Ok(t) => Ok(t),
-            Err(_) => Err(Error::LexError(l.slice().to_string())),
+            Err(_) => {
+                Err(Error::LexError(l.slice().to_string()).at(span_at(input, l.span().start)))
+            }
         }?;
 
-        Ok(Self {
+        let mut parser = Self {
+            source: input,
+            offset: l.span().start,
             lexer: l,
             current: t,
-        })
+            depth: 0,
+        };
+        parser.track_depth();
+
+        Ok(parser)
     }
 
     pub fn accept(&mut self, tok: &Token) -> Result<bool, Error> {
-        if std::mem::discriminant(&self.current) == std::mem::discriminant(&tok) {
+        if std::mem::discriminant(&self.current) == std::mem::discriminant(tok) {
             self.advance()?;
             return Ok(true);
         }
@@ -71,23 +116,48 @@ impl<'source> Parser<'source> {
     }
 
     fn advance(&mut self) -> Result<(), Error> {
-        self.current = match self.lexer.next().unwrap_or(Ok(Token::EOF)) {
-            Ok(t) => Ok(t),
-            Err(_) => Err(Error::LexError(self.lexer.slice().to_string())),
-        }?;
+        loop {
+            let next = self.lexer.next().unwrap_or(Ok(Token::EOF));
+            // Set first so a lex error is reported where it happened
+            self.offset = self.lexer.span().start;
+            self.current = match next {
+                Ok(t) => Ok(t),
+                Err(_) => Err(Error::LexError(self.lexer.slice().to_string())),
+            }?;
+
+            if !(self.depth > 0 && self.current == Token::EOL) {
+                break;
+            }
+        }
+        self.track_depth();
+
         Ok(())
     }
 
+    fn track_depth(&mut self) {
+        match self.current {
+            Token::LParen | Token::LBracket | Token::LBrace => self.depth += 1,
+            Token::RParen | Token::RBracket | Token::RBrace => {
+                self.depth = self.depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+    }
+
     fn curr(&self) -> Token {
         self.current.clone()
     }
 
+    fn span(&self) -> Span {
+        span_at(self.source, self.offset)
+    }
+
     // fn peek(&self) -> Token {
     //     self.peeked.clone()
     // }
 
     fn statement(&mut self) -> Result<Node, Error> {
-        return self.e1();
+        self.e1()
     }
 
     // Recursive descent parser for Meson's definition language.
@@ -107,6 +177,7 @@ impl<'source> Parser<'source> {
 
     // Assignment
     fn e1(&mut self) -> Result<Node, Error> {
+        let span = self.span();
         let left = self.e2()?;
 
         if self.accept(&Token::PlusAssign)? {
@@ -116,6 +187,7 @@ impl<'source> Parser<'source> {
             return Ok(Node::Assignment(Assignment {
                 left: Box::new(left),
                 right: Box::new(value),
+                span,
             }));
         }
 
@@ -138,6 +210,7 @@ impl<'source> Parser<'source> {
 
     // comparison
     fn e4(&mut self) -> Result<Node, Error> {
+        let span = self.span();
         let left = self.e5()?;
 
         let comparison_ops = [Token::Equal];
@@ -153,6 +226,7 @@ impl<'source> Parser<'source> {
                     left: Box::new(left),
                     op,
                     right: Box::new(self.e5()?),
+                    span,
                 }));
             }
         }
@@ -168,6 +242,7 @@ impl<'source> Parser<'source> {
     }
 
     fn e5addsub(&mut self) -> Result<Node, Error> {
+        let span = self.span();
         let mut left = self.e5muldiv()?;
 
         loop {
@@ -184,6 +259,7 @@ impl<'source> Parser<'source> {
                     left: Box::new(left),
                     right: Box::new(self.e5muldiv()?),
                     op: operator,
+                    span,
                 });
             } else {
                 break;
@@ -205,23 +281,43 @@ impl<'source> Parser<'source> {
 
     // function all, method call
     fn e7(&mut self) -> Result<Node, Error> {
-        let left = self.e8()?;
+        let span = self.span();
+        let mut left = self.e8()?;
 
         if self.accept(&Token::LParen)? {
             // Get ident
             // TODO throw if left isn't an IdentNode
             if let Node::Identifier(ident) = left {
-                let func = Node::Function(Function {
+                left = Node::Function(Function {
                     name: ident,
                     args: self.args()?,
+                    span,
                 });
                 self.expect(Token::RParen)?;
-                return Ok(func);
             } else {
                 // TODO error
             }
         }
 
+        while self.accept(&Token::Period)? {
+            let span = self.span();
+            let name = match self.curr() {
+                Token::Identifier(name) => name,
+                // TODO error
+                _ => break,
+            };
+            self.advance()?;
+
+            self.expect(Token::LParen)?;
+            left = Node::MethodCall(MethodCall {
+                object: Box::new(left),
+                name,
+                args: self.args()?,
+                span,
+            });
+            self.expect(Token::RParen)?;
+        }
+
         Ok(left)
     }
 
@@ -255,35 +351,74 @@ impl<'source> Parser<'source> {
             if let Token::StringLiteral(str) = tok {
                 return Ok(Node::String(str));
             }
+        } else if self.accept(&Token::LBracket)? {
+            let mut elements = vec![];
+            loop {
+                let element = self.statement()?;
+                if element == Node::None {
+                    break;
+                }
+                elements.push(element);
+
+                if !self.accept(&Token::Comma)? {
+                    break;
+                }
+            }
+            self.expect(Token::RBracket)?;
+
+            return Ok(Node::Array(elements));
+        } else if self.accept(&Token::LBrace)? {
+            let mut entries = vec![];
+            loop {
+                let key = self.statement()?;
+                if key == Node::None {
+                    break;
+                }
+                self.expect(Token::Colon)?;
+                entries.push((key, self.statement()?));
+
+                if !self.accept(&Token::Comma)? {
+                    break;
+                }
+            }
+            self.expect(Token::RBrace)?;
+
+            return Ok(Node::Dict(entries));
         }
 
         Ok(Node::None)
     }
 
     fn args(&mut self) -> Result<Arguments, Error> {
-        let mut s = self.statement()?;
         let mut args = Arguments {
             args: vec![],
-            kwargs: HashMap::new(),
+            kwargs: vec![],
         };
 
         loop {
+            let span = self.span();
+            let s = self.statement()?;
             if let Node::None = s {
                 break;
             }
 
-            if self.accept(&Token::Comma)? {
-                args.args.push(s);
-            } else if self.accept(&Token::Colon)? {
-                if let Node::Identifier(ident) = s {
-                    args.kwargs.insert(ident, self.statement()?);
+            if self.accept(&Token::Colon)? {
+                let Node::Identifier(ident) = s else {
+                    return Err(Error::InvalidKwarg(s).at(span));
+                };
+                if args.kwargs.iter().any(|(k, _)| *k == ident) {
+                    return Err(Error::DuplicateKwarg(ident).at(span));
                 }
+                args.kwargs.push((ident, self.statement()?));
+            } else if let Some((last, _)) = args.kwargs.last() {
+                return Err(Error::PositionalAfterKwarg(last.clone()).at(span));
             } else {
                 args.args.push(s);
-                return Ok(args);
             }
 
-            s = self.statement()?;
+            if !self.accept(&Token::Comma)? {
+                break;
+            }
         }
 
         Ok(args)
@@ -291,17 +426,22 @@ impl<'source> Parser<'source> {
 
     fn ifblock(&mut self) -> Result<IfClause, Error> {
         // let if_node = If {};
+        let span = self.span();
         let condition = self.statement()?;
         let mut clause = IfClause { ifs: vec![] };
         self.expect(Token::EOL)?;
 
         let block = self.code_block()?;
 
-        clause.ifs.push(If { condition, block });
+        clause.ifs.push(If {
+            condition,
+            block,
+            span,
+        });
 
         // Elseif blocks
 
-        return Ok(clause);
+        Ok(clause)
     }
 
     fn line(&mut self) -> Result<Node, Error> {
@@ -338,7 +478,7 @@ impl<'source> Parser<'source> {
 pub fn parse(input: &str) -> Result<Program, Error> {
     let mut parser = Parser::new(input)?;
 
-    let block = parser.code_block()?;
+    let block = parser.code_block().map_err(|e| e.at(parser.span()))?;
     let mut prog = Program { nodes: vec![] };
     if let Node::Codeblock(nodes) = block {
         prog.nodes = nodes;
@@ -371,6 +511,7 @@ mod tests {
                     left: Box::new(Node::Number(1)),
                     right: Box::new(Node::Number(2)),
                     op: MathOp::Add,
+                    span: Span { line: 1, column: 1 },
                 })],
             },
             Test {
@@ -381,9 +522,11 @@ mod tests {
                         name: "dependency".to_string(),
                         args: Arguments {
                             args: vec![],
-                            kwargs: HashMap::new(),
+                            kwargs: vec![],
                         },
+                        span: Span { line: 1, column: 9 },
                     })),
+                    span: Span { line: 1, column: 1 },
                 })],
             },
             Test {
@@ -397,13 +540,25 @@ mod tests {
                                 name: "get_option".to_string(),
                                 args: Arguments {
                                     args: vec![Node::String("buildtype".to_string())],
-                                    kwargs: HashMap::new(),
+                                    kwargs: vec![],
+                                },
+                                span: Span {
+                                    line: 2,
+                                    column: 20,
                                 },
                             })),
                             op: CompareOp::Equal,
                             right: Box::new(Node::String("debug".to_string())),
+                            span: Span {
+                                line: 2,
+                                column: 20,
+                            },
                         }),
                         block: Node::Codeblock(vec![]),
+                        span: Span {
+                            line: 2,
+                            column: 20,
+                        },
                     }],
                 })],
             },
@@ -416,11 +571,95 @@ mod tests {
                             Node::String("hello world".to_string()),
                             Node::String("cpp".to_string()),
                         ],
-                        kwargs: HashMap::from([(
-                            "version".to_string(),
-                            Node::String("0.1.0".to_string()),
-                        )]),
+                        kwargs: vec![("version".to_string(), Node::String("0.1.0".to_string()))],
+                    },
+                    span: Span { line: 1, column: 1 },
+                })],
+            },
+            Test {
+                input: "lib = library('foo',\n  ['a.c', 'b.c'], # sources\n)\ntrue",
+                expected: vec![
+                    Node::Assignment(Assignment {
+                        left: Box::new(Node::Identifier("lib".to_string())),
+                        right: Box::new(Node::Function(Function {
+                            name: "library".to_string(),
+                            args: Arguments {
+                                args: vec![
+                                    Node::String("foo".to_string()),
+                                    Node::Array(vec![
+                                        Node::String("a.c".to_string()),
+                                        Node::String("b.c".to_string()),
+                                    ]),
+                                ],
+                                kwargs: vec![],
+                            },
+                            span: Span { line: 1, column: 7 },
+                        })),
+                        span: Span { line: 1, column: 1 },
+                    }),
+                    Node::Boolean(true),
+                ],
+            },
+            Test {
+                input: "cc = meson.get_compiler('c')\ncc.get_id().strip()",
+                expected: vec![
+                    Node::Assignment(Assignment {
+                        left: Box::new(Node::Identifier("cc".to_string())),
+                        right: Box::new(Node::MethodCall(MethodCall {
+                            object: Box::new(Node::Identifier("meson".to_string())),
+                            name: "get_compiler".to_string(),
+                            args: Arguments {
+                                args: vec![Node::String("c".to_string())],
+                                kwargs: vec![],
+                            },
+                            span: Span {
+                                line: 1,
+                                column: 12,
+                            },
+                        })),
+                        span: Span { line: 1, column: 1 },
+                    }),
+                    Node::MethodCall(MethodCall {
+                        object: Box::new(Node::MethodCall(MethodCall {
+                            object: Box::new(Node::Identifier("cc".to_string())),
+                            name: "get_id".to_string(),
+                            args: Arguments {
+                                args: vec![],
+                                kwargs: vec![],
+                            },
+                            span: Span { line: 2, column: 4 },
+                        })),
+                        name: "strip".to_string(),
+                        args: Arguments {
+                            args: vec![],
+                            kwargs: vec![],
+                        },
+                        span: Span {
+                            line: 2,
+                            column: 13,
+                        },
+                    }),
+                ],
+            },
+            Test {
+                input: "f(b: 1, a: {'x': true}, kwargs: opts)",
+                expected: vec![Node::Function(Function {
+                    name: "f".to_string(),
+                    args: Arguments {
+                        args: vec![],
+                        kwargs: vec![
+                            ("b".to_string(), Node::Number(1)),
+                            (
+                                "a".to_string(),
+                                Node::Dict(vec![(
+                                    Node::String("x".to_string()),
+                                    Node::Boolean(true),
+                                )]),
+                            ),
+                            ("kwargs".to_string(), Node::Identifier("opts".to_string())),
+                        ],
                     },
+                    span: Span { line: 1, column: 1 },
                 })],
             },
             Test {
@@ -452,4 +691,35 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn invalid_arguments() {
+        let error = |input| match parse(input) {
+            Err(Error::At { error, .. }) => *error,
+            result => panic!("{:?}", result),
+        };
+        assert!(matches!(
+            error("f(a: 1, a: 2)"),
+            Error::DuplicateKwarg(k) if k == "a"
+        ));
+        assert!(matches!(
+            error("f(a: 1, 'b')"),
+            Error::PositionalAfterKwarg(k) if k == "a"
+        ));
+        assert!(matches!(error("f('a': 1)"), Error::InvalidKwarg(_)));
+    }
+
+    #[test]
+    fn error_spans() {
+        for (input, line, column) in [
+            ("x = 1\ny = $", 2, 5),
+            ("$", 1, 1),
+            ("a = 1\n\nf(a: 1, a: 2)", 3, 9),
+        ] {
+            match parse(input) {
+                Err(Error::At { span, .. }) => assert_eq!(span, Span { line, column }, "{}", input),
+                result => panic!("{}: {:?}", input, result),
+            }
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use logos::Logos;
 
 #[derive(Logos, Clone, Debug, PartialEq, Eq)]
-#[logos(skip r"([ \t\f]+)|(#[^\n]*\n?)")]
+#[allow(clippy::upper_case_acronyms)]
+#[logos(skip r"([ \t\f]+)|(#[^\n]*)")]
 pub enum Token {
     #[token("true")]
     True,
@@ -12,6 +13,14 @@ pub enum Token {
     LParen,
     #[token(")")]
     RParen,
+    #[token("[")]
+    LBracket,
+    #[token("]")]
+    RBracket,
+    #[token("{")]
+    LBrace,
+    #[token("}")]
+    RBrace,
 
     #[token("if")]
     If,
@@ -41,7 +50,7 @@ pub enum Token {
     #[regex("[0-9]+", |lex| lex.slice().parse::<i64>().unwrap())]
     NumberLiteral(i64),
 
-    #[regex("[a-zA-Z_]+", |lex| lex.slice().to_owned())]
+    #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_owned())]
     Identifier(String),
 
     #[token("\n")]
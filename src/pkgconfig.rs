@@ -0,0 +1,405 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::version;
+
+/// Where `.pc` files are looked for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PkgConfig {
+    search_path: Vec<PathBuf>,
+    /// Directories the compiler searches anyway, left out of the arguments
+    system_dirs: Vec<String>,
+}
+
+/// A package with its requirements resolved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    pub cflags: Vec<String>,
+    pub libs: Vec<String>,
+    /// Variables of the package's own `.pc` file, expanded
+    pub variables: Vec<(String, String)>,
+}
+
+/// A `.pc` file with its variables expanded
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct PcFile {
+    variables: Vec<(String, String)>,
+    version: String,
+    cflags: Vec<String>,
+    libs: Vec<String>,
+    libs_private: Vec<String>,
+    requires: Vec<(String, Option<String>)>,
+    requires_private: Vec<(String, Option<String>)>,
+}
+
+impl PkgConfig {
+    /// Search `PKG_CONFIG_PATH` first, then `PKG_CONFIG_LIBDIR` or the
+    /// default directories for `target` when it isn't set
+    pub fn new(path: Option<String>, libdir: Option<String>, target: Option<&str>) -> Self {
+        let split = |dirs: &str| std::env::split_paths(dirs).collect::<Vec<_>>();
+
+        let mut search_path = path.as_deref().map(split).unwrap_or_default();
+        match libdir {
+            Some(libdir) => search_path.extend(split(&libdir)),
+            None => {
+                for prefix in ["/usr/local", "/usr"] {
+                    if let Some(target) = target {
+                        search_path.push(format!("{}/lib/{}/pkgconfig", prefix, target).into());
+                    }
+                    search_path.push(format!("{}/lib/pkgconfig", prefix).into());
+                    search_path.push(format!("{}/share/pkgconfig", prefix).into());
+                }
+            }
+        }
+
+        let mut system_dirs = vec!["-I/usr/include".to_string()];
+        for dir in ["/usr/lib", "/lib"] {
+            system_dirs.push(format!("-L{}", dir));
+            if let Some(target) = target {
+                system_dirs.push(format!("-L{}/{}", dir, target));
+            }
+        }
+
+        Self {
+            search_path,
+            system_dirs,
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        self.search_path
+            .iter()
+            .map(|dir| dir.join(format!("{}.pc", name)))
+            .find(|path| path.is_file())
+    }
+
+    /// Load a package and everything it requires, `Libs.private` and the
+    /// libraries of private requirements are only included when `static`
+    pub fn load(&self, name: &str, r#static: bool) -> Result<Option<Package>, String> {
+        self.load_required(name, r#static, &mut vec![])
+    }
+
+    /// Load a package on behalf of the chain of packages in `visiting`
+    fn load_required(
+        &self,
+        name: &str,
+        r#static: bool,
+        visiting: &mut Vec<String>,
+    ) -> Result<Option<Package>, String> {
+        let Some(path) = self.find(name) else {
+            return Ok(None);
+        };
+        let pc = parse(&path)?;
+
+        let mut package = Package {
+            name: name.to_string(),
+            version: pc.version.clone(),
+            cflags: pc.cflags.clone(),
+            libs: pc.libs.clone(),
+            variables: pc.variables.clone(),
+        };
+        if r#static {
+            package.libs.extend(pc.libs_private.clone());
+        }
+
+        let requires = pc.requires.iter().map(|r| (r, true));
+        let private = pc.requires_private.iter().map(|r| (r, r#static));
+        visiting.push(name.to_string());
+        for ((required, constraint), libs) in requires.chain(private) {
+            if visiting.contains(required) {
+                return Err(format!(
+                    "Package '{}' requires itself through '{}'",
+                    required, name
+                ));
+            }
+            let dep = self
+                .load_required(required, r#static, visiting)?
+                .ok_or_else(|| {
+                    format!("Package '{}', required by '{}', not found", required, name)
+                })?;
+            if let Some(constraint) = constraint {
+                if !version::matches(&dep.version, constraint) {
+                    return Err(format!(
+                        "Package '{}' requires '{} {}' but version of {} is {}",
+                        name, required, constraint, required, dep.version
+                    ));
+                }
+            }
+
+            package.cflags.extend(dep.cflags);
+            if libs {
+                package.libs.extend(dep.libs);
+            }
+        }
+
+        visiting.pop();
+
+        package.cflags = self.clean(package.cflags);
+        package.libs = self.clean(package.libs);
+        Ok(Some(package))
+    }
+
+    /// Remove duplicates and arguments for directories searched anyway.
+    /// Like pkgconf, the last of repeated `-l` is kept so that a library
+    /// still comes after everything that links to it, other arguments keep
+    /// their first position.
+    fn clean(&self, args: Vec<String>) -> Vec<String> {
+        let mut cleaned: Vec<String> = vec![];
+        for (i, arg) in args.iter().enumerate() {
+            let repeated = if arg.starts_with("-l") {
+                args[i + 1..].contains(arg)
+            } else {
+                cleaned.contains(arg)
+            };
+            if !self.system_dirs.contains(arg) && !repeated {
+                cleaned.push(arg.clone());
+            }
+        }
+        cleaned
+    }
+}
+
+fn parse(path: &Path) -> Result<PcFile, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let invalid = |message: &str| format!("Invalid {}: {}", path.display(), message);
+
+    let mut pc = PcFile::default();
+    let pcfiledir = path.parent().unwrap_or(Path::new("")).to_string_lossy();
+    pc.variables
+        .push(("pcfiledir".to_string(), pcfiledir.to_string()));
+
+    // Backslash-newline continues a line
+    let contents = contents.replace("\\\n", " ");
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let field = line.find(':');
+        let variable = line.find('=');
+        match (field, variable) {
+            (Some(colon), equals) if equals.is_none_or(|equals| colon < equals) => {
+                let (key, value) = (line[..colon].trim(), line[colon + 1..].trim());
+                let value = expand(value, &pc.variables).map_err(|e| invalid(&e))?;
+                match key {
+                    "Version" => pc.version = value,
+                    "Cflags" | "CFlags" => pc.cflags = split_args(&value),
+                    "Libs" => pc.libs = split_args(&value),
+                    "Libs.private" => pc.libs_private = split_args(&value),
+                    "Requires" => pc.requires = requirements(&value),
+                    "Requires.private" => pc.requires_private = requirements(&value),
+                    _ => {}
+                }
+            }
+            (_, Some(equals)) => {
+                let (key, value) = (line[..equals].trim(), line[equals + 1..].trim());
+                let value = expand(value, &pc.variables).map_err(|e| invalid(&e))?;
+                pc.variables.push((key.to_string(), value));
+            }
+            _ => return Err(invalid(&format!("unexpected line '{}'", line))),
+        }
+    }
+
+    Ok(pc)
+}
+
+/// Replace `${name}` with the variable's value, `$$` is a literal `$`
+fn expand(value: &str, variables: &[(String, String)]) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(dollar) = rest.find('$') {
+        expanded += &rest[..dollar];
+        rest = &rest[dollar + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('{') {
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable in '{}'", value))?;
+            let name = &after[..end];
+            let (_, value) = variables
+                .iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .ok_or_else(|| format!("undefined variable '{}'", name))?;
+            expanded += value;
+            rest = &after[end + 1..];
+        } else {
+            expanded.push('$');
+        }
+    }
+
+    Ok(expanded + rest)
+}
+
+/// Split arguments on whitespace, honouring quotes and backslash escapes
+fn split_args(value: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', _) => {
+                current.extend(chars.next());
+                in_arg = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (c, None) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (c, None) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    args
+}
+
+/// Parse `foo >= 1.0, bar` into names and optional version constraints
+fn requirements(value: &str) -> Vec<(String, Option<String>)> {
+    let words = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut requirements: Vec<(String, Option<String>)> = vec![];
+    let mut i = 0;
+    while i < words.len() {
+        let is_operator = ["=", "==", "!=", "<", "<=", ">", ">="].contains(&words[i]);
+        match requirements.last_mut() {
+            Some((_, constraint)) if is_operator && i + 1 < words.len() => {
+                *constraint = Some(format!("{}{}", words[i], words[i + 1]));
+                i += 2;
+            }
+            _ => {
+                requirements.push((words[i].to_string(), None));
+                i += 1;
+            }
+        }
+    }
+
+    requirements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let variables = vec![
+            ("prefix".to_string(), "/opt/x".to_string()),
+            ("libdir".to_string(), "/opt/x/lib".to_string()),
+        ];
+        assert_eq!(
+            expand("-L${libdir} $$HOME ${prefix}/include", &variables).unwrap(),
+            "-L/opt/x/lib $HOME /opt/x/include"
+        );
+        assert!(expand("${nope}", &variables).is_err());
+
+        assert_eq!(
+            split_args(r#"-I/a\ b -DX="1 2" -lfoo  'c d'"#),
+            ["-I/a b", "-DX=1 2", "-lfoo", "c d"]
+        );
+
+        assert_eq!(
+            requirements("glib-2.0 >= 2.50, zlib gio-2.0 < 3"),
+            [
+                ("glib-2.0".to_string(), Some(">=2.50".to_string())),
+                ("zlib".to_string(), None),
+                ("gio-2.0".to_string(), Some("<3".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn load() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &str| {
+            fs::write(dir.path().join(format!("{}.pc", name)), contents).unwrap()
+        };
+        write(
+            "foo",
+            "prefix=/opt/foo\n\
+             libdir=${prefix}/lib # the libraries\n\
+             includedir=${prefix}/include\n\
+             \n\
+             Name: foo\n\
+             Version: 1.2.3\n\
+             Requires: bar >= 2\n\
+             Requires.private: baz\n\
+             Cflags: -I${includedir} -I/usr/include\n\
+             Libs: -L${libdir} -lfoo\n\
+             Libs.private: -lm\n",
+        );
+        write(
+            "bar",
+            "Name: bar\nVersion: 2.0\nCflags: -DBAR \\\n  -I${pcfiledir}\nLibs: -lbar -L/usr/lib\n",
+        );
+        write(
+            "baz",
+            "Name: baz\nVersion: 1\nCflags: -DBAZ\nLibs: -lbaz -lm\n",
+        );
+        write("old", "Name: old\nVersion: 1\nRequires: bar > 3\n");
+        write("broken", "Name: broken\nVersion: 1\nRequires: missing\n");
+        write("a", "Name: a\nVersion: 1\nRequires: b\n");
+        write("b", "Name: b\nVersion: 1\nRequires.private: a\n");
+
+        let pkg_config = PkgConfig::new(None, Some(dir.path().to_string_lossy().into()), None);
+        let foo = pkg_config.load("foo", false).unwrap().unwrap();
+        let bar_dir = format!("-I{}", dir.path().display());
+        assert_eq!(foo.version, "1.2.3");
+        assert_eq!(
+            foo.cflags,
+            ["-I/opt/foo/include", "-DBAR", bar_dir.as_str(), "-DBAZ"]
+        );
+        assert_eq!(foo.libs, ["-L/opt/foo/lib", "-lfoo", "-lbar"]);
+        assert!(foo
+            .variables
+            .contains(&("libdir".to_string(), "/opt/foo/lib".to_string())));
+
+        let foo = pkg_config.load("foo", true).unwrap().unwrap();
+        assert_eq!(
+            foo.libs,
+            ["-L/opt/foo/lib", "-lfoo", "-lbar", "-lbaz", "-lm"]
+        );
+
+        assert_eq!(pkg_config.load("missing", false).unwrap(), None);
+        assert_eq!(
+            pkg_config.load("old", false).unwrap_err(),
+            "Package 'old' requires 'bar >3' but version of bar is 2.0"
+        );
+        assert_eq!(
+            pkg_config.load("broken", false).unwrap_err(),
+            "Package 'missing', required by 'broken', not found"
+        );
+        assert_eq!(
+            pkg_config.load("a", false).unwrap_err(),
+            "Package 'a' requires itself through 'b'"
+        );
+    }
+}
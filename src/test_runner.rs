@@ -0,0 +1,596 @@
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    info,
+    log::{self, GREEN, RED, YELLOW},
+    Protocol, Test,
+};
+
+/// Names of the logs of test and benchmark runs in the build directory's
+/// `meson-logs`
+pub const TEST_LOG: &str = "testlog.txt";
+pub const BENCHMARK_LOG: &str = "benchmarklog.txt";
+
+/// Exit codes of the `exitcode` protocol with a special meaning
+const SKIP_CODE: i32 = 77;
+const ERROR_CODE: i32 = 99;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    ExpectedFail,
+    Fail,
+    UnexpectedPass,
+    Skip,
+    Timeout,
+    /// The test couldn't be run, or reported a hard error
+    Error,
+}
+
+impl Outcome {
+    pub fn is_failure(self) -> bool {
+        matches!(
+            self,
+            Outcome::Fail | Outcome::UnexpectedPass | Outcome::Timeout | Outcome::Error
+        )
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Outcome::Ok | Outcome::ExpectedFail => GREEN,
+            Outcome::Skip => YELLOW,
+            _ => RED,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok => f.pad("OK"),
+            Outcome::ExpectedFail => f.pad("EXPECTEDFAIL"),
+            Outcome::Fail => f.pad("FAIL"),
+            Outcome::UnexpectedPass => f.pad("UNEXPECTEDPASS"),
+            Outcome::Skip => f.pad("SKIP"),
+            Outcome::Timeout => f.pad("TIMEOUT"),
+            Outcome::Error => f.pad("ERROR"),
+        }
+    }
+}
+
+/// What to run and how, from the command line
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Run benchmarks instead of tests
+    pub benchmark: bool,
+    /// Names of the tests to run, all of them when empty
+    pub names: Vec<String>,
+    /// Suites to run, all of them when empty
+    pub suites: Vec<String>,
+    pub no_suites: Vec<String>,
+    pub repeat: usize,
+    pub num_processes: usize,
+    /// Scales every timeout, zero or less disables them
+    pub timeout_multiplier: f64,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            benchmark: false,
+            names: vec![],
+            suites: vec![],
+            no_suites: vec![],
+            repeat: 1,
+            num_processes: 1,
+            timeout_multiplier: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub name: String,
+    pub outcome: Outcome,
+    pub duration: Duration,
+    /// `None` when the test was killed or never started
+    pub returncode: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Whether a test is in `suite`, given as `project:suite`, `suite` or
+/// `project`
+fn in_suite(test: &Test, suite: &str) -> bool {
+    test.suites
+        .iter()
+        .any(|s| s == suite || s.split_once(':').is_some_and(|(_, name)| name == suite))
+}
+
+/// The tests to run in the order to start them, higher priorities first
+pub fn select(tests: &[Test], options: &RunOptions) -> Vec<Test> {
+    let mut selected = tests
+        .iter()
+        .filter(|t| t.is_benchmark == options.benchmark)
+        .filter(|t| options.names.is_empty() || options.names.contains(&t.name))
+        .filter(|t| options.suites.is_empty() || options.suites.iter().any(|s| in_suite(t, s)))
+        .filter(|t| !options.no_suites.iter().any(|s| in_suite(t, s)))
+        .cloned()
+        .collect::<Vec<_>>();
+    selected.sort_by_key(|t| -t.priority);
+    selected
+}
+
+/// Outcome of a TAP stream that ran to completion, `None` when it reports
+/// no failures
+fn tap_outcome(stdout: &str) -> Option<Outcome> {
+    let mut failed = false;
+    let mut skipped_plan = false;
+    for line in stdout.lines().map(str::trim_start) {
+        if line.starts_with("Bail out!") {
+            return Some(Outcome::Error);
+        }
+        if let Some(rest) = line.strip_prefix("1..") {
+            skipped_plan = rest.starts_with('0');
+        }
+        if line.starts_with("not ok") && !line.to_uppercase().contains("# TODO") {
+            failed = true;
+        }
+    }
+
+    if failed {
+        Some(Outcome::Fail)
+    } else if skipped_plan {
+        Some(Outcome::Skip)
+    } else {
+        None
+    }
+}
+
+fn outcome(test: &Test, returncode: i32, stdout: &str) -> Outcome {
+    let special = match test.protocol {
+        Protocol::Tap => tap_outcome(stdout),
+        Protocol::ExitCode | Protocol::Gtest | Protocol::Rust => None,
+    };
+    let special = special.or(match returncode {
+        SKIP_CODE => Some(Outcome::Skip),
+        ERROR_CODE => Some(Outcome::Error),
+        _ => None,
+    });
+    let failed = match special {
+        Some(Outcome::Fail) => true,
+        Some(outcome) => return outcome,
+        None => returncode != 0,
+    };
+
+    match (failed, test.should_fail) {
+        (false, false) => Outcome::Ok,
+        (true, false) => Outcome::Fail,
+        (true, true) => Outcome::ExpectedFail,
+        (false, true) => Outcome::UnexpectedPass,
+    }
+}
+
+/// Collect a pipe in the background, what was read so far stays available
+/// if the process has to be killed
+fn collect(mut pipe: impl Read + Send + 'static) -> (Arc<Mutex<Vec<u8>>>, thread::JoinHandle<()>) {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let handle = thread::spawn(move || {
+        let mut chunk = [0; 4096];
+        while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+            shared.lock().unwrap().extend_from_slice(&chunk[..n]);
+        }
+    });
+    (buffer, handle)
+}
+
+/// Run one test to completion or until its timeout
+pub fn run_test(test: &Test, build_dir: &Path, timeout_multiplier: f64) -> TestResult {
+    let start = Instant::now();
+    let mut result = TestResult {
+        name: test.name.clone(),
+        outcome: Outcome::Error,
+        duration: Duration::ZERO,
+        returncode: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+
+    let Some((program, args)) = test.command.split_first() else {
+        result.stderr = "Test has no command".to_string();
+        return result;
+    };
+    let spawned = Command::new(program)
+        .args(args)
+        .envs(test.env.iter().map(|(k, v)| (k, v)))
+        .current_dir(test.workdir.as_deref().unwrap_or(build_dir))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            result.stderr = format!("Failed to run {}: {}", program, e);
+            return result;
+        }
+    };
+
+    let (stdout, stdout_reader) = collect(child.stdout.take().unwrap());
+    let (stderr, stderr_reader) = collect(child.stderr.take().unwrap());
+    let timeout = test.timeout as f64 * timeout_multiplier;
+    let deadline = (timeout > 0.0).then(|| start + Duration::from_secs_f64(timeout));
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break Some(status),
+            Ok(None) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break None;
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(e) => {
+                result.stderr = format!("Failed to wait for {}: {}", program, e);
+                return result;
+            }
+        }
+    };
+    result.duration = start.elapsed();
+
+    // A killed test's children may still hold the pipes open, so only wait
+    // for the readers of one that exited
+    if status.is_some() {
+        let _ = stdout_reader.join();
+        let _ = stderr_reader.join();
+    }
+    let text = |buffer: &Mutex<Vec<u8>>| String::from_utf8_lossy(&buffer.lock().unwrap()).into();
+    result.stdout = text(&stdout);
+    result.stderr = text(&stderr);
+
+    match status {
+        Some(status) => {
+            result.returncode = status.code();
+            // Killed by a signal counts as a failure
+            result.outcome = outcome(test, status.code().unwrap_or(-1), &result.stdout);
+        }
+        None => result.outcome = Outcome::Timeout,
+    }
+    result
+}
+
+fn log_result(log: &mut String, test: &Test, result: &TestResult) {
+    *log += &format!(
+        "==================================== {} ====================================\n",
+        result.name
+    );
+    *log += &format!("command: {}\n", test.command.join(" "));
+    for (name, value) in &test.env {
+        *log += &format!("env: {}={}\n", name, value);
+    }
+    *log += &format!(
+        "result: {}, exit code {}, {:.2}s\n",
+        result.outcome,
+        result
+            .returncode
+            .map_or("none".to_string(), |c| c.to_string()),
+        result.duration.as_secs_f64()
+    );
+    for (name, output) in [("stdout", &result.stdout), ("stderr", &result.stderr)] {
+        if !output.is_empty() {
+            *log += &format!(
+                "----------------------------------- {} -----------------------------------\n",
+                name
+            );
+            *log += output;
+            if !output.ends_with('\n') {
+                log.push('\n');
+            }
+        }
+    }
+    log.push('\n');
+}
+
+/// Run the tests, parallel ones up to `num_processes` at a time and the
+/// others alone, printing each result as it comes and a summary at the end.
+/// Every result, with its output, is written to `meson-logs/testlog.txt`,
+/// or `benchmarklog.txt` for benchmarks.
+pub fn run(tests: &[Test], build_dir: &Path, options: &RunOptions) -> io::Result<Vec<TestResult>> {
+    let queue = (0..options.repeat.max(1))
+        .flat_map(|_| tests.iter())
+        .collect::<Vec<_>>();
+    let total = queue.len();
+    let name_width = tests.iter().map(|t| t.name.len()).max().unwrap_or(0);
+    let count_width = total.to_string().len();
+
+    let (sender, receiver) = mpsc::channel();
+    let mut queue = queue.into_iter().peekable();
+    let mut running = 0;
+    let mut running_alone = false;
+    let mut results = vec![];
+    let mut log = String::new();
+    while queue.peek().is_some() || running > 0 {
+        if let Some(test) = queue.peek() {
+            let can_start = running == 0
+                || (test.is_parallel && !running_alone && running < options.num_processes);
+            if can_start {
+                let test = (*queue.next().unwrap()).clone();
+                running += 1;
+                running_alone = !test.is_parallel;
+                let sender = sender.clone();
+                let build_dir = build_dir.to_path_buf();
+                let multiplier = options.timeout_multiplier;
+                thread::spawn(move || {
+                    let result = run_test(&test, &build_dir, multiplier);
+                    let _ = sender.send((test, result));
+                });
+                continue;
+            }
+        }
+
+        let (test, result) = receiver.recv().unwrap();
+        running -= 1;
+        running_alone = false;
+
+        info!(
+            "{:>count_width$}/{} {:name_width$} {} {:>8.2}s",
+            results.len() + 1,
+            total,
+            result.name,
+            log::paint(result.outcome.colour(), &format!("{:14}", result.outcome)),
+            result.duration.as_secs_f64(),
+            count_width = count_width,
+            name_width = name_width,
+        );
+        for (name, output) in [("stdout", &result.stdout), ("stderr", &result.stderr)] {
+            if !output.is_empty() {
+                crate::debug!("--- {} ---\n{}", name, output.trim_end());
+            }
+        }
+        log_result(&mut log, &test, &result);
+        results.push(result);
+    }
+
+    let summary = summary(&results);
+    log += &summary;
+    let log_name = if options.benchmark {
+        BENCHMARK_LOG
+    } else {
+        TEST_LOG
+    };
+    let log_path = build_dir.join("meson-logs").join(log_name);
+    fs::create_dir_all(log_path.parent().unwrap())?;
+    fs::write(&log_path, log)?;
+
+    info!("\n{}", summary.trim_end());
+    info!("\nFull log written to {}", log_path.display());
+    Ok(results)
+}
+
+/// Counts of each outcome, after the failures by name
+fn summary(results: &[TestResult]) -> String {
+    let mut out = String::new();
+    let failures = results
+        .iter()
+        .filter(|r| r.outcome.is_failure())
+        .collect::<Vec<_>>();
+    if !failures.is_empty() {
+        out += "Summary of Failures:\n\n";
+        for result in failures {
+            out += &format!("{} {}\n", result.name, result.outcome);
+        }
+        out.push('\n');
+    }
+
+    let count = |outcomes: &[Outcome]| {
+        results
+            .iter()
+            .filter(|r| outcomes.contains(&r.outcome))
+            .count()
+    };
+    let lines = [
+        ("Ok", count(&[Outcome::Ok]), GREEN),
+        ("Expected Fail", count(&[Outcome::ExpectedFail]), GREEN),
+        ("Fail", count(&[Outcome::Fail, Outcome::Error]), RED),
+        ("Unexpected Pass", count(&[Outcome::UnexpectedPass]), RED),
+        ("Skipped", count(&[Outcome::Skip]), YELLOW),
+        ("Timeout", count(&[Outcome::Timeout]), RED),
+    ];
+    for (name, count, colour) in lines {
+        let count = if count > 0 {
+            log::paint(colour, &count.to_string())
+        } else {
+            count.to_string()
+        };
+        out += &format!("{:16} {}\n", format!("{}:", name), count);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(name: &str, script: &str) -> Test {
+        Test {
+            name: name.to_string(),
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            timeout: 30,
+            suites: vec!["p".to_string()],
+            is_parallel: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn outcomes() {
+        let build_dir = tempfile::tempdir().unwrap();
+        let cases = [
+            (test("ok", "exit 0"), Outcome::Ok),
+            (test("fail", "exit 1"), Outcome::Fail),
+            (test("skip", "exit 77"), Outcome::Skip),
+            (test("error", "exit 99"), Outcome::Error),
+            (
+                Test {
+                    should_fail: true,
+                    ..test("expected", "exit 1")
+                },
+                Outcome::ExpectedFail,
+            ),
+            (
+                Test {
+                    should_fail: true,
+                    ..test("unexpected", "true")
+                },
+                Outcome::UnexpectedPass,
+            ),
+            (
+                Test {
+                    env: vec![("VALUE".to_string(), "x".to_string())],
+                    ..test("env", "test \"$VALUE\" = x")
+                },
+                Outcome::Ok,
+            ),
+            (
+                Test {
+                    timeout: 1,
+                    ..test("timeout", "sleep 5")
+                },
+                Outcome::Timeout,
+            ),
+            (
+                Test {
+                    protocol: Protocol::Tap,
+                    ..test("tap", "echo 1..2; echo ok 1; echo 'not ok 2 # TODO later'")
+                },
+                Outcome::Ok,
+            ),
+            (
+                Test {
+                    protocol: Protocol::Tap,
+                    ..test("tap fail", "echo 1..2; echo ok 1; echo not ok 2")
+                },
+                Outcome::Fail,
+            ),
+            (
+                Test {
+                    protocol: Protocol::Tap,
+                    ..test("tap skip", "echo '1..0 # SKIP no support'")
+                },
+                Outcome::Skip,
+            ),
+            (
+                Test {
+                    command: vec!["/nonexistent/test".to_string()],
+                    ..test("missing", "")
+                },
+                Outcome::Error,
+            ),
+        ];
+
+        for (test, expected) in cases {
+            let result = run_test(&test, build_dir.path(), 0.5);
+            assert_eq!(result.outcome, expected, "{}: {:?}", test.name, result);
+        }
+
+        let result = run_test(
+            &test("output", "echo out; echo err >&2"),
+            build_dir.path(),
+            1.0,
+        );
+        assert_eq!(
+            (result.stdout.as_str(), result.stderr.as_str()),
+            ("out\n", "err\n")
+        );
+    }
+
+    #[test]
+    fn selection() {
+        let tests = [
+            Test {
+                suites: vec!["p".to_string(), "p:fast".to_string()],
+                ..test("a", "")
+            },
+            Test {
+                priority: 2,
+                suites: vec!["p".to_string(), "p:slow".to_string()],
+                ..test("b", "")
+            },
+            Test {
+                is_benchmark: true,
+                ..test("bench", "")
+            },
+        ];
+        let names = |options: RunOptions| {
+            select(&tests, &options)
+                .into_iter()
+                .map(|t| t.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(RunOptions::default()), ["b", "a"]);
+        let suites = |suites: &[&str]| RunOptions {
+            suites: suites.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(names(suites(&["fast"])), ["a"]);
+        assert_eq!(names(suites(&["p:slow"])), ["b"]);
+        assert_eq!(names(suites(&["p"])), ["b", "a"]);
+        assert_eq!(
+            names(RunOptions {
+                no_suites: vec!["slow".to_string()],
+                ..Default::default()
+            }),
+            ["a"]
+        );
+        assert_eq!(
+            names(RunOptions {
+                benchmark: true,
+                ..Default::default()
+            }),
+            ["bench"]
+        );
+    }
+
+    #[test]
+    fn parallel_runs() {
+        let build_dir = tempfile::tempdir().unwrap();
+        let tests = [
+            test("one", "exit 0"),
+            test("two", "exit 1"),
+            Test {
+                is_parallel: false,
+                ..test("alone", "exit 0")
+            },
+        ];
+        let results = run(
+            &tests,
+            build_dir.path(),
+            &RunOptions {
+                repeat: 2,
+                num_processes: 4,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results.iter().filter(|r| r.outcome.is_failure()).count(), 2);
+
+        let log = fs::read_to_string(build_dir.path().join("meson-logs").join(TEST_LOG)).unwrap();
+        assert!(
+            log.contains("Summary of Failures:\n\ntwo FAIL\ntwo FAIL\n"),
+            "{}",
+            log
+        );
+        assert!(log.contains("Ok:              4\n"), "{}", log);
+    }
+}
@@ -0,0 +1,78 @@
+use std::cmp::Ordering;
+
+/// Compare two version strings component by component, numeric components
+/// numerically, like Meson's `version_compare`
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.split(['.', '-', '_'])
+            .filter(|c| !c.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+
+    for (l, r) in a.iter().zip(b.iter()) {
+        let ordering = match (l.parse::<u64>(), r.parse::<u64>()) {
+            (Ok(l), Ok(r)) => l.cmp(&r),
+            // Numbers sort after strings, so 1.0 > 1.rc1
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Err(_), Err(_)) => l.cmp(r),
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    a.len().cmp(&b.len())
+}
+
+/// Check `version` against a requirement such as `>=1.2.0`, a requirement
+/// with no operator must match exactly
+pub fn matches(version: &str, requirement: &str) -> bool {
+    let requirement = requirement.trim();
+    let (op, wanted) = ["==", "!=", ">=", "<=", ">", "<", "="]
+        .iter()
+        .find_map(|op| requirement.strip_prefix(op).map(|r| (*op, r.trim())))
+        .unwrap_or(("==", requirement));
+
+    let ordering = compare(version, wanted);
+    match op {
+        "==" | "=" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        ">=" => ordering != Ordering::Less,
+        "<=" => ordering != Ordering::Greater,
+        ">" => ordering == Ordering::Greater,
+        "<" => ordering == Ordering::Less,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_matches() {
+        let tests = [
+            ("1.2.3", ">=1.2", true),
+            ("1.2.3", "<1.2", false),
+            ("1.10", ">1.9", true),
+            ("1.2.3", "1.2.3", true),
+            ("1.2.3", "!=1.2.3", false),
+            ("1.0", ">1.0rc1", true),
+            ("0.60.0", "<= 0.59", false),
+        ];
+
+        for (version, requirement, expected) in tests {
+            assert_eq!(
+                matches(version, requirement),
+                expected,
+                "{} {}",
+                version,
+                requirement
+            );
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
-use reson::interpreter::Interpreter;
+use reson::{
+    backend::{self, Graph},
+    compiler::Language,
+    interpreter::{value::Value, CallKind, Error, Interpreter},
+    options::OptionValue,
+    File, IncludeDirectories, Protocol, TargetId, TargetKind,
+};
 
 #[test]
 fn interpret_cases() -> Result<()> {
@@ -35,35 +44,100 @@ fn interpret_cases() -> Result<()> {
             file_contents: "project('hello world')\nexecutable('exe')",
             compile: true,
         },
-        // Test {
-        //     file_contents: r#"project('hello world')
-        //     if get_option('buildtype') == 'debug'
-        //         executable('exe')
-        //     endif"#,
-        //     compile: true,
-        // },
+        Test {
+            file_contents:
+                "project('lib')\nlib = static_library('foo')\nexecutable('exe', link_whole: lib)",
+            compile: true,
+        },
+        Test {
+            file_contents:
+                "project('lib')\nlib = shared_library('foo')\nexecutable('exe', link_whole: lib)",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('lib')\nshared_library('foo', version: '1.a')",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('lib')\nshared_library('foo', pic: false)",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('lib')\nlibrary('foo', gnu_symbol_visibility: 'hidden')",
+            compile: true,
+        },
+        Test {
+            file_contents: "project('lib')\nlibrary('foo', gnu_symbol_visibility: 'secret')",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('lib')\nstatic_library('foo', vs_module_defs: 'foo.def')",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('methods')\nname = ' foo '.strip().to_upper()\nexecutable(name.to_lower() + '_exe')",
+            compile: true,
+        },
+        Test {
+            file_contents: "project('methods')\nexecutable(['a', 'b'].get(-1))\nexecutable(['a'].get(2))",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('kwargs')\nopts = {'install': true, 'build_by_default': false}\nexecutable('exe', kwargs: opts)",
+            compile: true,
+        },
+        Test {
+            file_contents: "project('kwargs')\nexecutable('exe', install: false, kwargs: {'install': true})",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('kwargs')\nexecutable('exe', kwargs: ['install'])",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('kwargs')\nexecutable('exe', install: true, install: false)",
+            compile: false,
+        },
+        Test {
+            file_contents: r#"project('hello world')
+            if get_option('buildtype') == 'debug'
+                executable('exe')
+            endif"#,
+            compile: true,
+        },
+        Test {
+            file_contents: "project('options')\nget_option('nope')",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('options', default_options: ['nope=1'])",
+            compile: false,
+        },
+        Test {
+            file_contents: "project('options', default_options: ['werror=maybe'])",
+            compile: false,
+        },
     ];
 
     for test in tests {
         let test_dir = tempfile::tempdir()?;
 
         let meson = test_dir.path().join("meson.build");
-        fs::write(meson, &test.file_contents)?;
+        fs::write(meson, test.file_contents)?;
 
-        let mut interpreter = Interpreter::new(test_dir.path(), &PathBuf::new());
+        let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
         let err = interpreter.interpret();
 
         match err {
             Ok(()) => {
                 if !test.compile {
-                    assert!(false, "Test: {}, should've failed", test.file_contents,);
+                    panic!("Test: {}, should've failed", test.file_contents);
                 }
             }
             Err(e) => {
                 if test.compile {
                     // should've succeeded
-                    assert!(
-                        false,
+                    panic!(
                         "Test: {}, should've succeeded but failed: {:?}",
                         test.file_contents, e
                     );
@@ -75,6 +149,1676 @@ fn interpret_cases() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn library_targets() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    fs::write(test_dir.path().join("foo.c"), "")?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        r#"project('lib', default_options: ['default_library=both', 'default_both_libraries=auto'])
+foo = library('foo', 'foo.c',
+    version: '1.2.3')
+shared_library('bar', soversion: 0)
+static_library('baz', link_with: [foo])
+executable('exe', link_with: foo, link_whole: foo.get_static_lib())
+assert(foo.name() == 'foo')
+assert(foo.get_shared_lib().full_path().endswith('libfoo.so.1.2.3'))"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+    interpreter.interpret()?;
+
+    let targets = &interpreter.builder().build_targets;
+    assert_eq!(targets.len(), 5);
+
+    assert_eq!(targets[0].kind, TargetKind::SharedLibrary);
+    assert_eq!(targets[0].filename(), "libfoo.so.1.2.3");
+    assert_eq!(
+        targets[0].aliases(),
+        vec![
+            ("libfoo.so".to_string(), "libfoo.so.1".to_string()),
+            ("libfoo.so.1".to_string(), "libfoo.so.1.2.3".to_string()),
+        ]
+    );
+
+    assert_eq!(targets[1].kind, TargetKind::StaticLibrary);
+    assert_eq!(targets[1].filename(), "libfoo.a");
+    assert!(targets[1].pic);
+    assert!(targets[1].aliases().is_empty());
+
+    assert_eq!(targets[2].filename(), "libbar.so.0");
+    assert_eq!(
+        targets[2].aliases(),
+        vec![("libbar.so".to_string(), "libbar.so.0".to_string())]
+    );
+
+    // With `auto` a static library links the static one
+    assert_eq!(targets[3].link_with, vec![1]);
+    assert_eq!(targets[4].link_with, vec![0]);
+    assert_eq!(targets[4].link_whole, vec![1]);
+
+    Ok(())
+}
+
+#[test]
+fn target_kwargs() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::write(root.join("foo.c"), "")?;
+    fs::write(root.join("foo.o"), "")?;
+    fs::write(root.join("foo.map"), "")?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('kwargs', 'c', 'cpp')
+shared_library('foo', 'foo.c',
+    version: '1.0.0',
+    name_prefix: '',
+    name_suffix: 'plugin',
+    c_args: ['-DFOO'],
+    cpp_args: '-DBAR',
+    objects: 'foo.o',
+    link_depends: files('foo.map'),
+    override_options: ['c_std=c11', 'werror=true'],
+    implicit_include_directories: false,
+    build_by_default: false,
+    install: true,
+    install_dir: 'lib/plugins')
+static_library('bar', 'foo.c', name_prefix: [], name_suffix: [])
+executable('exe', 'foo.c', override_options: {'b_pie': true}, name_prefix: 'my')"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.interpret()?;
+
+    let builder = interpreter.builder();
+    let [foo, bar, exe] = &builder.build_targets[..] else {
+        panic!("Expected three targets");
+    };
+    assert_eq!(foo.filename(), "foo.plugin");
+    assert!(foo.aliases().is_empty());
+    assert_eq!(
+        foo.language_args,
+        [
+            (Language::C, vec!["-DFOO".to_string()]),
+            (Language::Cpp, vec!["-DBAR".to_string()]),
+        ]
+    );
+    assert_eq!(
+        foo.objects,
+        [File::source(Path::new(""), Path::new("foo.o"))]
+    );
+    assert_eq!(
+        foo.link_depends,
+        [File::source(Path::new(""), Path::new("foo.map"))]
+    );
+    let options = foo.options(&builder.options).unwrap();
+    assert_eq!(options.value("c_std").unwrap().to_string(), "c11");
+    assert_eq!(options.value("werror").unwrap().to_string(), "true");
+    assert_eq!(
+        builder.options.value("werror").unwrap().to_string(),
+        "false"
+    );
+    assert!(!foo.implicit_include_directories);
+    assert!(!foo.build_by_default);
+    assert!(foo.install);
+    assert_eq!(foo.install_dir.as_deref(), Some("lib/plugins"));
+
+    assert_eq!(bar.filename(), "libbar.a");
+    assert!(bar.implicit_include_directories);
+    assert!(bar.build_by_default);
+    assert_eq!(exe.filename(), "myexe");
+    assert_eq!(
+        exe.override_options,
+        [("b_pie".to_string(), "true".to_string())]
+    );
+
+    let errors = [
+        (
+            "executable('exe', 'foo.c', override_options: ['c_std'])",
+            "Override option 'c_std' must be of the form name=value",
+        ),
+        (
+            "executable('exe', 'foo.c', override_options: ['c_std=c99x'])",
+            "Value 'c99x' for combo option 'c_std' is invalid",
+        ),
+        (
+            "executable('exe', 'foo.c', name_suffix: ['x'])",
+            "name_suffix must be a string or an empty array, got array",
+        ),
+        (
+            "executable('exe', 'foo.c', objects: 'missing.o')",
+            "File missing.o does not exist",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p', 'c')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn unknown_function() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        "project('unknown')\nexecutabel('exe')",
+    )?;
+
+    let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+    let err = interpreter.interpret().unwrap_err();
+
+    let Error::At { error, .. } = &err else {
+        panic!("Error isn't located: {:?}", err);
+    };
+    assert!(matches!(**error, Error::UnknownFunction { .. }));
+    assert_eq!(
+        err.to_string(),
+        "meson.build:2:1: Unknown function \"executabel\", did you mean \"executable\"?"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn signature_errors() -> Result<()> {
+    let tests = [
+        (
+            "project('p')\nexecutable('exe', sourcse: 'main.c')",
+            "meson.build:2:1: executable() got unknown keyword argument 'sourcse', did you mean \"sources\"?",
+        ),
+        (
+            "project('p')\nexecutable('exe', install: 'yes')",
+            "meson.build:2:1: executable() keyword argument 'install' was of type \"str\" but should have been \"bool\"",
+        ),
+        (
+            "project('p')\nexecutable(1)",
+            "meson.build:2:1: executable() argument 1 was of type \"int\" but should have been \"str\"",
+        ),
+        (
+            "project('p')\nexecutable('exe', link_with: ['a'])",
+            "meson.build:2:1: executable() keyword argument 'link_with' was of type \"array\" but should have been \"list[build_tgt]\"",
+        ),
+        (
+            "project()",
+            "meson.build:1:1: project() takes at least 1 positional arguments (0 given)",
+        ),
+        (
+            "project('p')\n'a'.strip('b', 'c')",
+            "meson.build:2:5: strip() takes between 0 and 1 positional arguments (2 given)",
+        ),
+        (
+            "project('p')\n'a'.strp()",
+            "meson.build:2:5: Unknown method \"strp\" in object of type str, did you mean \"strip\"?",
+        ),
+    ];
+
+    for (contents, expected) in tests {
+        let test_dir = tempfile::tempdir()?;
+        fs::write(test_dir.path().join("meson.build"), contents)?;
+
+        let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+        let err = interpreter.interpret().unwrap_err();
+        assert_eq!(err.to_string(), expected, "{}", contents);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn error_locations() -> Result<()> {
+    let tests = [
+        (
+            "project('p')\nx = 1\ny = 'a' + x",
+            "meson.build:3:5: Expected Number, got String(\"a\")",
+        ),
+        (
+            "project('p')\nx = undefined",
+            "meson.build:2:1: Unknown variable undefined",
+        ),
+        (
+            "project('p')\nif 1 == 1\n  message(undefined)\nendif",
+            "meson.build:3:3: Unknown variable undefined",
+        ),
+        (
+            "project('p')\nif 'a'\nendif",
+            "meson.build:2:4: Expected Boolean, got String(\"a\")",
+        ),
+        (
+            "project('p')\nx = 1\ny = $",
+            "meson.build:3:5: Parse error: Failed to lex $",
+        ),
+        (
+            "project('p')\n\nf(a: 1, a: 2)",
+            "meson.build:3:9: Parse error: Keyword argument a given multiple times",
+        ),
+    ];
+
+    for (contents, expected) in tests {
+        let test_dir = tempfile::tempdir()?;
+        fs::write(test_dir.path().join("meson.build"), contents)?;
+
+        let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+        let err = interpreter.interpret().unwrap_err();
+        assert_eq!(err.to_string(), expected, "{}", contents);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn report_unsupported() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    fs::write(test_dir.path().join("main.c"), "")?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        r#"project('unknown', 'c')
+pkg = import('pkgconfig')
+executable('exe', 'main.c')
+cc = meson.get_compiler('c')
+cc.get_define('FOO')
+pkg.generate(name: 'x')
+executable('e', pkg)
+if pkg.found()
+    x = 1
+endif
+dependency('reson-missing', required: false, allow_fallback: true)
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+    interpreter.set_report_unsupported(true);
+    interpreter.interpret()?;
+
+    let unsupported = interpreter
+        .unsupported()
+        .iter()
+        .map(|u| (u.kind, u.name.as_str(), u.span.line))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        unsupported,
+        vec![
+            (CallKind::Function, "import", 2),
+            (CallKind::Method, "get_define", 5),
+            (CallKind::Method, "generate", 6),
+            (CallKind::Method, "found", 8),
+            (CallKind::Kwarg, "allow_fallback", 11),
+        ]
+    );
+
+    // Only what unsupported calls break is skipped, other errors still stop
+    // the audit
+    let errors = [
+        ("x = 'a' + 1", "Expected Number, got String(\"a\")"),
+        ("message(undefined)", "Unknown variable undefined"),
+        (
+            "executable('e', 'missing.c')",
+            "File missing.c does not exist",
+        ),
+        (
+            "executable('e', 'main.c', not_a_kwarg: true)",
+            "executable() got unknown keyword argument 'not_a_kwarg'",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            test_dir.path().join("meson.build"),
+            format!("project('p')\nimport('fs')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+        interpreter.set_report_unsupported(true);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn compiler_methods() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let build_dir = tempfile::tempdir()?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        r#"project('compilers', 'c')
+cc = meson.get_compiler('c')
+id = cc.get_id()
+version = cc.version()
+linker = cc.get_linker_id()
+cmd = cc.cmd_array()
+has_wall = cc.has_argument('-Wall')
+has_bogus = cc.has_argument('-Wreson-bogus')
+supported = cc.get_supported_arguments('-Wshadow', ['-Wreson-bogus', '-Wundef'])
+first = cc.first_supported_argument('-Wreson-bogus', '-Wextra', '-Wall')
+none = cc.first_supported_argument('-Wreson-bogus')
+link = cc.get_supported_link_arguments('-Wl,--as-needed', '-Wl,--reson-bogus')
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(test_dir.path(), build_dir.path());
+    interpreter.interpret()?;
+
+    let string = |name| interpreter.variable(name).unwrap().as_string().unwrap();
+    let strings = |name| -> Vec<&str> {
+        interpreter
+            .variable(name)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_string().unwrap().as_str())
+            .collect()
+    };
+
+    let compiler = &interpreter.builder().compilers[0];
+    assert_eq!(*string("id"), compiler.id.to_string());
+    assert_eq!(*string("version"), compiler.version);
+    assert!(string("linker").starts_with("ld."));
+    assert_eq!(strings("cmd"), [compiler.path.to_str().unwrap()]);
+    assert_eq!(
+        interpreter.variable("has_wall"),
+        Some(&Value::Boolean(true))
+    );
+    assert_eq!(
+        interpreter.variable("has_bogus"),
+        Some(&Value::Boolean(false))
+    );
+    assert_eq!(strings("supported"), ["-Wshadow", "-Wundef"]);
+    assert_eq!(strings("first"), ["-Wextra"]);
+    assert!(strings("none").is_empty());
+    assert_eq!(strings("link"), ["-Wl,--as-needed"]);
+
+    let errors = [
+        (
+            "project('p')\nmeson.get_compiler('c')",
+            "meson.build:2:7: Tried to access compiler for language \"c\", not specified for host machine",
+        ),
+        (
+            "project('p', 'c')\nmeson.get_compiler('c').get_supported_arguments('-Wreson-bogus', checked: 'require')",
+            "meson.build:2:25: Compiler for c does not support \"-Wreson-bogus\"",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(test_dir.path().join("meson.build"), contents)?;
+        let mut interpreter = Interpreter::new(test_dir.path(), build_dir.path());
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn compiler_checks() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let build_dir = tempfile::tempdir()?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        r#"project('checks', 'c')
+cc = meson.get_compiler('c')
+stdio = cc.has_header('stdio.h')
+missing = cc.has_header('reson_missing.h')
+printf = cc.has_function('printf', prefix: '#include <stdio.h>')
+bogus_function = cc.has_function('reson_bogus_function')
+int_size = cc.sizeof('int')
+unknown_size = cc.sizeof('struct reson_unknown')
+int_alignment = cc.alignment('int32_t', prefix: '#include <stdint.h>')
+member = cc.has_member('struct tm', 'tm_sec', prefix: '#include <time.h>')
+compiles = cc.compiles('int x = 1;', name: 'global')
+broken = cc.compiles('int x = ;')
+links = cc.links(files('main.c').get(0))
+result = cc.run('#include <stdio.h>
+int main(void) { printf("hi"); return 2; }')
+compiled = result.compiled()
+returncode = result.returncode()
+stdout = result.stdout()
+"#,
+    )?;
+    fs::write(
+        test_dir.path().join("main.c"),
+        "int main(void) { return 0; }",
+    )?;
+
+    let cache = build_dir.path().join("reson-private/check_cache.txt");
+    let mut cached = 0;
+    for _ in 0..2 {
+        let mut interpreter = Interpreter::new(test_dir.path(), build_dir.path());
+        interpreter.interpret()?;
+
+        let expected = [
+            ("stdio", Value::Boolean(true)),
+            ("missing", Value::Boolean(false)),
+            ("printf", Value::Boolean(true)),
+            ("bogus_function", Value::Boolean(false)),
+            ("int_size", Value::Number(4)),
+            ("unknown_size", Value::Number(-1)),
+            ("int_alignment", Value::Number(4)),
+            ("member", Value::Boolean(true)),
+            ("compiles", Value::Boolean(true)),
+            ("broken", Value::Boolean(false)),
+            ("links", Value::Boolean(true)),
+            ("compiled", Value::Boolean(true)),
+            ("returncode", Value::Number(2)),
+            ("stdout", Value::String("hi".to_string())),
+        ];
+        for (name, value) in expected {
+            assert_eq!(interpreter.variable(name), Some(&value), "{}", name);
+        }
+
+        // run() isn't cached, everything else is and only once
+        let lines = fs::read_to_string(&cache)?.lines().count();
+        assert_eq!(lines, 11);
+        assert!(cached == 0 || cached == lines);
+        cached = lines;
+    }
+
+    fs::write(
+        test_dir.path().join("meson.build"),
+        "project('checks', 'c')\nmeson.get_compiler('c').has_header('reson_missing.h', required: true)",
+    )?;
+    let mut interpreter = Interpreter::new(test_dir.path(), build_dir.path());
+    assert_eq!(
+        interpreter.interpret().unwrap_err().to_string(),
+        "meson.build:2:25: C header 'reson_missing.h' not found"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn dependencies() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let build_dir = tempfile::tempdir()?;
+    let pc_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::write(
+        pc_dir.path().join("reson-foo.pc"),
+        "prefix=/opt/foo\nlibdir=${prefix}/lib\n\nName: foo\nVersion: 1.4.0\n\
+         Requires.private: reson-bar\nCflags: -I${prefix}/include\nLibs: -L${libdir} -lfoo\n",
+    )?;
+    fs::write(
+        pc_dir.path().join("reson-bar.pc"),
+        "Name: bar\nVersion: 2\nCflags: -DBAR\nLibs: -lbar\n",
+    )?;
+    let pc_libdir = pc_dir.path().to_str().unwrap();
+
+    fs::write(root.join("main.c"), "")?;
+    fs::write(
+        root.join("meson.options"),
+        "option('foo', type: 'feature', value: 'disabled')",
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('deps', 'c')
+foo = dependency('reson-missing', 'reson-foo', version: ['>=1.2', '<2'],
+    fallback: ['foo', 'foo_dep'])
+missing = dependency('reson-missing', required: false)
+checks = [
+    foo.found(),
+    foo.name() == 'reson-foo',
+    foo.version() == '1.4.0',
+    foo.type_name() == 'pkgconfig',
+    foo.get_variable('libdir') == '/opt/foo/lib',
+    foo.get_variable(pkgconfig: 'nope', default_value: 'x') == 'x',
+    missing.version() == 'unknown',
+    missing.type_name() == 'not-found',
+]
+old = dependency('reson-foo', version: '>=2', required: false)
+none = dependency('', required: false)
+skipped = dependency('reson-foo', required: get_option('foo'))
+not_found = [missing.found(), old.found(), none.found(), skipped.found()]
+subdir('nope', if_found: missing)
+executable('exe', 'main.c', dependencies: [foo, missing])
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, build_dir.path());
+    interpreter.set_env("PKG_CONFIG_PATH", None);
+    interpreter.set_env("PKG_CONFIG_LIBDIR", Some(pc_libdir));
+    interpreter.interpret()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+    let not_found = interpreter
+        .variable("not_found")
+        .unwrap()
+        .as_array()
+        .unwrap();
+    for (i, check) in not_found.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(false), "Check {} failed", i);
+    }
+
+    let builder = interpreter.builder();
+    let foo = &builder.dependencies[0];
+    assert_eq!(foo.compile_args, ["-I/opt/foo/include", "-DBAR"]);
+    assert_eq!(foo.link_args, ["-L/opt/foo/lib", "-lfoo"]);
+    assert_eq!(builder.build_targets[0].dependencies, [0, 1]);
+    assert!(builder
+        .environment
+        .contains(&("PKG_CONFIG_LIBDIR".to_string(), Some(pc_libdir.to_string()))));
+
+    let errors = [
+        (
+            "project('p', 'c')\ndependency('reson-missing')",
+            "meson.build:2:1: Dependency \"reson-missing\" not found, tried pkgconfig",
+        ),
+        (
+            "project('p', 'c')\ndependency('reson-foo', version: '>=2')",
+            "meson.build:2:1: Dependency \"reson-foo\" not found, found 1.4.0 but need '>=2'",
+        ),
+        (
+            "project('p', 'c')\ndependency('reson-missing', fallback: ['foo', 'foo_dep'])",
+            "meson.build:2:1: dependency() keyword argument 'fallback' is not supported yet",
+        ),
+        (
+            "project('p', 'c')\ndependency('reson-foo').get_variable('nope')",
+            "meson.build:2:25: Could not get variable 'nope' of dependency \"reson-foo\" and no default provided",
+        ),
+        (
+            "project('p', 'c')\nexecutable('exe', dependencies: 'zlib')",
+            "meson.build:2:1: executable() keyword argument 'dependencies' was of type \"str\" but should have been \"list[dep]\"",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(root.join("meson.build"), contents)?;
+        let mut interpreter = Interpreter::new(root, build_dir.path());
+        interpreter.set_env("PKG_CONFIG_PATH", None);
+        interpreter.set_env("PKG_CONFIG_LIBDIR", Some(pc_libdir));
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn internal_dependencies() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::create_dir_all(root.join("lib/include"))?;
+    for file in ["lib/a.c", "lib/b.c", "lib/gen.c", "main.c"] {
+        fs::write(root.join(file), "")?;
+    }
+    fs::write(
+        root.join("lib/meson.build"),
+        r#"a = static_library('a', 'a.c', link_args: '-lm')
+b = static_library('b', 'b.c', link_with: a)
+shared = shared_library('shared', 'b.c')
+base_dep = declare_dependency(compile_args: '-DBASE', link_args: '-lbase')
+lib_dep = declare_dependency(
+    include_directories: 'include',
+    compile_args: ['-DLIB'],
+    sources: files('gen.c'),
+    link_with: [b, shared],
+    dependencies: base_dep,
+    variables: {'datadir': 'share/lib'},
+    version: '2.1',
+)
+"#,
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('internal', version: '1.0')
+subdir('lib')
+exe = executable('exe', 'main.c', dependencies: lib_dep)
+headers = lib_dep.partial_dependency(includes: true, compile_args: true)
+headers_exe = executable('headers', 'main.c', dependencies: headers)
+whole_exe = executable('whole', 'main.c',
+    dependencies: declare_dependency(link_with: a).as_link_whole())
+checks = [
+    lib_dep.found(),
+    lib_dep.type_name() == 'internal',
+    lib_dep.version() == '2.1',
+    base_dep.version() == '1.0',
+    lib_dep.get_variable('datadir') == 'share/lib',
+    lib_dep.get_variable(internal: 'datadir') == 'share/lib',
+    declare_dependency(variables: ['x = 1']).get_variable('x') == '1',
+]
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.interpret()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+
+    let targets = &interpreter.builder().build_targets;
+    let include = IncludeDirectories {
+        subdir: "lib".into(),
+        dirs: vec!["include".into()],
+        is_system: false,
+    };
+    let exe = &targets[3];
+    assert_eq!(exe.compile_args, ["-DLIB", "-DBASE"]);
+    assert_eq!(exe.include_directories, std::slice::from_ref(&include));
+    // b is static, so whatever it links is linked by exe too
+    assert_eq!(exe.link_with, [1, 2, 0]);
+    assert_eq!(exe.link_args, ["-lbase", "-lm"]);
+    let paths = exe
+        .files
+        .iter()
+        .map(|f| f.relative_path())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, [PathBuf::from("main.c"), PathBuf::from("lib/gen.c")]);
+
+    let headers = &targets[4];
+    assert_eq!(headers.compile_args, ["-DLIB", "-DBASE"]);
+    assert_eq!(headers.include_directories, [include]);
+    assert!(headers.link_with.is_empty() && headers.link_args.is_empty());
+    assert_eq!(headers.files.len(), 1);
+
+    let whole = &targets[5];
+    assert!(whole.link_with.is_empty());
+    assert_eq!(whole.link_whole, [0]);
+    assert_eq!(whole.link_args, ["-lm"]);
+
+    let errors = [
+        (
+            "project('p')\nsubdir('lib')\ndeclare_dependency(link_with: shared).as_link_whole()",
+            "meson.build:3:39: Arguments passed to function don't match with required: Only static libraries can be linked whole, shared is a shared library",
+        ),
+        (
+            "project('p')\ndeclare_dependency(variables: ['nope'])",
+            "meson.build:2:1: Arguments passed to function don't match with required: Variable 'nope' must have a value separated by equals sign",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(root.join("meson.build"), contents)?;
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn find_program() -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::create_dir_all(root.join("tools"))?;
+    fs::create_dir_all(root.join("bin"))?;
+    fs::write(root.join("main.c"), "")?;
+    // Not executable, run through its #! line
+    fs::write(
+        root.join("tools/gen.sh"),
+        "#!/bin/sh\necho 'gen version 1.2.3'\n",
+    )?;
+    fs::write(root.join("bin/tool"), "#!/bin/sh\necho 'tool 2.0' >&2\n")?;
+    fs::set_permissions(root.join("bin/tool"), fs::Permissions::from_mode(0o755))?;
+    fs::write(
+        root.join("native.ini"),
+        "[binaries]\nstrict_sh = ['sh', '-e']\n",
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('programs', 'c', version: '0.5')
+sh = find_program('reson-missing', 'sh')
+gen = find_program('tools/gen.sh', version: '>=1.2')
+tool = find_program('tool', dirs: ['bin'], version: ['>=2', '<3'])
+old_tool = find_program('tool', dirs: ['bin'], version: '>=3', required: false)
+missing = find_program('reson-missing', required: false)
+strict_sh = find_program('strict_sh')
+mygen = executable('mygen', 'main.c')
+meson.override_find_program('mygen', mygen)
+meson.override_find_program('mysh', sh)
+checks = [
+    sh.found(),
+    sh.full_path().endswith('/sh'),
+    gen.version() == '1.2.3',
+    gen.full_path().endswith('/tools/gen.sh'),
+    tool.version() == '2.0',
+    strict_sh.found(),
+    find_program('mygen').found(),
+    find_program('mygen').version() == '0.5',
+    find_program('mysh').full_path() == sh.full_path(),
+]
+not_found = [old_tool.found(), missing.found()]
+subdir('nope', if_found: missing)
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.set_native_files(vec![root.join("native.ini")]);
+    interpreter.interpret()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+    let not_found = interpreter
+        .variable("not_found")
+        .unwrap()
+        .as_array()
+        .unwrap();
+    assert_eq!(not_found, &[Value::Boolean(false), Value::Boolean(false)]);
+
+    let builder = interpreter.builder();
+    let program =
+        |name| &builder.programs[*interpreter.variable(name).unwrap().as_program().unwrap()];
+    let gen = root.join("tools/gen.sh").to_string_lossy().to_string();
+    assert_eq!(program("gen").command, ["/bin/sh", gen.as_str()]);
+    assert_eq!(program("strict_sh").command[1..], ["-e"]);
+    assert!(builder.build_files.contains(&root.join("native.ini")));
+
+    let errors = [
+        (
+            "project('p')\nfind_program('reson-missing')",
+            "meson.build:2:1: Program 'reson-missing' not found or not executable",
+        ),
+        (
+            "project('p')\nfind_program('tool', dirs: 'bin', version: '>=3')",
+            "meson.build:2:1: Program 'tool' not found or not executable, found 2.0 but need '>=3'",
+        ),
+        (
+            "project('p')\nfind_program('sh')\nmeson.override_find_program('sh', find_program('tool', dirs: 'bin'))",
+            "meson.build:3:7: Tried to override finding of executable \"sh\" which has already been found",
+        ),
+        (
+            "project('p')\nfind_program('reson-missing', required: false).full_path()",
+            "meson.build:2:48: Program 'reson-missing' was not found, so it has no path",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(root.join("meson.build"), contents)?;
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn custom_targets() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::create_dir_all(root.join("gen"))?;
+    fs::write(root.join("main.c"), "")?;
+    fs::write(root.join("gen/proto.in"), "")?;
+    fs::write(root.join("gen/defs.txt"), "")?;
+    fs::write(root.join("gen/gen.sh"), "#!/bin/sh\n")?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('generated', 'c')
+subdir('gen')
+exe = executable('exe', 'main.c', proto)
+both = custom_target(
+    output: ['a.h', 'a.c'],
+    command: [gen, '--out=@OUTDIR@', '@OUTPUT@', '--first=@OUTPUT0@', '@PRIVATE_DIR@/x'],
+    depends: exe,
+    install: true,
+    install_dir: 'include',
+)
+stamp = custom_target('stamp',
+    input: [proto, 'main.c'],
+    output: 'stamp',
+    command: ['sh', '-c', 'touch @OUTPUT@', '@INPUT@', '@INPUT1@', exe],
+    build_always_stale: true,
+)
+paths = [proto.full_path(), both.to_list().length()]
+"#,
+    )?;
+    fs::write(
+        root.join("gen/meson.build"),
+        r#"gen = find_program('gen.sh')
+proto = custom_target(
+    input: 'proto.in',
+    output: '@BASENAME@.c',
+    command: [gen, '@INPUT@', '-o', '@OUTPUT@', '-d', '@DEPFILE@', '-I@CURRENT_SOURCE_DIR@'],
+    depfile: '@PLAINNAME@.d',
+    depend_files: files('defs.txt'),
+    capture: false,
+)
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build);
+    interpreter.interpret()?;
+    let builder = interpreter.builder();
+    let path = |p: PathBuf| p.to_string_lossy().to_string();
+    let source = |p: &str| path(root.join(p));
+    let built = |p: &str| path(build.join(p));
+
+    let proto = &builder.custom_targets[0];
+    assert_eq!(proto.name, "proto.c");
+    assert_eq!(proto.outputs, ["proto.c"]);
+    assert_eq!(proto.depfile.as_deref(), Some("proto.in.d"));
+    assert_eq!(
+        proto.command,
+        [
+            "/bin/sh".to_string(),
+            source("gen/gen.sh"),
+            source("gen/proto.in"),
+            "-o".to_string(),
+            built("gen/proto.c"),
+            "-d".to_string(),
+            built("gen/proto.in.d"),
+            format!("-I{}", source("gen")),
+        ]
+    );
+    assert!(!proto.build_by_default);
+
+    let exe = &builder.build_targets[0];
+    assert!(exe
+        .files
+        .contains(&File::built(Path::new("gen"), Path::new("proto.c"))));
+
+    let both = &builder.custom_targets[1];
+    assert_eq!(
+        both.command[2..],
+        [
+            format!("--out={}", built("")),
+            built("a.h"),
+            built("a.c"),
+            format!("--first={}", built("a.h")),
+            format!("{}/x", built("a.h.p")),
+        ]
+    );
+    assert_eq!(both.depends, [TargetId::Build(0)]);
+    assert!(both.install && both.build_by_default);
+
+    let stamp = &builder.custom_targets[2];
+    assert_eq!(
+        stamp.command[1..],
+        [
+            "-c".to_string(),
+            format!("touch {}", built("stamp")),
+            built("gen/proto.c"),
+            source("main.c"),
+            source("main.c"),
+            built("exe"),
+        ]
+    );
+    assert_eq!(stamp.depends, [TargetId::Custom(0), TargetId::Build(0)]);
+    assert!(stamp.build_always_stale);
+
+    assert_eq!(
+        interpreter.variable("paths").unwrap(),
+        &Value::Array(vec![Value::String(built("gen/proto.c")), Value::Number(2),])
+    );
+
+    let errors = [
+        (
+            "custom_target(output: ['a', 'b'], command: ['sh'], capture: true)",
+            "Capturing can only output to a single file",
+        ),
+        (
+            "custom_target(output: 'sub/a', command: ['sh'])",
+            "Output 'sub/a' must be a file name, not a path",
+        ),
+        (
+            "custom_target(input: ['main.c', 'main.c'], output: 'a', command: ['sh', '-o@INPUT@'])",
+            "Command has '@INPUT@' as part of a string and 2 input files",
+        ),
+        (
+            "custom_target(output: ['a'], command: ['sh', '@OUTPUT2@'])",
+            "Index 2 out of bounds of output array of size 1",
+        ),
+        (
+            "custom_target(output: 'a', command: ['sh', '@DEPFILE@'])",
+            "Custom target uses @DEPFILE@ but no depfile keyword argument",
+        ),
+        (
+            "custom_target(output: 'a', command: ['reson-missing'])",
+            "Program 'reson-missing' not found or not executable",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn generators() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::create_dir_all(root.join("proto/sub"))?;
+    fs::write(root.join("main.c"), "")?;
+    fs::write(root.join("proto/a.proto"), "")?;
+    fs::write(root.join("proto/sub/b.proto"), "")?;
+    fs::write(root.join("protoc.sh"), "#!/bin/sh\n")?;
+    fs::write(
+        root.join("meson.build"),
+        format!(
+            r#"project('generated', 'c')
+protoc = find_program('protoc.sh')
+gen = generator(protoc,
+    output: ['@BASENAME@.pb.c', '@BASENAME@.pb.h'],
+    arguments: ['--out=@BUILD_DIR@', '@EXTRA_ARGS@', '@INPUT@', '-d', '@DEPFILE@'],
+    depfile: '@PLAINNAME@.d',
+)
+protos = gen.process('proto/a.proto', files('proto/sub/b.proto'),
+    extra_args: ['--lite', '-v'],
+    preserve_path_from: '{}',
+)
+exe = executable('exe', 'main.c', protos)
+lib = static_library('lib', sources: gen.process('proto/a.proto'))
+copier = generator(exe, output: '@PLAINNAME@', arguments: ['@INPUT@', '@OUTPUT@'])
+copies = executable('copies', copier.process('main.c'))
+"#,
+            root.join("proto").display()
+        ),
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build);
+    interpreter.interpret()?;
+    let builder = interpreter.builder();
+    let path = |p: PathBuf| p.to_string_lossy().to_string();
+    let built = |dir: &str, name: &str| File::built(Path::new(dir), Path::new(name));
+
+    let exe = &builder.build_targets[0];
+    assert_eq!(
+        exe.files[1..],
+        [
+            built("exe.p", "a.pb.c"),
+            built("exe.p", "a.pb.h"),
+            built("exe.p/sub", "b.pb.c"),
+            built("exe.p/sub", "b.pb.h"),
+        ]
+    );
+    let b = &exe.generated[1];
+    assert_eq!(b.depfile, Some(built("exe.p/sub", "b.proto.d")));
+    assert_eq!(
+        b.command,
+        [
+            "/bin/sh".to_string(),
+            path(root.join("protoc.sh")),
+            format!("--out={}", path(build.join("exe.p/sub"))),
+            "--lite".to_string(),
+            "-v".to_string(),
+            path(root.join("proto/sub/b.proto")),
+            "-d".to_string(),
+            path(build.join("exe.p/sub/b.proto.d")),
+        ]
+    );
+
+    // Each target gets its own copy of the outputs
+    let lib = &builder.build_targets[1];
+    assert_eq!(
+        lib.files,
+        [built("liblib.a.p", "a.pb.c"), built("liblib.a.p", "a.pb.h")]
+    );
+    assert_eq!(
+        lib.generated[0].command[3],
+        path(root.join("proto/a.proto"))
+    );
+
+    let copies = &builder.build_targets[2];
+    assert_eq!(copies.files, [built("copies.p", "main.c")]);
+    assert_eq!(
+        copies.generated[0].command,
+        [
+            path(build.join("exe")),
+            path(root.join("main.c")),
+            path(build.join("copies.p/main.c")),
+        ]
+    );
+    assert_eq!(copies.generated[0].depends, [TargetId::Build(0)]);
+
+    let errors = [
+        (
+            "generator(find_program('sh'), output: 'out.c')",
+            "Output 'out.c' of a generator must contain @BASENAME@ or @PLAINNAME@",
+        ),
+        (
+            "generator(find_program('sh'), output: '@BASENAME@.c').process('main.c', preserve_path_from: 'proto')",
+            "preserve_path_from must be an absolute path, got 'proto'",
+        ),
+        (
+            "g = generator(find_program('sh'), output: ['@BASENAME@.c', '@BASENAME@.h'], arguments: ['-o@OUTPUT@'])\nexecutable('e', g.process('main.c'))",
+            "Command has '@OUTPUT@' as part of a string and 2 output files",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn configure_files() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::create_dir_all(root.join("sub"))?;
+    fs::write(
+        root.join("config.h.in"),
+        "#define NAME @NAME@\n#mesondefine HAVE_FOO\n#mesondefine HAVE_BAR\n#mesondefine NOPE\n",
+    )?;
+    fs::write(
+        root.join("sub/cmake.h.in"),
+        "#define VERSION ${VERSION}\n#cmakedefine HAVE_FOO 1\n#cmakedefine01 HAVE_BAR\n",
+    )?;
+    fs::write(root.join("sub/data.txt"), "some data\n")?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('configured', 'c', version: '1.2')
+conf = configuration_data()
+conf.set_quoted('NAME', 'reson')
+conf.set('HAVE_FOO', true, description: 'Foo is there')
+conf.set10('HAVE_BAR', false)
+other = configuration_data({'VERSION': '1.2', 'COUNT': 3})
+conf.merge_from(other)
+checks = [
+    conf.has('HAVE_FOO'),
+    conf.get('MISSING', 'fallback') == 'fallback',
+    conf.get('NAME') == '"reson"',
+    conf.get_unquoted('NAME') == 'reson',
+    conf.get('HAVE_BAR') == 0,
+    conf.keys() == ['COUNT', 'HAVE_BAR', 'HAVE_FOO', 'NAME', 'VERSION'],
+]
+config_h = configure_file(input: 'config.h.in', output: 'config.h', configuration: conf)
+header = configure_file(output: 'header.h', configuration: conf, macro_name: 'HEADER_H')
+subdir('sub')
+"#,
+    )?;
+    fs::write(
+        root.join("sub/meson.build"),
+        r#"cmake_h = configure_file(input: 'cmake.h.in', output: '@BASENAME@', configuration: conf, format: 'cmake')
+copied = configure_file(input: files('data.txt'), output: 'copied.txt', copy: true)
+upper = configure_file(
+    input: 'data.txt',
+    output: 'upper.txt',
+    command: ['sh', '-c', 'tr a-z A-Z < "$0"', '@INPUT@'],
+    capture: true,
+)
+"#,
+    )?;
+
+    let configure = || -> Result<Interpreter> {
+        let mut interpreter = Interpreter::new(root, &build);
+        interpreter.interpret()?;
+        Ok(interpreter)
+    };
+    let interpreter = configure()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+    assert_eq!(
+        interpreter.variable("cmake_h").unwrap(),
+        &Value::File(File::built(Path::new("sub"), Path::new("cmake.h")))
+    );
+    assert!(interpreter
+        .builder()
+        .build_files
+        .contains(&PathBuf::from("sub/cmake.h.in")));
+
+    let read = |path: &str| fs::read_to_string(build.join(path)).unwrap();
+    assert_eq!(
+        read("config.h"),
+        "#define NAME \"reson\"\n#define HAVE_FOO\n#define HAVE_BAR 0\n/* #undef NOPE */\n"
+    );
+    let header = read("header.h");
+    assert!(header.contains("#ifndef HEADER_H\n"));
+    assert!(header.contains("/* Foo is there */\n#define HAVE_FOO\n\n#define NAME \"reson\"\n"));
+    assert_eq!(
+        read("sub/cmake.h"),
+        "#define VERSION 1.2\n#define HAVE_FOO 1\n#define HAVE_BAR 0\n"
+    );
+    assert_eq!(read("sub/copied.txt"), "some data\n");
+    assert_eq!(read("sub/upper.txt"), "SOME DATA\n");
+
+    // Unchanged outputs aren't written again, so nothing using them rebuilds
+    let modified = || {
+        fs::metadata(build.join("config.h"))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let before = modified();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    configure()?;
+    assert_eq!(modified(), before);
+
+    let errors = [
+        (
+            "configure_file(output: 'x.h')",
+            "Must specify an action with one of these keyword arguments: 'configuration', 'command', or 'copy'",
+        ),
+        (
+            "c = configuration_data()\nconfigure_file(output: 'x.h', configuration: c)\nc.set('A', 1)",
+            "Can not set values on configuration object that has been used",
+        ),
+        (
+            "configuration_data().get('A')",
+            "Entry A not in configuration data",
+        ),
+        (
+            "configure_file(input: 'config.h.in', output: 'x.h', configuration: {'HAVE_FOO': true, 'NAME': true})",
+            "Tried to replace variable 'NAME' value with something other than a string or int",
+        ),
+        (
+            "configuration_data().set10('A', 'yes')",
+            "set10() argument 2 was of type \"str\" but should have been \"bool | int\"",
+        ),
+        (
+            "empty = []\nconfigure_file(output: 'x.h', command: [empty, [[]]])",
+            "configure_file() command must not be empty",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn include_directories() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::create_dir_all(root.join("inc"))?;
+    fs::write(root.join("main.c"), "")?;
+    fs::write(root.join("inc/source.h"), "#define SOURCE 1\n")?;
+    fs::write(
+        root.join("inc/meson.build"),
+        "configure_file(output: 'generated.h', configuration: {'GENERATED': 1})\n",
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('includes', 'c')
+subdir('inc')
+inc = include_directories('inc', is_system: true)
+cc = meson.get_compiler('c')
+checks = [
+    cc.has_header('source.h', include_directories: inc),
+    cc.has_header('generated.h', include_directories: inc),
+    cc.has_header('source.h', include_directories: 'inc'),
+    cc.has_header('source.h', dependencies: declare_dependency(include_directories: inc)),
+]
+missing = cc.has_header('source.h')
+exe = executable('exe', 'main.c', include_directories: [inc, '.'])
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build);
+    interpreter.interpret()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+    assert_eq!(
+        interpreter.variable("missing").unwrap(),
+        &Value::Boolean(false)
+    );
+    let exe = &interpreter.builder().build_targets[0];
+    assert_eq!(
+        exe.include_directories,
+        [
+            IncludeDirectories {
+                subdir: "".into(),
+                dirs: vec!["inc".into()],
+                is_system: true,
+            },
+            IncludeDirectories {
+                subdir: "".into(),
+                dirs: vec![".".into()],
+                is_system: false,
+            },
+        ]
+    );
+
+    let errors = [
+        (
+            "include_directories('nope')".to_string(),
+            "File nope does not exist".to_string(),
+        ),
+        (
+            format!("include_directories('{}')", root.join("inc").display()),
+            format!(
+                "Tried to form an absolute path to {} in the source tree, use a path relative to the current directory instead",
+                root.join("inc").display()
+            ),
+        ),
+        (
+            "executable('e', 'main.c', include_directories: 'nope')".to_string(),
+            "File nope does not exist".to_string(),
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(&expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn messages() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::write(
+        root.join("meson.build"),
+        r#"project('messages', version: '1.2')
+message('text', 1, ['a', true], {'k': 'v'})
+warning('careful')
+assert(true, 'not shown')
+summary('Prefix', '/usr')
+summary({'Docs': false, 'Tests': true}, section: 'Features', bool_yn: true)
+summary('Languages', ['c', 'cpp'], section: 'Features')
+summary('Inputs', ['a', 'b'], list_sep: ', ')
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build);
+    interpreter.interpret()?;
+    assert_eq!(
+        interpreter.summary().unwrap(),
+        "messages 1.2
+
+  Prefix : /usr
+  Inputs : a, b
+
+  Features
+    Docs      : NO
+    Tests     : YES
+    Languages : c
+                cpp
+"
+    );
+
+    let errors = [
+        ("error('Broken', 2)", "Problem encountered: Broken 2"),
+        ("assert(false)", "Assert failed"),
+        ("assert(1 == 2, 'Numbers')", "Assert failed: Numbers"),
+        (
+            "summary('a', 1)\nsummary('a', 2)",
+            "Summary section '' already has key 'a'",
+        ),
+        (
+            "summary('a')",
+            "summary() takes either a key and a value, or a dictionary",
+        ),
+        (
+            "message(meson)",
+            "Value other than strings, integers, bools, features, dictionaries and lists thereof: meson",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn tests() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build = root.join("build");
+    fs::create_dir_all(root.join("sub"))?;
+    fs::write(root.join("main.c"), "int main(void) { return 0; }\n")?;
+    fs::write(root.join("sub/check.sh"), "exit 0\n")?;
+    fs::write(
+        root.join("sub/meson.build"),
+        "exe = executable('unit', '../main.c')\ntool = executable('tool', '../main.c')\n",
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        format!(
+            r#"project('tested', 'c')
+subdir('sub')
+sh = find_program('sh')
+test('unit', exe, args: ['--fast', files('sub/check.sh')], env: ['A=1', 'B=x=y'],
+     suite: ['fast', 'unit tests'], timeout: 10, priority: 3)
+test('script', sh, args: files('sub/check.sh'), env: {{'C': '2'}}, should_fail: true,
+     is_parallel: false, protocol: 'tap', depends: [exe, tool, exe], workdir: '{}')
+benchmark('bench', exe)
+"#,
+            root.display()
+        ),
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build);
+    interpreter.interpret()?;
+    let tests = &interpreter.builder().tests;
+    let check = root.join("sub/check.sh").display().to_string();
+    let exe = build.join("sub/unit").display().to_string();
+    assert_eq!(tests[0].name, "unit");
+    assert_eq!(
+        tests[0].command,
+        [exe.clone(), "--fast".to_string(), check.clone()]
+    );
+    assert_eq!(
+        tests[0].env,
+        [
+            ("A".to_string(), "1".to_string()),
+            ("B".to_string(), "x=y".to_string())
+        ]
+    );
+    assert_eq!(
+        tests[0].suites,
+        ["tested", "tested:fast", "tested:unit_tests"]
+    );
+    assert_eq!((tests[0].timeout, tests[0].priority), (10, 3));
+    assert!(tests[0].is_parallel && !tests[0].should_fail);
+    assert_eq!(tests[0].depends, [PathBuf::from("sub/unit")]);
+
+    assert_eq!(tests[1].command[1..], [check]);
+    assert_eq!(tests[1].env, [("C".to_string(), "2".to_string())]);
+    assert!(!tests[1].is_parallel && tests[1].should_fail);
+    assert_eq!(tests[1].protocol, Protocol::Tap);
+    assert_eq!(
+        tests[1].depends,
+        [PathBuf::from("sub/unit"), PathBuf::from("sub/tool")]
+    );
+    assert_eq!(tests[1].workdir.as_deref(), Some(root));
+    assert_eq!(tests[1].timeout, 30);
+
+    assert!(tests[2].is_benchmark && !tests[2].is_parallel);
+    assert_eq!(tests[2].command, [exe]);
+
+    let errors = [
+        (
+            "test('t', find_program('sh'), workdir: 'rel')",
+            "workdir must be an absolute path, got 'rel'",
+        ),
+        (
+            "test('t', find_program('sh'), protocol: 'junit')",
+            "Protocol 'junit' is invalid, must be one of exitcode, tap, gtest, rust",
+        ),
+        (
+            "test('t', find_program('sh'), env: ['A'])",
+            "Environment variable 'A' must be of the form NAME=value",
+        ),
+        (
+            "test('t', 'sh')",
+            "test() argument 2 was of type \"str\" but should have been \"build_tgt | custom_tgt | external_program | file\"",
+        ),
+    ];
+    for (contents, expected) in errors {
+        fs::write(
+            root.join("meson.build"),
+            format!("project('p')\n{}", contents),
+        )?;
+        let mut interpreter = Interpreter::new(root, &build);
+        let error = interpreter.interpret().unwrap_err().to_string();
+        assert!(error.ends_with(expected), "{}", error);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn subdirs() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::create_dir_all(root.join("src/lib"))?;
+    fs::write(
+        root.join("meson.build"),
+        "project('subdirs')\nsubdir('src')\nexecutable('exe', 'main.c', link_with: lib)",
+    )?;
+    fs::write(root.join("src/meson.build"), "subdir('lib')")?;
+    fs::write(
+        root.join("src/lib/meson.build"),
+        "lib = static_library('foo', 'foo.c')",
+    )?;
+
+    fs::write(root.join("src/lib/foo.c"), "")?;
+    fs::write(root.join("main.c"), "")?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.interpret()?;
+
+    let targets = &interpreter.builder().build_targets;
+    assert_eq!(
+        targets[0].files[0].relative_path(),
+        PathBuf::from("src/lib/foo.c")
+    );
+    assert_eq!(targets[0].output(), PathBuf::from("src/lib/libfoo.a"));
+    assert_eq!(targets[1].files[0].relative_path(), PathBuf::from("main.c"));
+    assert_eq!(targets[1].link_with, vec![0]);
+
+    let tests = [
+        ("subdir('src')\nsubdir('src')", "meson.build:2:1: Tried to enter directory \"src\", which has already been visited"),
+        ("subdir('.')", "meson.build:1:1: Tried to enter directory \"\", which has already been visited"),
+        ("subdir('..')", "meson.build:1:1: Arguments passed to function don't match with required: Subdir .. must be a relative path inside the current directory"),
+        ("subdir('nope')", "meson.build:1:1: Nonexistent build file 'nope/meson.build'"),
+        ("subdir('src/lib')\nexecutable('exe', sourcse: 'a.c')", "meson.build:2:1: executable() got unknown keyword argument 'sourcse', did you mean \"sources\"?"),
+    ];
+    for (contents, expected) in tests {
+        fs::write(root.join("meson.build"), contents)?;
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    fs::write(root.join("meson.build"), "subdir('src/lib')")?;
+    fs::write(root.join("src/lib/meson.build"), "\nfoo()")?;
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    assert_eq!(
+        interpreter.interpret().unwrap_err().to_string(),
+        "src/lib/meson.build:2:1: Unknown function \"foo\""
+    );
+
+    Ok(())
+}
+
+#[test]
+fn files() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::create_dir_all(root.join("src"))?;
+    fs::write(root.join("src/a.c"), "")?;
+    fs::write(root.join("src/b.c"), "")?;
+    fs::write(root.join("main.c"), "")?;
+    fs::write(
+        root.join("meson.build"),
+        "project('files')\nsubdir('src')\nexecutable('exe', 'main.c', sources: srcs)",
+    )?;
+    fs::write(root.join("src/meson.build"), "srcs = files('a.c', ['b.c'])")?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.interpret()?;
+
+    let files = &interpreter.builder().build_targets[0].files;
+    let paths = files.iter().map(|f| f.relative_path()).collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("main.c"),
+            PathBuf::from("src/a.c"),
+            PathBuf::from("src/b.c")
+        ]
+    );
+    assert!(files.iter().all(|f| !f.is_built));
+
+    fs::write(root.join("src/meson.build"), "\nsrcs = files('a.c', 'c.c')")?;
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    assert_eq!(
+        interpreter.interpret().unwrap_err().to_string(),
+        "src/meson.build:2:8: File src/c.c does not exist"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn options() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    fs::write(
+        root.join("meson.options"),
+        r#"option('name', type: 'string', value: 'reson', description: 'Name')
+option('docs', type: 'boolean', value: false)
+option('backend', type: 'combo', choices: ['x11', 'wayland'])
+option('jobs', type: 'integer', min: 1, max: 16, value: 4)
+option('langs', type: 'array', choices: ['c', 'cpp', 'rust'], value: ['c'])
+option('tests', type: 'feature', value: 'disabled')
+"#,
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('options', default_options: ['default_library=static', 'jobs=8'])
+checks = [
+    get_option('name') == 'reson',
+    get_option('docs') == false,
+    get_option('backend') == 'x11',
+    get_option('jobs') == 8,
+    get_option('langs') == ['c'],
+    get_option('tests').disabled(),
+    get_option('buildtype') == 'debug',
+    get_option('prefix') == '/usr/local',
+    get_option('warning_level') == '1',
+    get_option('b_staticpic'),
+]
+lib = library('foo')
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.interpret()?;
+    let checks = interpreter.variable("checks").unwrap().as_array().unwrap();
+    for (i, check) in checks.iter().enumerate() {
+        assert_eq!(check, &Value::Boolean(true), "Check {} failed", i);
+    }
+    assert_eq!(
+        interpreter.builder().build_targets[0].kind,
+        TargetKind::StaticLibrary
+    );
+
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.set_option_overrides(vec![
+        ("jobs".to_string(), "2".to_string()),
+        ("default_library".to_string(), "shared".to_string()),
+        ("buildtype".to_string(), "release".to_string()),
+    ]);
+    interpreter.interpret()?;
+    let options = &interpreter.builder().options;
+    assert_eq!(options.value("jobs"), Some(&OptionValue::Integer(2)));
+    assert_eq!(options.value("debug"), Some(&OptionValue::Boolean(false)));
+    assert_eq!(
+        interpreter.builder().build_targets[0].kind,
+        TargetKind::SharedLibrary
+    );
+
+    let overrides = [
+        (
+            ("docs", "maybe"),
+            "Value 'maybe' for boolean option 'docs' is invalid",
+        ),
+        (("unknown", "1"), "Unknown option: \"unknown\""),
+        (
+            ("warning_level", "4"),
+            "Value '4' for combo option 'warning_level' is invalid, possible choices are: 0, 1, 2, 3, everything",
+        ),
+    ];
+    for ((name, value), expected) in overrides {
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        interpreter.set_option_overrides(vec![(name.to_string(), value.to_string())]);
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    // Checking overrides re-reads the option file but not meson.build
+    let saved = options.clone();
+    let mut option_file = fs::read_to_string(root.join("meson.options"))?;
+    option_file += "option('extra', type: 'boolean', value: false)\n";
+    fs::write(root.join("meson.options"), option_file)?;
+    fs::write(root.join("meson.build"), "error('interpreted')")?;
+    let mut interpreter = Interpreter::new(root, &root.join("build"));
+    interpreter.set_option_overrides(vec![
+        ("extra".to_string(), "true".to_string()),
+        ("buildtype".to_string(), "minsize".to_string()),
+    ]);
+    interpreter.check_option_overrides(&saved)?;
+    let options = &interpreter.builder().options;
+    assert_eq!(options.value("extra"), Some(&OptionValue::Boolean(true)));
+    assert_eq!(options.value("jobs"), Some(&OptionValue::Integer(4)));
+    assert!(interpreter.builder().build_targets.is_empty());
+    for ((name, value), expected) in overrides {
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        interpreter.set_option_overrides(vec![(name.to_string(), value.to_string())]);
+        let error = interpreter.check_option_overrides(&saved).unwrap_err();
+        assert_eq!(error.to_string(), expected);
+    }
+
+    let tests = [
+        (
+            "option('jobs', type: 'integer', min: 1, value: 0)",
+            "meson.options:1:1: Value '0' for integer option 'jobs' is invalid, must be at least 1",
+        ),
+        (
+            "option('mode', type: 'combo', choices: ['a'], value: 'b')",
+            "meson.options:1:1: Value 'b' for combo option 'mode' is invalid, possible choices are: a",
+        ),
+        (
+            "option('docs', type: 'boolean', value: 'maybe')",
+            "meson.options:1:1: Value 'maybe' for boolean option 'docs' is invalid",
+        ),
+        (
+            "\noption('buildtype', type: 'string')",
+            "meson.options:2:1: Option name buildtype is reserved",
+        ),
+        (
+            "option('docs', type: 'bool')",
+            "meson.options:1:1: Option docs: unknown option type 'bool'",
+        ),
+        (
+            "project('nested')",
+            "meson.options:1:1: Arguments passed to function don't match with required: Only calls to option() are allowed in option files, not project()",
+        ),
+        (
+            "\noption('a', type: 'string',\n  value: $)",
+            "meson.options:3:10: Parse error: Failed to lex $",
+        ),
+        (
+            "option('a', type: 'string')\n  x = 1",
+            "meson.options:2:3: Arguments passed to function don't match with required: Option files may only contain option() calls",
+        ),
+    ];
+    fs::write(root.join("meson.build"), "project('options')")?;
+    for (contents, expected) in tests {
+        fs::write(root.join("meson.options"), contents)?;
+        let mut interpreter = Interpreter::new(root, &root.join("build"));
+        assert_eq!(interpreter.interpret().unwrap_err().to_string(), expected);
+    }
+
+    Ok(())
+}
+
 #[test]
 fn missing_build() {
     let mut interpreter = Interpreter::new(&PathBuf::from("missing/path"), &PathBuf::new());
@@ -87,8 +1831,103 @@ fn compile_test() -> Result<()> {
     let current_dir = current_file.parent().unwrap().join("simple");
     println!("Current directory: {:?}", current_dir);
 
-    let mut interpreter = Interpreter::new(&current_dir, &current_dir.join("build"));
+    let build_dir = tempfile::tempdir()?;
+    let mut interpreter = Interpreter::new(&current_dir, build_dir.path());
     interpreter.interpret()?;
 
+    let compilers = &interpreter.builder().compilers;
+    assert_eq!(compilers.len(), 1);
+    assert_eq!(compilers[0].language, Language::Cpp);
+    assert!(compilers[0].path.exists());
+
+    let test_dir = tempfile::tempdir()?;
+    fs::write(
+        test_dir.path().join("meson.build"),
+        "project('x', ['c', 'fortran'])",
+    )?;
+    let mut interpreter = Interpreter::new(test_dir.path(), &test_dir.path().join("build"));
+    assert_eq!(
+        interpreter.interpret().unwrap_err().to_string(),
+        "meson.build:1:1: Language 'fortran' is not supported, must be one of c, cpp"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn build() -> Result<()> {
+    let test_dir = tempfile::tempdir()?;
+    let root = test_dir.path();
+    let build_dir = root.join("build");
+    fs::create_dir_all(root.join("lib"))?;
+    fs::write(
+        root.join("gen.sh"),
+        "#!/bin/sh\necho \"#define GREETING \\\"$1\\\"\"\n",
+    )?;
+    fs::set_permissions(
+        root.join("gen.sh"),
+        std::os::unix::fs::PermissionsExt::from_mode(0o755),
+    )?;
+    fs::write(
+        root.join("lib/greet.c"),
+        "#include <stdio.h>\n#include \"greeting.h\"\nvoid greet(void) { puts(GREETING); }\n",
+    )?;
+    fs::write(
+        root.join("main.c"),
+        "void greet(void);\nint main(void) { greet(); return 0; }\n",
+    )?;
+    fs::write(
+        root.join("lib/meson.build"),
+        "greet = shared_library('greet', 'greet.c', header, version: '1.2.3')\n",
+    )?;
+    fs::write(
+        root.join("meson.build"),
+        r#"project('built', 'c')
+header = custom_target('header',
+    output: 'greeting.h',
+    command: [find_program('gen.sh'), 'hello'],
+    capture: true,
+    depend_files: 'gen.sh',
+)
+subdir('lib')
+exe = executable('exe', 'main.c', link_with: greet)
+unused = executable('unused', 'main.c', link_with: greet, build_by_default: false)
+"#,
+    )?;
+
+    let mut interpreter = Interpreter::new(root, &build_dir);
+    interpreter.interpret()?;
+    let graph = Graph::new(interpreter.builder()).map_err(anyhow::Error::msg)?;
+    // Custom targets are built by default only when installed, the header
+    // is built because the library uses it
+    assert!(!graph.defaults.contains(&PathBuf::from("greeting.h")));
+    assert!(graph.defaults.contains(&PathBuf::from("lib/libgreet.so.1")));
+    assert!(!graph.defaults.contains(&PathBuf::from("unused")));
+
+    // The header, the library's object, library and its two symlinks, and
+    // the executable's object and executable
+    let built = backend::build(&graph, &build_dir, &graph.defaults).map_err(anyhow::Error::msg)?;
+    assert_eq!(built, 7);
+    assert!(!build_dir.join("unused").exists());
+    let output = std::process::Command::new(build_dir.join("exe")).output()?;
+    assert_eq!(String::from_utf8(output.stdout)?, "hello\n");
+
+    let built = backend::build(&graph, &build_dir, &graph.defaults).map_err(anyhow::Error::msg)?;
+    assert_eq!(built, 0);
+
+    // Changing the generator regenerates the header, which the library's
+    // depfile lists, and relinks what uses the library
+    let modified = fs::metadata(build_dir.join("greeting.h"))?.modified()?;
+    fs::File::options()
+        .append(true)
+        .open(root.join("gen.sh"))?
+        .set_modified(modified + std::time::Duration::from_millis(1))?;
+    let built = backend::build(&graph, &build_dir, &graph.defaults).map_err(anyhow::Error::msg)?;
+    assert_eq!(built, 4);
+
+    let unused = [PathBuf::from("unused")];
+    let built = backend::build(&graph, &build_dir, &unused).map_err(anyhow::Error::msg)?;
+    assert_eq!(built, 2);
+
     Ok(())
 }